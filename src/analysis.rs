@@ -0,0 +1,210 @@
+use core::f32;
+use nih_plug::prelude::util::{db_to_gain, gain_to_db};
+
+use crate::filter::Biquad;
+use crate::TILT_PIVOT_HZ;
+
+/// Lowest and highest pitches the detector will report.
+const MIN_DETECTABLE_FREQ: f32 = 20.0;
+const MAX_DETECTABLE_FREQ: f32 = 1000.0;
+/// Peaks of the normalized autocorrelation within this fraction of the highest one are treated as
+/// equally good candidates, and the one with the shortest period wins. This keeps the detector
+/// from locking onto octaves below the actual pitch.
+const PEAK_THRESHOLD: f32 = 0.9;
+/// Anything below this much self-similarity is not considered pitched at all.
+const MIN_CLARITY: f32 = 0.5;
+
+/// Centers of the octave bands the match EQ compares, half of them on either side of the tilt
+/// pivot.
+pub const MATCH_EQ_BANDS: [f32; 8] = [40.0, 80.0, 160.0, 320.0, 640.0, 1280.0, 2560.0, 5120.0];
+/// Bands this far below the loudest band in both recordings are too quiet to compare.
+const MATCH_EQ_FLOOR_DB: f32 = -60.0;
+
+/// Estimates the fundamental of `samples` using the normalized square difference function (a
+/// normalized form of autocorrelation), returning `None` if nothing periodic was found.
+pub(crate) fn detect_pitch(samples: &[f32], sample_rate: f32) -> Option<f32> {
+    let min_lag = (sample_rate / MAX_DETECTABLE_FREQ) as usize;
+    let max_lag = usize::min(
+        (sample_rate / MIN_DETECTABLE_FREQ) as usize,
+        samples.len() / 2,
+    );
+    if min_lag + 2 >= max_lag {
+        return None;
+    }
+
+    let nsdf = (0..=max_lag)
+        .map(|lag| {
+            let (mut correlation, mut energy) = (0.0, 0.0);
+            for (a, b) in samples.iter().zip(&samples[lag..]) {
+                correlation += a * b;
+                energy += a * a + b * b;
+            }
+            match energy > 0.0 {
+                true => 2.0 * correlation / energy,
+                false => 0.0,
+            }
+        })
+        .collect::<Vec<f32>>();
+
+    let peaks = (min_lag.max(1)..max_lag)
+        .filter(|&lag| nsdf[lag] > 0.0 && nsdf[lag] >= nsdf[lag - 1] && nsdf[lag] > nsdf[lag + 1])
+        .collect::<Vec<usize>>();
+    let highest = peaks.iter().map(|&lag| nsdf[lag]).fold(0.0, f32::max);
+    if highest < MIN_CLARITY {
+        return None;
+    }
+
+    let lag = *peaks
+        .iter()
+        .find(|&&lag| nsdf[lag] >= PEAK_THRESHOLD * highest)?;
+
+    // refine the period by fitting a parabola through the peak and its neighbors
+    let (prev, peak, next) = (nsdf[lag - 1], nsdf[lag], nsdf[lag + 1]);
+    let denominator = prev - 2.0 * peak + next;
+    let offset = match denominator.abs() > f32::EPSILON {
+        true => 0.5 * (prev - next) / denominator,
+        false => 0.0,
+    };
+
+    Some(sample_rate / (lag as f32 + offset))
+}
+
+/// How far below its peak the reference has to fall before its decay is considered over.
+const REFERENCE_DECAY_DB: f32 = -40.0;
+/// Our amp envelope decays along `(1 - t)^2`, so it reaches -40 dB 90% of the way through the
+/// decay stage.
+const DECAY_FRACTION_AT_40DB: f32 = 0.9;
+/// Onset detection for the first cycle, relative to the reference's peak.
+const REFERENCE_ONSET_DB: f32 = -20.0;
+
+/// The gain that brings `reference` to the same RMS level as `hit` over the length they share, or
+/// `None` if either is silent.
+pub(crate) fn level_match(hit: &[f32], reference: &[f32]) -> Option<f32> {
+    let len = usize::min(hit.len(), reference.len());
+    let power = |samples: &[f32]| {
+        samples[..len]
+            .iter()
+            .map(|sample| sample * sample)
+            .sum::<f32>()
+    };
+    let (hit_power, reference_power) = (power(hit), power(reference));
+    match hit_power > 0.0 && reference_power > 0.0 {
+        true => Some((hit_power / reference_power).sqrt()),
+        false => None,
+    }
+}
+
+/// What could be gathered from a recorded reference kick, in terms of our own parameters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReferenceAnalysis {
+    pub start_freq: Option<f32>,
+    pub end_freq: Option<f32>,
+    /// Amp decay time in seconds.
+    pub decay: f32,
+}
+
+pub(crate) fn analyze_reference(samples: &[f32], sample_rate: f32) -> Option<ReferenceAnalysis> {
+    let (peak_index, peak) = samples.iter().map(|sample| sample.abs()).enumerate().fold(
+        (0, 0.0),
+        |(max_index, max), (index, sample)| match sample > max {
+            true => (index, sample),
+            false => (max_index, max),
+        },
+    );
+    if peak == 0.0 {
+        return None;
+    }
+
+    let frame_len = usize::max(1, (0.01 * sample_rate) as usize);
+    let decay_threshold = peak * db_to_gain(REFERENCE_DECAY_DB);
+    let decay_len = samples[peak_index..]
+        .chunks(frame_len)
+        .position(|frame| frame.iter().all(|sample| sample.abs() < decay_threshold))
+        .map(|frame| frame * frame_len)
+        .unwrap_or(samples.len() - peak_index);
+    let decay = decay_len as f32 / sample_rate / DECAY_FRACTION_AT_40DB;
+
+    // the first half cycle after the onset gives a rough idea of where the sweep starts
+    let onset_threshold = peak * db_to_gain(REFERENCE_ONSET_DB);
+    let onset = samples
+        .iter()
+        .position(|sample| sample.abs() >= onset_threshold)?;
+    let mut crossings = (onset + 1..samples.len())
+        .filter(|&i| samples[i - 1].is_sign_negative() != samples[i].is_sign_negative());
+    let start_freq = match (crossings.next(), crossings.next()) {
+        (Some(first), Some(second)) => Some(sample_rate / (2 * (second - first)) as f32),
+        _ => None,
+    };
+
+    // by the time a quarter of the decay has passed, the sweep has usually settled
+    let tail_start = peak_index + decay_len / 4;
+    let tail_end = usize::min(tail_start + (0.5 * sample_rate) as usize, samples.len());
+    let end_freq = match tail_start < tail_end {
+        true => detect_pitch(&samples[tail_start..tail_end], sample_rate),
+        false => None,
+    };
+
+    Some(ReferenceAnalysis {
+        start_freq,
+        end_freq,
+        decay,
+    })
+}
+
+/// How the reference's tonal balance differs from the last hit's.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MatchEq {
+    /// How much louder each of `MATCH_EQ_BANDS` is in the reference, relative to its overall
+    /// level, in decibels. NaN for bands that are too quiet to compare.
+    pub bands: [f32; MATCH_EQ_BANDS.len()],
+    /// The change to the engines' tilt that gets closest to the reference.
+    pub tilt: f32,
+}
+
+pub(crate) fn match_eq(hit: &[f32], reference: &[f32], sample_rate: f32) -> Option<MatchEq> {
+    let hit_bands = band_levels(hit, sample_rate)?;
+    let reference_bands = band_levels(reference, sample_rate)?;
+    let mut bands = [f32::NAN; MATCH_EQ_BANDS.len()];
+    let (mut lows, mut highs) = ((0.0, 0), (0.0, 0));
+    for (i, band) in bands.iter_mut().enumerate() {
+        let (hit_band, reference_band) = (hit_bands[i], reference_bands[i]);
+        if hit_band.max(reference_band) < MATCH_EQ_FLOOR_DB {
+            continue;
+        }
+        *band = reference_band - hit_band;
+        let side = match MATCH_EQ_BANDS[i] < TILT_PIVOT_HZ {
+            true => &mut lows,
+            false => &mut highs,
+        };
+        side.0 += *band;
+        side.1 += 1;
+    }
+    // the tilt boosts one side by as much as it cuts the other
+    let tilt = match (lows, highs) {
+        ((low_sum, low_count), (high_sum, high_count)) if low_count > 0 && high_count > 0 => {
+            high_sum / high_count as f32 - low_sum / low_count as f32
+        }
+        _ => 0.0,
+    };
+    Some(MatchEq { bands, tilt })
+}
+
+/// The level of each of `MATCH_EQ_BANDS` in `samples` relative to the loudest one, in decibels, or
+/// `None` if it's silent.
+fn band_levels(samples: &[f32], sample_rate: f32) -> Option<[f32; MATCH_EQ_BANDS.len()]> {
+    let mut levels = [0.0; MATCH_EQ_BANDS.len()];
+    for (level, center) in levels.iter_mut().zip(MATCH_EQ_BANDS) {
+        let q = f32::consts::FRAC_1_SQRT_2;
+        let mut highpass = Biquad::highpass(center / f32::consts::SQRT_2, q, sample_rate);
+        let mut lowpass = Biquad::lowpass(center * f32::consts::SQRT_2, q, sample_rate);
+        *level = samples
+            .iter()
+            .map(|&sample| lowpass.process(highpass.process(sample)).powi(2))
+            .sum();
+    }
+    let loudest = levels.iter().copied().fold(0.0, f32::max);
+    if loudest <= 0.0 {
+        return None;
+    }
+    Some(levels.map(|level| gain_to_db((level / loudest).sqrt())))
+}
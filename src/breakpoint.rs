@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+/// Most points a breakpoint envelope can hold. Fixed so envelopes can be copied around the audio
+/// thread without allocating.
+pub const MAX_BREAKPOINTS: usize = 16;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    /// Seconds since the note started.
+    pub time: f32,
+    pub value: f32,
+    /// Bends the segment leading up to this point, from -1 (moves quickly at first) to 1 (moves
+    /// slowly at first). 0 is a straight line.
+    #[serde(default)]
+    pub curve: f32,
+}
+
+impl Breakpoint {
+    pub fn new(time: f32, value: f32, curve: f32) -> Self {
+        Self { time, value, curve }
+    }
+}
+
+/// An envelope made of any number of points joined by curved segments, for shapes that don't fit
+/// into AHDSR. Always has at least two points, sorted by time, with the first at time zero.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<Breakpoint>", into = "Vec<Breakpoint>")]
+pub struct BreakpointEnvelope {
+    points: [Breakpoint; MAX_BREAKPOINTS],
+    len: usize,
+}
+
+impl Default for BreakpointEnvelope {
+    fn default() -> Self {
+        Self::from(vec![
+            Breakpoint::new(0.0, 1.0, 0.0),
+            Breakpoint::new(0.04, 0.25, -0.6),
+            Breakpoint::new(0.25, 0.0, 0.0),
+        ])
+    }
+}
+
+impl From<Vec<Breakpoint>> for BreakpointEnvelope {
+    fn from(mut points: Vec<Breakpoint>) -> Self {
+        points.sort_by(|a, b| a.time.total_cmp(&b.time));
+        points.truncate(MAX_BREAKPOINTS);
+        if points.len() < 2 {
+            return Self::default();
+        }
+
+        let mut envelope = Self {
+            points: [Breakpoint::default(); MAX_BREAKPOINTS],
+            len: points.len(),
+        };
+        envelope.points[..points.len()].copy_from_slice(&points);
+        envelope.points[0].time = 0.0;
+        envelope
+    }
+}
+
+impl From<BreakpointEnvelope> for Vec<Breakpoint> {
+    fn from(envelope: BreakpointEnvelope) -> Self {
+        envelope.points().to_vec()
+    }
+}
+
+impl BreakpointEnvelope {
+    pub fn points(&self) -> &[Breakpoint] {
+        &self.points[..self.len]
+    }
+
+    /// When the last point is reached.
+    pub fn duration(&self) -> f32 {
+        self.points()[self.len - 1].time
+    }
+
+    pub fn value_at(&self, time: f32) -> f32 {
+        let points = self.points();
+        let next = points.partition_point(|point| point.time <= time);
+        if next == 0 {
+            return points[0].value;
+        }
+        if next == points.len() {
+            return points[points.len() - 1].value;
+        }
+
+        let (from, to) = (points[next - 1], points[next]);
+        let t = (time - from.time) / (to.time - from.time);
+        from.value + (to.value - from.value) * t.powf(f32::powf(4.0, to.curve))
+    }
+
+    /// Stretches every point in time by `factor`.
+    pub fn scaled(mut self, factor: f32) -> Self {
+        for point in &mut self.points[..self.len] {
+            point.time *= factor;
+        }
+        self
+    }
+
+    /// Adds a point, keeping the points sorted. Returns the index it ended up at, or `None` if the
+    /// envelope is full.
+    pub fn insert(&mut self, point: Breakpoint) -> Option<usize> {
+        if self.len == MAX_BREAKPOINTS {
+            return None;
+        }
+        // nothing can come before the first point
+        let index = self.points()[1..].partition_point(|other| other.time <= point.time) + 1;
+        self.points.copy_within(index..self.len, index + 1);
+        self.points[index] = point;
+        self.len += 1;
+        Some(index)
+    }
+
+    /// Removes a point, unless it's the first one or one of the last two remaining.
+    pub fn remove(&mut self, index: usize) {
+        if index == 0 || index >= self.len || self.len <= 2 {
+            return;
+        }
+        self.points.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+    }
+
+    /// Moves a point, keeping it between its neighbours. The first point always stays at time
+    /// zero.
+    pub fn set(&mut self, index: usize, mut point: Breakpoint) {
+        if index >= self.len {
+            return;
+        }
+        point.time = match index {
+            0 => 0.0,
+            _ => {
+                let min = self.points[index - 1].time;
+                let max = match index + 1 < self.len {
+                    true => self.points[index + 1].time,
+                    false => f32::INFINITY,
+                };
+                point.time.clamp(min, max)
+            }
+        };
+        point.value = point.value.clamp(0.0, 1.0);
+        point.curve = point.curve.clamp(-1.0, 1.0);
+        self.points[index] = point;
+    }
+}
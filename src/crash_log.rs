@@ -0,0 +1,154 @@
+//! Keeps the most recent parameter changes and note events around so they can be written to the
+//! log if the plugin panics, which makes DSP bugs reported by users reproducible. Only built with
+//! the `crash-log` feature.
+
+use nih_plug::prelude::*;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+
+use crate::VoiceParams;
+
+/// Shared by every instance in the process, so it holds a few blocks' worth for each of them.
+const CAPACITY: usize = 256;
+
+static LOG: Mutex<CrashLog> = Mutex::new(CrashLog::new());
+static INSTALL_HOOK: Once = Once::new();
+static NEXT_INSTANCE: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// The instance and block this thread last started processing. Hosts run one block at a time
+    /// on each thread, so this is the one that panicked if the panic came from processing.
+    static CURRENT: Cell<Option<(u64, u64)>> = const { Cell::new(None) };
+}
+
+// entries live in a fixed buffer, so boxing the params would only add allocations on the audio
+// thread
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone, Debug)]
+pub enum Entry {
+    Params(VoiceParams),
+    NoteOn { note: u8, velocity: f32 },
+    NoteOff { note: u8 },
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Record {
+    instance: u64,
+    block: u64,
+    entry: Entry,
+}
+
+struct CrashLog {
+    /// Oldest first starting at `head`.
+    entries: [Option<Record>; CAPACITY],
+    head: usize,
+}
+
+impl CrashLog {
+    const fn new() -> Self {
+        Self {
+            entries: [None; CAPACITY],
+            head: 0,
+        }
+    }
+
+    fn push(&mut self, record: Record) {
+        self.entries[self.head] = Some(record);
+        self.head = (self.head + 1) % CAPACITY;
+    }
+
+    /// Writes out the entries of the instance that panicked, or of every instance if the panic
+    /// didn't come from processing.
+    fn dump(&self, current: Option<(u64, u64)>) {
+        match current {
+            Some((instance, block)) => {
+                nih_error!(
+                    "recent events of instance {instance} before the panic in block {block}:"
+                );
+            }
+            None => {
+                nih_error!("recent events before the panic:");
+            }
+        }
+        let (newer, older) = self.entries.split_at(self.head);
+        let records = older
+            .iter()
+            .chain(newer)
+            .flatten()
+            .filter(|record| current.is_none_or(|(instance, _)| record.instance == instance));
+        for record in records {
+            let description = match record.entry {
+                Entry::Params(params) => format!("{params:?}"),
+                Entry::NoteOn { note, velocity } => {
+                    format!("note on {note} at velocity {velocity}")
+                }
+                Entry::NoteOff { note } => format!("note off {note}"),
+            };
+            nih_error!(
+                "  instance {}, block {}: {description}",
+                record.instance,
+                record.block
+            );
+        }
+    }
+}
+
+/// Dumps the log when any thread panics. Safe to call more than once.
+pub fn install() {
+    INSTALL_HOOK.call_once(|| {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // the panic might have happened while the log was locked
+            if let Ok(log) = LOG.try_lock() {
+                log.dump(CURRENT.with(Cell::get));
+            }
+            previous_hook(info);
+        }));
+    });
+}
+
+/// One plugin instance's side of the log, which tags everything it records so instances sharing
+/// the process can be told apart.
+#[derive(Debug)]
+pub struct Recorder {
+    instance: u64,
+    block: u64,
+    last_params: Option<VoiceParams>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self {
+            instance: NEXT_INSTANCE.fetch_add(1, Ordering::Relaxed),
+            block: 0,
+            last_params: None,
+        }
+    }
+}
+
+impl Recorder {
+    /// Starts a new block, recording `params` if they changed since the last one. Never blocks, so
+    /// this is safe to call from the audio thread.
+    pub fn begin_block(&mut self, params: VoiceParams) {
+        self.block += 1;
+        CURRENT.with(|current| current.set(Some((self.instance, self.block))));
+        if self.last_params != Some(params) && self.record(Entry::Params(params)) {
+            self.last_params = Some(params);
+        }
+    }
+
+    /// Returns whether the entry made it in. Never blocks, so this is safe to call from the audio
+    /// thread.
+    pub fn record(&self, entry: Entry) -> bool {
+        let Ok(mut log) = LOG.try_lock() else {
+            return false;
+        };
+        log.push(Record {
+            instance: self.instance,
+            block: self.block,
+            entry,
+        });
+        true
+    }
+}
@@ -0,0 +1,2559 @@
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use nih_plug_egui::{create_egui_editor, egui, widgets, EguiState};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::analysis::{ReferenceAnalysis, MATCH_EQ_BANDS};
+use crate::breakpoint::{Breakpoint, BreakpointEnvelope};
+use crate::generative::{Pattern, Rule, Target, MAX_CYCLE_LEN, MAX_RULES};
+use crate::gesture::Gesture;
+use crate::meter::{ChainNode, HISTORY_BARS, HISTORY_STEPS, HISTORY_STEPS_PER_BAR};
+use crate::midi_monitor::MidiKind;
+use crate::morph::{SceneTable, Snapshot, KIT_SLOTS, SCENE_COUNT};
+use crate::notifications::{Notification, Severity};
+use crate::presets::{self, Preset, FIELDS_GROUP, NON_PATCH_PARAMS, PARAM_GROUPS};
+use crate::settings::{KeyboardSteps, KnobDrag, Settings, Theme};
+use crate::{
+    nearest_note_freq, AhdsrParams, AhdsrValues, EnvelopeMode, KickParams, KickSynth, PitchEnvMode,
+    SharedState, StartFreqMode, Task, VelocityCurve, MORPH_PARAM_ID,
+};
+
+/// Starting points for the amp envelope.
+const AMP_ENV_TEMPLATES: &[(&str, AhdsrValues)] = &[
+    ("Punchy", AhdsrValues::ahdsr(0.0, 0.01, 0.35, 0.0, 0.15)),
+    ("Boomy", AhdsrValues::ahdsr(0.0, 0.02, 1.2, 0.0, 0.6)),
+    ("Tight", AhdsrValues::ahdsr(0.0, 0.0, 0.15, 0.0, 0.05)),
+    ("808 Long", AhdsrValues::ahdsr(0.0, 0.05, 2.5, 0.0, 1.5)),
+];
+/// Starting points for the pitch envelope, matching `AMP_ENV_TEMPLATES`.
+const PITCH_ENV_TEMPLATES: &[(&str, AhdsrValues)] = &[
+    ("Punchy", AhdsrValues::ahdsr(0.0, 0.0, 0.03, 0.0, 0.03)),
+    ("Boomy", AhdsrValues::ahdsr(0.0, 0.0, 0.08, 0.0, 0.08)),
+    ("Tight", AhdsrValues::ahdsr(0.0, 0.0, 0.015, 0.0, 0.015)),
+    ("808 Long", AhdsrValues::ahdsr(0.0, 0.0, 0.05, 0.0, 0.05)),
+];
+
+/// The time span shown by the pitch breakpoint editor.
+const BREAKPOINT_EDITOR_SECONDS: f32 = 1.0;
+
+/// The start of the last hit shown by the scope, long enough to see the transient and the sweep.
+const HIT_SCOPE_SECONDS: f32 = 0.15;
+
+/// How fast the chain view's meters fall back after a peak.
+const CHAIN_METER_FALL_DB_PER_SECOND: f32 = 20.0;
+/// The quietest level the chain view's meters show.
+const CHAIN_METER_FLOOR_DB: f32 = -60.0;
+
+/// Sizes on the performance page, big enough to hit on a touchscreen.
+const PERFORMANCE_KNOB_SIZE: f32 = 72.0;
+const PERFORMANCE_PAD_SIZE: f32 = 96.0;
+const PERFORMANCE_SCENE_WIDTH: f32 = 64.0;
+/// How far a performance knob is dragged to go from one end to the other, at a sensitivity of 1.
+const PERFORMANCE_KNOB_DRAG_PIXELS: f32 = 200.0;
+/// How far one notch of the scroll wheel turns a knob, out of its range.
+const KNOB_SCROLL_STEP: f32 = 0.02;
+/// How far the scroll wheel moves for one notch, in points.
+const SCROLL_LINE_POINTS: f32 = 50.0;
+/// Holding Shift while dragging or scrolling a knob turns it this much slower.
+const KNOB_FINE_SCALE: f32 = 0.1;
+/// The pads shown outside of kit mode, which play the audition note at different velocities.
+const PERFORMANCE_PAD_VELOCITIES: [(&str, f32); 4] = [
+    ("Soft", 0.3),
+    ("Medium", 0.6),
+    ("Hard", 0.85),
+    ("Full", 1.0),
+];
+
+/// How long a parameter picked from the search palette flashes for.
+const PALETTE_FLASH_SECONDS: f64 = 1.5;
+/// Most matches the search palette lists.
+const PALETTE_MATCHES: usize = 12;
+
+/// How long notifications stay on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+/// Clipping is reported at most this often.
+const CLIP_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+/// How long the subsonic warning stays up after the last hit that set it off.
+const SUBSONIC_WARNING_DURATION: Duration = Duration::from_secs(3);
+
+/// How often the user preset directory is checked for changes while the editor is open.
+const USER_PRESET_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) fn default_state() -> Arc<EguiState> {
+    EguiState::from_size(460, 640)
+}
+
+struct EditorState {
+    factory_presets: Vec<Preset>,
+    user_presets: Vec<Preset>,
+    preset_query: String,
+    preset_tag: Option<String>,
+    current_preset: Option<String>,
+    user_dir_input: String,
+    last_user_preset_poll: Option<Instant>,
+    new_preset_name: String,
+    new_preset_author: String,
+    new_preset_tags: String,
+    new_preset_description: String,
+    awaiting_paste: bool,
+    /// The section copied by Copy Section.
+    clipboard_section: &'static str,
+    clipboard_status: Option<String>,
+    /// Notifications along with when they were first shown.
+    toasts: Vec<(Notification, Instant)>,
+    last_clip_warning: Option<Instant>,
+    /// When a hit last ended below the subsonic filter's cutoff.
+    below_subsonic: Option<Instant>,
+    sweep_was_rising: bool,
+    /// A hit frozen in the scope to compare the next ones against, and its sample rate.
+    frozen_hit: Option<(Vec<f32>, f32)>,
+    /// The mod expression being typed, and why it doesn't compile if it doesn't.
+    expression_text: String,
+    expression_error: Option<String>,
+    /// The level shown by each of the chain view's meters, in decibels.
+    chain_levels: [f32; ChainNode::ALL.len()],
+    palette: Option<Palette>,
+    /// What the preset picked in the browser would change, while the changes are being previewed.
+    preset_diff: Option<PresetDiff>,
+}
+
+/// What loading a preset would change, grouped so sections of it can be taken on their own.
+struct PresetDiff {
+    preset_name: String,
+    /// The state `load_preset` would load.
+    target: PluginState,
+    groups: Vec<DiffGroup>,
+}
+
+struct DiffGroup {
+    name: &'static str,
+    selected: bool,
+    changes: Vec<DiffChange>,
+}
+
+struct DiffChange {
+    /// The parameter ID, or the key of a persisted field.
+    id: String,
+    is_field: bool,
+    name: String,
+    /// The current value and the preset's value, formatted for display.
+    from: String,
+    to: String,
+}
+
+/// The parameter search palette, opened with Ctrl+F.
+#[derive(Default)]
+struct Palette {
+    query: String,
+    /// The highlighted match.
+    selected: usize,
+    /// The parameter picked from the list, and the value being typed in for it.
+    picked: Option<(ParamPtr, String)>,
+    invalid: bool,
+}
+
+impl EditorState {
+    fn new() -> Self {
+        Self {
+            factory_presets: presets::factory_presets(),
+            user_presets: Vec::new(),
+            preset_query: String::new(),
+            preset_tag: None,
+            current_preset: None,
+            user_dir_input: String::new(),
+            last_user_preset_poll: None,
+            new_preset_name: String::new(),
+            new_preset_author: String::new(),
+            new_preset_tags: String::new(),
+            new_preset_description: String::new(),
+            awaiting_paste: false,
+            clipboard_section: PARAM_GROUPS[0].0,
+            clipboard_status: None,
+            toasts: Vec::new(),
+            last_clip_warning: None,
+            below_subsonic: None,
+            sweep_was_rising: false,
+            frozen_hit: None,
+            chain_levels: [f32::NEG_INFINITY; ChainNode::ALL.len()],
+            expression_text: String::new(),
+            expression_error: None,
+            palette: None,
+            preset_diff: None,
+        }
+    }
+
+    fn toast(&mut self, severity: Severity, message: String) {
+        self.toasts
+            .push((Notification { severity, message }, Instant::now()));
+    }
+
+    /// Remembers the preset `load_preset` just loaded, and warns about version mismatches.
+    fn preset_loaded(&mut self, (name, mismatch): (String, Option<String>)) {
+        self.current_preset = Some(name);
+        if let Some(mismatch) = mismatch {
+            self.toast(Severity::Warning, mismatch);
+        }
+    }
+
+    fn presets(&self) -> impl Iterator<Item = &Preset> {
+        self.factory_presets.iter().chain(&self.user_presets)
+    }
+}
+
+fn user_preset_dir(params: &KickParams) -> Option<PathBuf> {
+    params.settings.read().unwrap().preset_dir()
+}
+
+fn apply_settings(egui_ctx: &egui::Context, settings: &Settings) {
+    egui_ctx.set_visuals(match settings.theme {
+        Theme::Dark => egui::Visuals::dark(),
+        Theme::Light => egui::Visuals::light(),
+    });
+    egui_ctx.set_pixels_per_point(settings.ui_scale);
+    // the controls don't have the settings at hand
+    egui_ctx.data_mut(|data| data.insert_temp(keyboard_steps_id(), settings.keyboard_steps));
+}
+
+fn keyboard_steps_id() -> egui::Id {
+    egui::Id::new("keyboard_steps")
+}
+
+/// Which way the arrow keys are moving a focused control this frame, from -1 to 1, and whether
+/// Shift is held for the coarse step. Up and right raise it, down and left lower it. Also keeps
+/// the arrow keys from moving the focus away from it.
+fn arrow_keys(ui: &egui::Ui, response: &egui::Response) -> Option<(egui::Vec2, f32)> {
+    if !response.has_focus() {
+        return None;
+    }
+    let ctx = ui.ctx();
+    ctx.memory_mut(|memory| {
+        memory.set_focus_lock_filter(
+            response.id,
+            egui::EventFilter {
+                horizontal_arrows: true,
+                vertical_arrows: true,
+                ..Default::default()
+            },
+        )
+    });
+    let steps: KeyboardSteps = ctx
+        .data(|data| data.get_temp(keyboard_steps_id()))
+        .unwrap_or_default();
+    ctx.input(|input| {
+        let axis = |negative, positive| {
+            input.key_pressed(positive) as i32 as f32 - input.key_pressed(negative) as i32 as f32
+        };
+        let direction = egui::vec2(
+            axis(egui::Key::ArrowLeft, egui::Key::ArrowRight),
+            axis(egui::Key::ArrowDown, egui::Key::ArrowUp),
+        );
+        let step = match input.modifiers.shift {
+            true => steps.coarse,
+            false => steps.fine,
+        };
+        Some((direction, step)).filter(|_| direction != egui::Vec2::ZERO)
+    })
+}
+
+/// Lets the arrow keys nudge a focused parameter control, and tells screen readers what the
+/// control is and what it's set to. Discrete parameters always move at least one value.
+fn keyboard_control<P: Param>(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    param: &P,
+    setter: &ParamSetter,
+) {
+    response.widget_info(|| {
+        egui::WidgetInfo::slider(
+            param.modulated_normalized_value() as f64,
+            format!("{}: {}", param.name(), param),
+        )
+    });
+    let Some((direction, step)) = arrow_keys(ui, response) else {
+        return;
+    };
+    let step = match param.step_count() {
+        Some(count) => f32::max((step * count as f32).round(), 1.0) / count as f32,
+        None => step,
+    };
+    let normalized = param.unmodulated_normalized_value() + (direction.x + direction.y) * step;
+    setter.begin_set_parameter(param);
+    setter.set_parameter_normalized(param, normalized.clamp(0.0, 1.0));
+    setter.end_set_parameter(param);
+}
+
+fn scan_user_presets(
+    params: &KickParams,
+    async_executor: &AsyncExecutor<KickSynth>,
+    state: &mut EditorState,
+    force: bool,
+) {
+    state.last_user_preset_poll = Some(Instant::now());
+    if let Some(dir) = user_preset_dir(params) {
+        async_executor.execute_background(Task::ScanUserPresets { dir, force });
+    }
+}
+
+pub(crate) fn create(
+    params: Arc<KickParams>,
+    shared: Arc<SharedState>,
+    async_executor: AsyncExecutor<KickSynth>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor_state.clone(),
+        EditorState::new(),
+        {
+            let params = params.clone();
+            let async_executor = async_executor.clone();
+            move |egui_ctx, state| {
+                apply_settings(egui_ctx, &params.settings.read().unwrap());
+                state.user_dir_input = user_preset_dir(&params)
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_default();
+                scan_user_presets(&params, &async_executor, state, true);
+            }
+        },
+        move |egui_ctx, setter, state| {
+            let poll_due = state
+                .last_user_preset_poll
+                .is_none_or(|last| last.elapsed() >= USER_PRESET_POLL_INTERVAL);
+            if poll_due {
+                scan_user_presets(&params, &async_executor, state, false);
+            }
+            if let Some(user_presets) = shared.user_presets.lock().unwrap().take() {
+                state.user_presets = user_presets;
+            }
+            let notifications = shared.notifications.take();
+            state.toasts.extend(
+                notifications
+                    .into_iter()
+                    .map(|notification| (notification, Instant::now())),
+            );
+            check_warnings(&params, &shared, state);
+
+            apply_learned_kit_note(&params, &shared);
+
+            // the host can only be handed a new state from the GUI thread. until then the audio
+            // thread plays the program's values on top of the old state
+            let mut program_table = shared.program_table.write().unwrap();
+            let program = shared.requested_program.swap(-1, Ordering::Relaxed);
+            if program >= 0 {
+                *program_table = SceneTable::default();
+            }
+            drop(program_table);
+            if let Ok(program) = usize::try_from(program) {
+                let loaded = state
+                    .presets()
+                    .nth(program)
+                    .map(|preset| load_preset(&params, setter, preset));
+                if let Some(loaded) = loaded {
+                    state.preset_loaded(loaded);
+                }
+            }
+
+            palette(egui_ctx, &params, state, setter);
+
+            if params.settings.read().unwrap().performance_mode {
+                egui::CentralPanel::default().show(egui_ctx, |ui| {
+                    performance_page(ui, &params, &shared, setter);
+                });
+                toasts(egui_ctx, state);
+                return;
+            }
+
+            egui::CentralPanel::default().show(egui_ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.heading("Presets");
+                        if ui.button("Performance").clicked() {
+                            params.settings.write().unwrap().performance_mode = true;
+                        }
+                        if ui.button("Search").on_hover_text("Ctrl+F").clicked() {
+                            state.palette = Some(Palette::default());
+                        }
+                        audition_button(ui, &params, &shared);
+                        solo_toggle(ui, &shared.solo_body, "Solo Body");
+                        solo_toggle(ui, &shared.solo_click, "Solo Click");
+                        cpu_meter(ui, &shared);
+                    });
+                    patch_clipboard_ui(ui, state, setter);
+                    preset_browser(ui, &params, state, setter);
+                    user_preset_ui(ui, &params, &async_executor, state, setter);
+                    morph_ui(ui, &params, state, setter);
+                    scenes_ui(ui, &params, setter);
+                    kit_ui(ui, &params, &shared, setter);
+                    locks_ui(ui, &params);
+                    midi_monitor_ui(ui, &shared);
+                    expression_ui(ui, &params, state, setter);
+                    generative_ui(ui, &params, setter);
+                    chain_view(ui, &params, &shared, state);
+                    peak_history_view(ui, &shared);
+
+                    ui.separator();
+                    egui::Grid::new("global").show(ui, |ui| {
+                        param_row(ui, &params.length, setter);
+                        param_row(ui, &params.output_gain, setter);
+                        param_row(ui, &params.output_trim, setter);
+                        param_row(ui, &params.analog_clip, setter);
+                        param_row(ui, &params.fx_mix, setter);
+                        param_row(ui, &params.dry_output, setter);
+                        param_row(ui, &params.subsonic, setter);
+                        param_row(ui, &params.subsonic_cutoff, setter);
+                        param_row(ui, &params.subsonic_slope, setter);
+                        param_row(ui, &params.subsonic_phase, setter);
+                        subsonic_warning_row(ui, &shared, state);
+                        param_row(ui, &params.multiband, setter);
+                        param_row(ui, &params.multiband_crossover, setter);
+                        param_row(ui, &params.multiband_low_drive, setter);
+                        param_row(ui, &params.multiband_low_level, setter);
+                        param_row(ui, &params.multiband_high_drive, setter);
+                        param_row(ui, &params.multiband_high_level, setter);
+                        param_row(ui, &params.auto_level, setter);
+                        param_row(ui, &params.quantize, setter);
+                        param_row(ui, &params.humanize, setter);
+                        param_row(ui, &params.voice_mode, setter);
+                        param_row(ui, &params.glide, setter);
+                        param_row(ui, &params.legato_pitch_retrigger, setter);
+                        param_row(ui, &params.on_stop, setter);
+                        param_row(ui, &params.accent_mode, setter);
+                        param_row(ui, &params.accent_every, setter);
+                        param_row(ui, &params.accent_level, setter);
+                        param_row(ui, &params.accent_pitch, setter);
+                        param_row(ui, &params.pressure_tune, setter);
+                        param_row(ui, &params.program_change, setter);
+                        param_row(ui, &params.solo_keyswitches, setter);
+                        param_row(ui, &params.keyswitch_octave, setter);
+                        param_row(ui, &params.modwheel_dest, setter);
+                        param_row(ui, &params.modwheel_depth, setter);
+                        param_row(ui, &params.cv_trigger, setter);
+                        param_row(ui, &params.cv_threshold, setter);
+                        param_row(ui, &params.cv_pitch, setter);
+                        param_row(ui, &params.seed, setter);
+                        param_row(ui, &params.reseed_on_play, setter);
+                    });
+
+                    ui.separator();
+                    ui.heading("Velocity");
+                    egui::Grid::new("velocity").show(ui, |ui| {
+                        param_row(ui, &params.fixed_velocity, setter);
+                        param_row(ui, &params.velocity_curve, setter);
+                        param_row(ui, &params.dynamic_range, setter);
+                        param_row(ui, &params.velocity_sweep_depth, setter);
+                        param_row(ui, &params.key_sweep_time, setter);
+                    });
+                    if !params.fixed_velocity.value()
+                        && params.velocity_curve.value() == VelocityCurve::Custom
+                    {
+                        breakpoint_editor(ui, &params.custom_velocity_curve, 1.0);
+                    }
+
+                    ui.separator();
+                    ui.heading("Oscillator");
+                    egui::Grid::new("oscillator").show(ui, |ui| {
+                        param_row(ui, &params.engine, setter);
+                        param_row(ui, &params.engine_b, setter);
+                        param_row(ui, &params.engine_blend, setter);
+                        param_row(ui, &params.engine_a_tune, setter);
+                        param_row(ui, &params.engine_b_tune, setter);
+                        param_row(ui, &params.engine_a_pan, setter);
+                        param_row(ui, &params.engine_b_pan, setter);
+                        param_row(ui, &params.engine_a_tilt, setter);
+                        param_row(ui, &params.engine_b_tilt, setter);
+                        param_row(ui, &params.engine_a_invert, setter);
+                        param_row(ui, &params.engine_b_invert, setter);
+                        param_row(ui, &params.engine_a_delay, setter);
+                        param_row(ui, &params.engine_b_delay, setter);
+                        layer_correlation_row(ui, &params, &shared);
+                        param_row(ui, &params.start_freq_mode, setter);
+                        match params.start_freq_mode.value() {
+                            StartFreqMode::Absolute => param_row(ui, &params.start_freq, setter),
+                            StartFreqMode::Relative => param_row(ui, &params.start_offset, setter),
+                        }
+                        end_freq_rows(ui, &params, setter);
+                        param_row(ui, &params.sweep_end_clamp, setter);
+                        param_row(ui, &params.sweep_floor, setter);
+                        param_row(ui, &params.sweep_law, setter);
+                        param_row(ui, &params.sweep_curve, setter);
+                        if !params.align_peak.value() {
+                            param_row(ui, &params.phase_offset, setter);
+                        }
+                        param_row(ui, &params.align_peak, setter);
+                        param_row(ui, &params.phase_random, setter);
+                        param_row(ui, &params.key_follow_center, setter);
+                        param_row(ui, &params.phase_key_follow, setter);
+                        param_row(ui, &params.level_key_follow, setter);
+                        param_row(ui, &params.voice_clip, setter);
+                        param_row(ui, &params.voice_drive, setter);
+                        param_row(ui, &params.voice_character, setter);
+                        param_row(ui, &params.click, setter);
+                        param_row(ui, &params.attack_click, setter);
+                        param_row(ui, &params.attack_click_tone, setter);
+                        param_row(ui, &params.body_delay, setter);
+                        param_row(ui, &params.body_fade, setter);
+                        param_row(ui, &params.crackle, setter);
+                        param_row(ui, &params.crackle_density, setter);
+                        param_row(ui, &params.crackle_tone, setter);
+                        param_row(ui, &params.reverse, setter);
+                        param_row(ui, &params.reverse_length, setter);
+                        param_row(ui, &params.reverse_trigger, setter);
+                        param_row(ui, &params.reverse_note, setter);
+                        param_row(ui, &params.membrane_tension, setter);
+                        param_row(ui, &params.membrane_damping, setter);
+                        param_row(ui, &params.strike_position, setter);
+                    });
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.heading("Amp Envelope");
+                        if ui.button("Copy to Pitch").clicked() {
+                            copy_envelope(setter, &params.amp_env, &params.pitch_env);
+                        }
+                    });
+                    envelope_templates(ui, &params.amp_env, AMP_ENV_TEMPLATES, setter);
+                    envelope_grid(ui, "amp_env", &params.amp_env, setter);
+                    egui::Grid::new("amp_env_options").show(ui, |ui| {
+                        param_row(ui, &params.anti_click, setter);
+                        param_row(ui, &params.live_envelopes, setter);
+                    });
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.heading("Pitch Envelope");
+                        if ui.button("Copy to Amp").clicked() {
+                            copy_envelope(setter, &params.pitch_env, &params.amp_env);
+                        }
+                    });
+                    egui::Grid::new("pitch_env_mode").show(ui, |ui| {
+                        param_row(ui, &params.pitch_env_mode, setter);
+                    });
+                    match params.pitch_env_mode.value() {
+                        PitchEnvMode::Ahdsr => {
+                            envelope_templates(ui, &params.pitch_env, PITCH_ENV_TEMPLATES, setter);
+                            envelope_grid(ui, "pitch_env", &params.pitch_env, setter);
+                        }
+                        PitchEnvMode::Breakpoint => breakpoint_editor(
+                            ui,
+                            &params.pitch_breakpoints,
+                            BREAKPOINT_EDITOR_SECONDS,
+                        ),
+                    }
+                    egui::Grid::new("pitch_env_link").show(ui, |ui| {
+                        param_row(ui, &params.pitch_lag, setter);
+                        param_row(ui, &params.link_decays, setter);
+                        param_row(ui, &params.pitch_sync, setter);
+                        param_row(ui, &params.pitch_sync_length, setter);
+                    });
+
+                    ui.separator();
+                    ui.heading("Last Hit");
+                    last_hit_grid(ui, &shared);
+                    hit_scope(ui, &shared, state);
+
+                    ui.separator();
+                    reference_ui(ui, &params, &shared, setter);
+
+                    ui.separator();
+                    settings_ui(ui, &params, &async_executor, state);
+                });
+            });
+            toasts(egui_ctx, state);
+        },
+    )
+}
+
+/// A page with nothing but big controls for playing live: knobs for the parameters worth riding,
+/// pads that play hits, and scene buttons. Stays up until it's switched back, even when the editor
+/// is closed.
+fn performance_page(
+    ui: &mut egui::Ui,
+    params: &KickParams,
+    shared: &SharedState,
+    setter: &ParamSetter,
+) {
+    ui.horizontal(|ui| {
+        ui.heading("Performance");
+        if ui.button("Main Page").clicked() {
+            params.settings.write().unwrap().performance_mode = false;
+        }
+    });
+    ui.separator();
+
+    let settings = params.settings.read().unwrap().clone();
+    ui.horizontal_wrapped(|ui| {
+        for param in [
+            &params.morph,
+            &params.length,
+            &params.end_freq,
+            &params.click,
+            &params.voice_drive,
+            &params.fx_mix,
+        ] {
+            performance_knob(ui, param, setter, &settings);
+        }
+    });
+    ui.separator();
+
+    let pad_size = egui::vec2(PERFORMANCE_PAD_SIZE, PERFORMANCE_PAD_SIZE);
+    ui.horizontal_wrapped(|ui| match params.kit_mode.value() {
+        true => {
+            let kit = params.kit.read().unwrap();
+            for slot in 0..KIT_SLOTS {
+                let note =
+                    kit.notes[slot].map_or(params.kit_base_note.value() + slot as i32, i32::from);
+                let pad = egui::Button::new(format!("{}", slot + 1)).min_size(pad_size);
+                if ui.add_enabled(kit.slots[slot].is_some(), pad).clicked() {
+                    play_pad(shared, note, 1.0);
+                }
+            }
+        }
+        false => {
+            let note = params.settings.read().unwrap().audition_note as i32;
+            for (name, velocity) in PERFORMANCE_PAD_VELOCITIES {
+                if ui.add(egui::Button::new(name).min_size(pad_size)).clicked() {
+                    play_pad(shared, note, velocity);
+                }
+            }
+        }
+    });
+    ui.separator();
+
+    let scenes = params.scenes.read().unwrap();
+    ui.horizontal_wrapped(|ui| {
+        for scene in 0..=SCENE_COUNT as i32 {
+            let name = match scene {
+                0 => String::from("Live"),
+                scene => scene.to_string(),
+            };
+            let stored = scene == 0 || scenes.slots[scene as usize - 1].is_some();
+            let button = egui::Button::new(name)
+                .min_size(egui::vec2(
+                    PERFORMANCE_SCENE_WIDTH,
+                    PERFORMANCE_PAD_SIZE / 2.0,
+                ))
+                .selected(params.scene.value() == scene);
+            if ui.add_enabled(stored, button).clicked() {
+                Gesture::new(setter).set(&params.scene, scene);
+            }
+        }
+    });
+}
+
+/// Hands a hit over to the audio thread, the same way the audition button does.
+fn play_pad(shared: &SharedState, note: i32, velocity: f32) {
+    shared.pad_velocity.store(velocity, Ordering::Relaxed);
+    shared.pad_note.store(note, Ordering::Release);
+}
+
+/// A large knob that's dragged or scrolled, and reset with a double click. Holding Shift turns it
+/// finely.
+fn performance_knob(
+    ui: &mut egui::Ui,
+    param: &FloatParam,
+    setter: &ParamSetter,
+    settings: &Settings,
+) {
+    ui.vertical(|ui| {
+        let size = egui::vec2(PERFORMANCE_KNOB_SIZE, PERFORMANCE_KNOB_SIZE);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let (shift, scroll) = ui
+            .ctx()
+            .input(|input| (input.modifiers.shift, input.raw_scroll_delta));
+        let scale = settings.knob_sensitivity
+            * match shift {
+                true => KNOB_FINE_SCALE,
+                false => 1.0,
+            };
+        if response.drag_started() {
+            setter.begin_set_parameter(param);
+        }
+        if response.dragged() {
+            let delta = response.drag_delta();
+            let turn = match (settings.knob_drag, response.interact_pointer_pos()) {
+                (KnobDrag::Circular, Some(pos)) => {
+                    let angle = |pos: egui::Pos2| {
+                        let offset = pos - rect.center();
+                        offset.y.atan2(offset.x)
+                    };
+                    // wrapped so crossing the left side doesn't count as a whole turn
+                    let turned = (angle(pos) - angle(pos - delta) + std::f32::consts::PI)
+                        .rem_euclid(std::f32::consts::TAU)
+                        - std::f32::consts::PI;
+                    turned / (1.5 * std::f32::consts::PI)
+                }
+                _ => (delta.x - delta.y) / PERFORMANCE_KNOB_DRAG_PIXELS,
+            };
+            let normalized = param.modulated_normalized_value() + turn * scale;
+            setter.set_parameter_normalized(param, normalized.clamp(0.0, 1.0));
+        }
+        if response.drag_released() {
+            setter.end_set_parameter(param);
+        }
+        // Shift scrolls sideways on some platforms, so both directions count
+        let notches = (scroll.x + scroll.y) / SCROLL_LINE_POINTS;
+        if response.hovered() && !response.dragged() && notches != 0.0 {
+            let normalized =
+                param.modulated_normalized_value() + notches * KNOB_SCROLL_STEP * scale;
+            setter.begin_set_parameter(param);
+            setter.set_parameter_normalized(param, normalized.clamp(0.0, 1.0));
+            setter.end_set_parameter(param);
+        }
+        if response.double_clicked() {
+            setter.begin_set_parameter(param);
+            setter.set_parameter(param, param.default_plain_value());
+            setter.end_set_parameter(param);
+        }
+        keyboard_control(ui, &response, param, setter);
+
+        // the knob turns through 270 degrees, starting at the bottom left
+        let angle = |normalized: f32| (0.75 + 1.5 * normalized) * std::f32::consts::PI;
+        let point = |normalized: f32, radius: f32| {
+            let angle = angle(normalized);
+            rect.center() + radius * egui::vec2(angle.cos(), angle.sin())
+        };
+        let radius = 0.4 * rect.width();
+        let painter = ui.painter();
+        let arc = |to: f32| {
+            (0..=32)
+                .map(|i| point(to * i as f32 / 32.0, radius))
+                .collect::<Vec<_>>()
+        };
+        painter.add(egui::Shape::line(
+            arc(1.0),
+            egui::Stroke::new(6.0, egui::Color32::from_gray(48)),
+        ));
+        let normalized = param.modulated_normalized_value();
+        painter.add(egui::Shape::line(
+            arc(normalized),
+            egui::Stroke::new(6.0, egui::Color32::LIGHT_GREEN),
+        ));
+        painter.line_segment(
+            [rect.center(), point(normalized, radius)],
+            egui::Stroke::new(3.0, ui.visuals().text_color()),
+        );
+        ui.label(param.name());
+        ui.small(param.to_string());
+    });
+}
+
+/// Plays a single hit, or when latched, holds the audition note until it's pressed again.
+fn audition_button(ui: &mut egui::Ui, params: &KickParams, shared: &SharedState) {
+    let mut latch = params.settings.read().unwrap().audition_latch;
+    match latch {
+        true => {
+            let mut held = shared.audition_held.load(Ordering::Relaxed);
+            if ui.toggle_value(&mut held, "Audition").changed() {
+                shared.audition_held.store(held, Ordering::Relaxed);
+            }
+        }
+        false => {
+            if ui.button("Audition").clicked() {
+                shared.audition_requested.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+    if ui.checkbox(&mut latch, "Latch").changed() {
+        params.settings.write().unwrap().audition_latch = latch;
+        shared.audition_held.store(false, Ordering::Relaxed);
+    }
+}
+
+fn end_freq_rows(ui: &mut egui::Ui, params: &KickParams, setter: &ParamSetter) {
+    ui.label(params.end_freq.name());
+    let slider = ui.add(widgets::ParamSlider::for_param(&params.end_freq, setter));
+    ui.end_row();
+    ui.label(params.snap_end_freq.name());
+    let (toggled, clicked) = ui
+        .horizontal(|ui| {
+            let toggle = ui.add(widgets::ParamSlider::for_param(
+                &params.snap_end_freq,
+                setter,
+            ));
+            (toggle.changed(), ui.button("Snap to Note").clicked())
+        })
+        .inner;
+    ui.end_row();
+
+    let snap_on = params.snap_end_freq.value();
+    if clicked || (snap_on && (toggled || slider.drag_released())) {
+        snap_end_freq(params, setter);
+    }
+}
+
+/// Moves End Freq onto the nearest note through the host, so the change gets recorded.
+fn snap_end_freq(params: &KickParams, setter: &ParamSetter) {
+    let snapped = nearest_note_freq(params.end_freq.value());
+    if snapped != params.end_freq.value() {
+        Gesture::new(setter).set(&params.end_freq, snapped);
+    }
+}
+
+/// How the engines' lows line up, for setting their polarity and delay by ear and eye.
+fn layer_correlation_row(ui: &mut egui::Ui, params: &KickParams, shared: &SharedState) {
+    ui.label("Low Band Correlation");
+    let blend = params.engine_blend.value();
+    ui.horizontal(|ui| {
+        if blend <= 0.0 || blend >= 1.0 {
+            ui.weak("Only one engine is playing");
+            return;
+        }
+        let correlation = shared.layer_correlation.load(Ordering::Relaxed);
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(120.0, 8.0), egui::Sense::hover());
+        let painter = ui.painter();
+        painter.rect_filled(rect, 1.0, egui::Color32::from_gray(24));
+        let x = rect.center().x + 0.5 * correlation.clamp(-1.0, 1.0) * rect.width();
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(f32::min(x, rect.center().x), rect.top()),
+            egui::pos2(f32::max(x, rect.center().x), rect.bottom()),
+        );
+        let color = match correlation {
+            correlation if correlation < 0.0 => egui::Color32::LIGHT_RED,
+            correlation if correlation < 0.5 => egui::Color32::YELLOW,
+            _ => egui::Color32::LIGHT_GREEN,
+        };
+        painter.rect_filled(bar, 1.0, color);
+        ui.label(format!("{correlation:+.2}"))
+            .on_hover_text("1 when the engines' lows add up, -1 when they cancel out");
+    });
+    ui.end_row();
+}
+
+fn solo_toggle(ui: &mut egui::Ui, solo: &AtomicBool, text: &str) {
+    let mut on = solo.load(Ordering::Relaxed);
+    if ui.toggle_value(&mut on, text).changed() {
+        solo.store(on, Ordering::Relaxed);
+    }
+}
+
+fn param_row<P: Param>(ui: &mut egui::Ui, param: &P, setter: &ParamSetter) {
+    let label = ui
+        .add(egui::Label::new(param.name()).sense(egui::Sense::click()))
+        .on_hover_text("Double click or press Enter to type in a value");
+    let slider = ui.add(widgets::ParamSlider::for_param(param, setter));
+    keyboard_control(ui, &slider, param, setter);
+    palette_flash(ui, param, &slider, label.rect.union(slider.rect));
+    ui.end_row();
+    value_entry(ui, param, setter, &label);
+}
+
+/// A popup under `anchor` for typing in an exact value, with units like "55 Hz", "35 ms", or "-6 dB".
+/// Opened by double clicking `anchor`, since double clicking the slider resets it.
+fn value_entry<P: Param>(
+    ui: &mut egui::Ui,
+    param: &P,
+    setter: &ParamSetter,
+    anchor: &egui::Response,
+) {
+    let id = anchor.id.with("value_entry");
+    let text_id = id.with("text");
+    let ctx = ui.ctx();
+    let enter = anchor.has_focus() && ctx.input(|input| input.key_pressed(egui::Key::Enter));
+    if anchor.double_clicked() || enter {
+        ctx.data_mut(|data| data.insert_temp(id, (param.to_string(), false)));
+        ctx.memory_mut(|memory| memory.request_focus(text_id));
+    }
+    // the text being typed, and whether it was already rejected
+    let Some((mut text, mut invalid)) = ctx.data(|data| data.get_temp::<(String, bool)>(id)) else {
+        return;
+    };
+
+    let response = egui::Area::new(id)
+        .order(egui::Order::Foreground)
+        .fixed_pos(anchor.rect.left_bottom())
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style())
+                .show(ui, |ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut text)
+                            .id(text_id)
+                            .desired_width(120.0),
+                    );
+                    if invalid {
+                        ui.colored_label(egui::Color32::LIGHT_RED, "Couldn't read that value");
+                    }
+                    response
+                })
+                .inner
+        })
+        .inner;
+
+    if !response.lost_focus() {
+        ctx.data_mut(|data| data.insert_temp(id, (text, invalid)));
+        return;
+    }
+    if ctx.input(|input| input.key_pressed(egui::Key::Enter)) {
+        match param.string_to_normalized_value(&text) {
+            Some(normalized) => {
+                setter.begin_set_parameter(param);
+                setter.set_parameter_normalized(param, normalized);
+                setter.end_set_parameter(param);
+            }
+            None => {
+                invalid = true;
+                ctx.data_mut(|data| data.insert_temp(id, (text, invalid)));
+                ctx.memory_mut(|memory| memory.request_focus(text_id));
+                return;
+            }
+        }
+    }
+    ctx.data_mut(|data| data.remove::<(String, bool)>(id));
+}
+
+fn palette_jump_id() -> egui::Id {
+    egui::Id::new("palette_jump")
+}
+
+/// Points out a parameter picked from the palette. Its row scrolls into view and flashes the next
+/// time it's drawn.
+fn palette_jump(ctx: &egui::Context, param: ParamPtr) {
+    let now = ctx.input(|input| input.time);
+    ctx.data_mut(|data| data.insert_temp(palette_jump_id(), (param, now, false)));
+}
+
+/// Searches the parameters by name. Picking one jumps to it and asks for a value to type in, and
+/// Escape closes the palette at any point.
+fn palette(
+    egui_ctx: &egui::Context,
+    params: &KickParams,
+    state: &mut EditorState,
+    setter: &ParamSetter,
+) {
+    let (open, escape, enter, up, down) = egui_ctx.input(|input| {
+        (
+            input.modifiers.command && input.key_pressed(egui::Key::F),
+            input.key_pressed(egui::Key::Escape),
+            input.key_pressed(egui::Key::Enter),
+            input.key_pressed(egui::Key::ArrowUp),
+            input.key_pressed(egui::Key::ArrowDown),
+        )
+    });
+    if open {
+        state.palette = Some(Palette::default());
+    }
+    let Some(palette) = &mut state.palette else {
+        return;
+    };
+
+    let mut close = escape;
+    let mut picked = None;
+    egui::Area::new("palette")
+        .order(egui::Order::Foreground)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 24.0))
+        .show(egui_ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_width(240.0);
+                match &mut palette.picked {
+                    None => {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut palette.query)
+                                .hint_text("Search parameters")
+                                .desired_width(f32::INFINITY),
+                        )
+                        .request_focus();
+                        let query = palette.query.to_lowercase();
+                        // SAFETY: the pointers in the param map live as long as `params` does
+                        let matches: Vec<_> = params
+                            .param_map()
+                            .into_iter()
+                            .map(|(_, ptr, _)| ptr)
+                            .filter(|ptr| unsafe { ptr.name() }.to_lowercase().contains(&query))
+                            .take(PALETTE_MATCHES)
+                            .collect();
+                        palette.selected = match (up, down) {
+                            (true, false) => palette.selected.saturating_sub(1),
+                            (false, true) => palette.selected + 1,
+                            _ => palette.selected,
+                        }
+                        .min(matches.len().saturating_sub(1));
+                        for (index, ptr) in matches.iter().enumerate() {
+                            // SAFETY: as above
+                            let (name, value) = unsafe {
+                                let value = ptr.normalized_value_to_string(
+                                    ptr.unmodulated_normalized_value(),
+                                    true,
+                                );
+                                (ptr.name(), value)
+                            };
+                            let selected = index == palette.selected;
+                            let label = ui.selectable_label(selected, format!("{name}: {value}"));
+                            if label.clicked() {
+                                picked = Some(*ptr);
+                            }
+                        }
+                        if matches.is_empty() {
+                            ui.weak("No parameters match");
+                        } else if enter {
+                            picked = matches.get(palette.selected).copied();
+                        }
+                    }
+                    Some((ptr, text)) => {
+                        // SAFETY: as above
+                        ui.label(unsafe { ptr.name() });
+                        ui.add(egui::TextEdit::singleline(text).desired_width(f32::INFINITY))
+                            .request_focus();
+                        if palette.invalid {
+                            ui.colored_label(egui::Color32::LIGHT_RED, "Couldn't read that value");
+                        }
+                        if enter {
+                            // SAFETY: as above
+                            match unsafe { ptr.string_to_normalized_value(text) } {
+                                Some(normalized) => unsafe {
+                                    setter.raw_context.raw_begin_set_parameter(*ptr);
+                                    setter
+                                        .raw_context
+                                        .raw_set_parameter_normalized(*ptr, normalized);
+                                    setter.raw_context.raw_end_set_parameter(*ptr);
+                                    close = true;
+                                },
+                                None => palette.invalid = true,
+                            }
+                        }
+                    }
+                }
+            });
+        });
+
+    if let Some(ptr) = picked {
+        // SAFETY: as above
+        let value =
+            unsafe { ptr.normalized_value_to_string(ptr.unmodulated_normalized_value(), false) };
+        palette.picked = Some((ptr, value));
+        palette_jump(egui_ctx, ptr);
+    }
+    if close {
+        // the row flashes again once the palette is out of the way, and takes the focus
+        if let Some((ptr, _)) = palette.picked {
+            palette_jump(egui_ctx, ptr);
+        }
+        state.palette = None;
+    }
+}
+
+/// Scrolls to and flashes `row` when its parameter was just picked from the palette, and focuses
+/// its slider so the arrow keys work on it.
+fn palette_flash<P: Param>(ui: &egui::Ui, param: &P, slider: &egui::Response, row: egui::Rect) {
+    let ctx = ui.ctx();
+    let Some((target, since, shown)) =
+        ctx.data(|data| data.get_temp::<(ParamPtr, f64, bool)>(palette_jump_id()))
+    else {
+        return;
+    };
+    if target != param.as_ptr() {
+        return;
+    }
+    let age = ctx.input(|input| input.time) - since;
+    if age > PALETTE_FLASH_SECONDS {
+        ctx.data_mut(|data| data.remove::<(ParamPtr, f64, bool)>(palette_jump_id()));
+        return;
+    }
+    if !shown {
+        ui.scroll_to_rect(row, Some(egui::Align::Center));
+        slider.request_focus();
+        ctx.data_mut(|data| data.insert_temp(palette_jump_id(), (target, since, true)));
+    }
+    let fade = 1.0 - (age / PALETTE_FLASH_SECONDS) as f32;
+    ui.painter().rect_stroke(
+        row.expand(2.0),
+        2.0,
+        egui::Stroke::new(2.0, egui::Color32::YELLOW.gamma_multiply(fade)),
+    );
+    ctx.request_repaint();
+}
+
+fn envelope_grid(ui: &mut egui::Ui, id: &str, env: &AhdsrParams, setter: &ParamSetter) {
+    egui::Grid::new(id).show(ui, |ui| {
+        param_row(ui, &env.mode, setter);
+        param_row(ui, &env.attack_time, setter);
+        param_row(ui, &env.hold_time, setter);
+        param_row(ui, &env.decay_time, setter);
+        // one-shot envelopes never reach either of these
+        if env.mode.value() == EnvelopeMode::Gated {
+            param_row(ui, &env.sustain_level, setter);
+            param_row(ui, &env.release_time, setter);
+        }
+    });
+}
+
+/// Edits the envelope behind `envelope_lock`, showing the first `span` units of time.
+fn breakpoint_editor(ui: &mut egui::Ui, envelope_lock: &RwLock<BreakpointEnvelope>, span: f32) {
+    let mut envelope = *envelope_lock.read().unwrap();
+    let (response, painter) = ui.allocate_painter(
+        egui::vec2(ui.available_width(), 140.0),
+        egui::Sense::click(),
+    );
+    let response = response.on_hover_text(
+        "Drag points to move them, and shift-drag to bend the segment leading up to them. Double \
+         click to add a point, right click to remove one. Focused points move with the arrow keys \
+         and are removed with Delete.",
+    );
+    let rect = response.rect;
+    let to_screen = |time: f32, value: f32| {
+        egui::pos2(
+            rect.left() + time / span * rect.width(),
+            rect.bottom() - value * rect.height(),
+        )
+    };
+
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(24));
+    let curve = (0..=200)
+        .map(|i| {
+            let time = i as f32 / 200.0 * span;
+            to_screen(time, envelope.value_at(time))
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        curve,
+        egui::Stroke::new(1.5, egui::Color32::LIGHT_BLUE),
+    ));
+
+    let mut moved = None;
+    let mut removed = None;
+    for (index, point) in envelope.points().iter().enumerate() {
+        let center = to_screen(point.time, point.value);
+        let point_response = ui.interact(
+            egui::Rect::from_center_size(center, egui::Vec2::splat(12.0)),
+            response.id.with(index),
+            egui::Sense::click_and_drag(),
+        );
+        if point_response.dragged() {
+            let delta = point_response.drag_delta();
+            let mut point = *point;
+            match ui.ctx().input(|input| input.modifiers.shift) {
+                true => point.curve -= 2.0 * delta.y / rect.height(),
+                false => {
+                    point.time += delta.x / rect.width() * span;
+                    point.value -= delta.y / rect.height();
+                }
+            }
+            moved = Some((index, point));
+        }
+        point_response.widget_info(|| {
+            egui::WidgetInfo::labeled(
+                egui::WidgetType::Slider,
+                format!(
+                    "Point {}: {:.0} ms, {:.0}%",
+                    index + 1,
+                    point.time * 1000.0,
+                    point.value * 100.0
+                ),
+            )
+        });
+        // arrow keys move a focused point, and shift-arrows bend it, like dragging does
+        if let Some((direction, step)) = arrow_keys(ui, &point_response) {
+            let mut point = *point;
+            match ui.ctx().input(|input| input.modifiers.shift) {
+                true => point.curve += 2.0 * direction.y * step,
+                false => {
+                    point.time += direction.x * step * span;
+                    point.value += direction.y * step;
+                }
+            }
+            moved = Some((index, point));
+        }
+        let delete = point_response.has_focus()
+            && ui.ctx().input(|input| input.key_pressed(egui::Key::Delete));
+        if point_response.secondary_clicked() || delete {
+            removed = Some(index);
+        }
+        painter.circle_filled(center, 4.0, egui::Color32::WHITE);
+    }
+
+    let old_envelope = envelope;
+    if let Some((index, point)) = moved {
+        envelope.set(index, point);
+    }
+    if let Some(index) = removed {
+        envelope.remove(index);
+    }
+    if response.double_clicked() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let time = (pos.x - rect.left()) / rect.width() * span;
+            let value = (rect.bottom() - pos.y) / rect.height();
+            envelope.insert(Breakpoint::new(time, value.clamp(0.0, 1.0), 0.0));
+        }
+    }
+    if envelope != old_envelope {
+        *envelope_lock.write().unwrap() = envelope;
+    }
+}
+
+fn envelope_templates(
+    ui: &mut egui::Ui,
+    env: &AhdsrParams,
+    templates: &[(&str, AhdsrValues)],
+    setter: &ParamSetter,
+) {
+    ui.horizontal_wrapped(|ui| {
+        for (name, values) in templates {
+            if ui.button(*name).clicked() {
+                set_envelope(setter, env, values);
+            }
+        }
+    });
+}
+
+fn set_envelope(setter: &ParamSetter, env: &AhdsrParams, values: &AhdsrValues) {
+    let mut gesture = Gesture::new(setter);
+    gesture.set(&env.attack_time, values.attack);
+    gesture.set(&env.hold_time, values.hold);
+    gesture.set(&env.decay_time, values.decay);
+    gesture.set(&env.sustain_level, values.sustain);
+    gesture.set(&env.release_time, values.release);
+}
+
+fn copy_envelope(setter: &ParamSetter, from: &AhdsrParams, to: &AhdsrParams) {
+    let mut gesture = Gesture::new(setter);
+    for (src, dst) in [
+        (&from.attack_time, &to.attack_time),
+        (&from.hold_time, &to.hold_time),
+        (&from.decay_time, &to.decay_time),
+        (&from.sustain_level, &to.sustain_level),
+        (&from.release_time, &to.release_time),
+    ] {
+        gesture.set(dst, src.unmodulated_plain_value());
+    }
+    gesture.set(&to.mode, from.mode.value());
+}
+
+fn last_hit_grid(ui: &mut egui::Ui, shared: &SharedState) {
+    let measurement = &shared.last_hit;
+    let lufs = measurement.momentary_lufs.load(Ordering::Relaxed);
+    let true_peak = measurement.true_peak_db.load(Ordering::Relaxed);
+    let duration = measurement.duration.load(Ordering::Relaxed);
+    egui::Grid::new("last_hit").show(ui, |ui| {
+        ui.label("Loudness (momentary max)");
+        ui.label(format!("{} LUFS", format_db(lufs)));
+        ui.end_row();
+        ui.label("True Peak");
+        ui.label(format!("{} dBTP", format_db(true_peak)));
+        ui.end_row();
+        ui.label("Duration");
+        ui.label(format!("{duration:.3} s"));
+        ui.end_row();
+        ui.label("Tail Pitch");
+        let tail_pitch = shared.tail_pitch.load(Ordering::Relaxed);
+        match tail_pitch > 0.0 {
+            true => ui.label(formatters::v2s_f32_hz_then_khz_with_note_name(1, true)(
+                tail_pitch,
+            )),
+            false => ui.label("-"),
+        };
+        ui.end_row();
+    });
+}
+
+/// Draws the start of the last hit, with the frozen hit behind it when there is one.
+fn hit_scope(ui: &mut egui::Ui, shared: &SharedState, state: &mut EditorState) {
+    // the audio thread drops its recording rather than waiting, so don't hold on to this
+    let (last_hit, sample_rate) = match shared.last_hit_capture.try_lock() {
+        Ok(capture) => {
+            let len = usize::min(
+                (HIT_SCOPE_SECONDS * capture.sample_rate) as usize,
+                capture.samples.len(),
+            );
+            (capture.samples[..len].to_vec(), capture.sample_rate)
+        }
+        Err(_) => (Vec::new(), 0.0),
+    };
+
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(!last_hit.is_empty(), egui::Button::new("Freeze"))
+            .on_hover_text("Keep this hit in the background to compare the next ones against")
+            .clicked()
+        {
+            state.frozen_hit = Some((last_hit.clone(), sample_rate));
+        }
+        if state.frozen_hit.is_some() && ui.button("Clear Frozen").clicked() {
+            state.frozen_hit = None;
+        }
+    });
+
+    let (response, painter) = ui.allocate_painter(
+        egui::vec2(ui.available_width(), 100.0),
+        egui::Sense::hover(),
+    );
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(24));
+    painter.line_segment(
+        [rect.left_center(), rect.right_center()],
+        egui::Stroke::new(1.0, egui::Color32::from_gray(48)),
+    );
+    let trace = |samples: &[f32], sample_rate: f32, color| {
+        let scope_len = HIT_SCOPE_SECONDS * sample_rate;
+        if samples.is_empty() || scope_len <= 0.0 {
+            return;
+        }
+        let points = samples
+            .iter()
+            .enumerate()
+            .step_by(usize::max(samples.len() / rect.width() as usize, 1))
+            .map(|(i, sample)| {
+                egui::pos2(
+                    rect.left() + i as f32 / scope_len * rect.width(),
+                    rect.center().y - sample.clamp(-1.0, 1.0) * rect.height() / 2.0,
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, color)));
+    };
+    if let Some((frozen, frozen_rate)) = &state.frozen_hit {
+        trace(frozen, *frozen_rate, egui::Color32::from_rgb(200, 120, 60));
+    }
+    trace(&last_hit, sample_rate, egui::Color32::LIGHT_BLUE);
+}
+
+/// Points out hits whose fundamental the subsonic filter is cutting into, for a few seconds after
+/// the last one.
+fn subsonic_warning_row(ui: &mut egui::Ui, shared: &SharedState, state: &mut EditorState) {
+    if shared.below_subsonic.swap(false, Ordering::Relaxed) {
+        state.below_subsonic = Some(Instant::now());
+    }
+    if state
+        .below_subsonic
+        .is_some_and(|last| last.elapsed() < SUBSONIC_WARNING_DURATION)
+    {
+        ui.label("");
+        ui.colored_label(egui::Color32::YELLOW, "The fundamental is below the cutoff");
+        ui.end_row();
+    }
+}
+
+/// The signal flow from the engines out through the effects, with a peak meter at every stage so
+/// it's clear where the level jumps, clips, or goes missing.
+fn chain_view(
+    ui: &mut egui::Ui,
+    params: &KickParams,
+    shared: &SharedState,
+    state: &mut EditorState,
+) {
+    // the peaks are taken even while the view is closed, so it doesn't open on stale ones
+    let dt = ui.ctx().input(|input| input.stable_dt);
+    for (node, level) in ChainNode::ALL.into_iter().zip(&mut state.chain_levels) {
+        let peak = util::gain_to_db(shared.chain_levels.take(node));
+        *level = f32::max(peak, *level - CHAIN_METER_FALL_DB_PER_SECOND * dt);
+    }
+
+    let enabled = |node| match node {
+        ChainNode::EngineA => params.engine_blend.value() < 1.0,
+        ChainNode::EngineB => params.engine_blend.value() > 0.0,
+        ChainNode::Multiband => params.multiband.value(),
+        ChainNode::AnalogClip => params.analog_clip.value() > 0.0,
+        ChainNode::Subsonic => params.subsonic.value(),
+        _ => true,
+    };
+    let node_ui = |ui: &mut egui::Ui, node: ChainNode| {
+        chain_node(
+            ui,
+            node.name(),
+            state.chain_levels[node as usize],
+            enabled(node),
+        );
+    };
+    ui.collapsing("Signal Chain", |ui| {
+        ui.horizontal_wrapped(|ui| {
+            // both engines feed the voices side by side
+            ui.vertical(|ui| {
+                node_ui(ui, ChainNode::EngineA);
+                node_ui(ui, ChainNode::EngineB);
+            });
+            for node in &ChainNode::ALL[2..] {
+                ui.label("→");
+                node_ui(ui, *node);
+            }
+        });
+    });
+}
+
+fn chain_node(ui: &mut egui::Ui, name: &str, level: f32, enabled: bool) {
+    ui.group(|ui| {
+        ui.vertical(|ui| {
+            ui.small(name);
+            let (rect, response) =
+                ui.allocate_exact_size(egui::vec2(60.0, 6.0), egui::Sense::hover());
+            response.widget_info(|| {
+                let level = match enabled {
+                    true => format!("{} dB", format_db(level)),
+                    false => String::from("Off"),
+                };
+                egui::WidgetInfo::labeled(egui::WidgetType::Label, format!("{name}: {level}"))
+            });
+            let painter = ui.painter();
+            painter.rect_filled(rect, 1.0, egui::Color32::from_gray(24));
+            if !enabled {
+                ui.weak("Off");
+                return;
+            }
+            let fill = ((level - CHAIN_METER_FLOOR_DB) / -CHAIN_METER_FLOOR_DB).clamp(0.0, 1.0);
+            let color = match level {
+                level if level > 0.0 => egui::Color32::LIGHT_RED,
+                level if level > -6.0 => egui::Color32::YELLOW,
+                _ => egui::Color32::LIGHT_GREEN,
+            };
+            let bar =
+                egui::Rect::from_min_size(rect.min, egui::vec2(fill * rect.width(), rect.height()));
+            painter.rect_filled(bar, 1.0, color);
+            let level = match level > CHAIN_METER_FLOOR_DB {
+                true => level,
+                false => f32::NEG_INFINITY,
+            };
+            ui.small(format!("{} dB", format_db(level)));
+        });
+    });
+}
+
+/// The output's peaks over the last few bars, with the left channel above the line and the right
+/// channel below it, so uneven hits stand out.
+fn peak_history_view(ui: &mut egui::Ui, shared: &SharedState) {
+    ui.collapsing(format!("Peak History ({HISTORY_BARS} Bars)"), |ui| {
+        let history = &shared.peak_history;
+        let position = history.position();
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width().max(256.0), 80.0),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(24));
+        let step_width = rect.width() / HISTORY_STEPS as f32;
+        let half_height = 0.5 * rect.height();
+        for step in 0..HISTORY_STEPS {
+            let left = rect.left() + step as f32 * step_width;
+            if step % HISTORY_STEPS_PER_BAR == 0 {
+                painter.line_segment(
+                    [
+                        egui::pos2(left, rect.top()),
+                        egui::pos2(left, rect.bottom()),
+                    ],
+                    egui::Stroke::new(1.0, egui::Color32::from_gray(48)),
+                );
+            }
+            for (channel, direction) in [(0, -1.0), (1, 1.0)] {
+                let level = util::gain_to_db(history.peak(step, channel));
+                let fill = ((level - CHAIN_METER_FLOOR_DB) / -CHAIN_METER_FLOOR_DB).clamp(0.0, 1.0);
+                if fill <= 0.0 {
+                    continue;
+                }
+                let color = match level {
+                    level if level > 0.0 => egui::Color32::LIGHT_RED,
+                    level if level > -6.0 => egui::Color32::YELLOW,
+                    _ => egui::Color32::LIGHT_GREEN,
+                };
+                let top = rect.center().y + direction * fill * half_height;
+                painter.rect_filled(
+                    egui::Rect::from_min_max(
+                        egui::pos2(left, f32::min(top, rect.center().y)),
+                        egui::pos2(left + step_width.max(1.0), f32::max(top, rect.center().y)),
+                    ),
+                    0.0,
+                    color,
+                );
+            }
+        }
+        match position {
+            Some(step) => {
+                let x = rect.left() + (step as f32 + 0.5) * step_width;
+                painter.line_segment(
+                    [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                    egui::Stroke::new(1.0, egui::Color32::WHITE),
+                );
+            }
+            None => {
+                ui.weak("Recording starts when the host's transport is playing.");
+            }
+        }
+    });
+}
+
+fn cpu_meter(ui: &mut egui::Ui, shared: &SharedState) {
+    let average = shared.cpu_load.average.load(Ordering::Relaxed);
+    let peak = shared.cpu_load.peak.load(Ordering::Relaxed);
+    ui.label(format!(
+        "CPU {:.1}% (peak {:.1}%)",
+        average * 100.0,
+        peak * 100.0
+    ))
+    .on_hover_text("Time spent processing as a share of the time each block lasts");
+}
+
+fn format_db(value: f32) -> String {
+    match value.is_finite() {
+        true => format!("{value:.1}"),
+        false => String::from("-inf"),
+    }
+}
+
+fn reference_ui(
+    ui: &mut egui::Ui,
+    params: &KickParams,
+    shared: &SharedState,
+    setter: &ParamSetter,
+) {
+    ui.horizontal(|ui| {
+        ui.heading("Reference");
+        let listening = shared.reference_listening.load(Ordering::Relaxed);
+        if ui.selectable_label(listening, "Listen").clicked() {
+            shared
+                .reference_listening
+                .store(!listening, Ordering::Relaxed);
+        }
+        let null_test = shared.null_test.load(Ordering::Relaxed);
+        let response = ui.selectable_label(null_test, "Null Test").on_hover_text(
+            "Play the recorded reference upside down under each hit, matched to the level of \
+                 the last one. The quieter it gets, the closer the patch is.",
+        );
+        if response.clicked() {
+            shared.null_test.store(!null_test, Ordering::Relaxed);
+        }
+        if null_test {
+            let gain = shared.null_test_gain.load(Ordering::Relaxed);
+            ui.label(format!("{} dB", format_db(util::gain_to_db(gain))));
+        }
+    });
+
+    let analysis = *shared.reference_analysis.lock().unwrap();
+    let Some(analysis) = analysis else {
+        ui.label("Play a kick into the sidechain input while listening to match it.");
+        return;
+    };
+
+    let format_freq = formatters::v2s_f32_hz_then_khz_with_note_name(0, true);
+    egui::Grid::new("reference").show(ui, |ui| {
+        ui.label("Start Freq");
+        ui.label(
+            analysis
+                .start_freq
+                .map_or(String::from("-"), |freq| format_freq(freq)),
+        );
+        ui.end_row();
+        ui.label("End Freq");
+        ui.label(
+            analysis
+                .end_freq
+                .map_or(String::from("-"), |freq| format_freq(freq)),
+        );
+        ui.end_row();
+        ui.label("Amp Decay Time");
+        ui.label(format!("{:.2} s", analysis.decay));
+        ui.end_row();
+    });
+    if ui.button("Apply").clicked() {
+        apply_reference(params, setter, &analysis);
+    }
+
+    let match_eq = *shared.match_eq.lock().unwrap();
+    let Some(match_eq) = match_eq else {
+        return;
+    };
+    ui.label("Match EQ")
+        .on_hover_text("How much louder each band is in the reference than in the last hit");
+    egui::Grid::new("match_eq").show(ui, |ui| {
+        for center in MATCH_EQ_BANDS {
+            ui.label(formatters::v2s_f32_hz_then_khz(0)(center));
+        }
+        ui.end_row();
+        for band in match_eq.bands {
+            match band.is_nan() {
+                true => ui.label("-"),
+                false => ui.label(format!("{band:+.1} dB")),
+            };
+        }
+        ui.end_row();
+    });
+    ui.horizontal(|ui| {
+        ui.label(format!("Suggested tilt change: {:+.1} dB", match_eq.tilt));
+        if ui.button("Apply Tilt").clicked() {
+            let mut gesture = Gesture::new(setter);
+            for tilt in [&params.engine_a_tilt, &params.engine_b_tilt] {
+                gesture.set(tilt, tilt.value() + match_eq.tilt);
+            }
+        }
+    });
+}
+
+fn apply_reference(params: &KickParams, setter: &ParamSetter, analysis: &ReferenceAnalysis) {
+    let mut gesture = Gesture::new(setter);
+    if let Some(end_freq) = analysis.end_freq {
+        gesture.set(&params.end_freq, end_freq);
+    }
+    if let Some(start_freq) = analysis.start_freq {
+        match params.start_freq_mode.value() {
+            StartFreqMode::Absolute => gesture.set(&params.start_freq, start_freq),
+            StartFreqMode::Relative => {
+                let end_freq = analysis.end_freq.unwrap_or(params.end_freq.value());
+                let offset = 12.0 * f32::log2(start_freq / end_freq);
+                gesture.set(&params.start_offset, offset);
+            }
+        }
+    }
+    // the decay we measured already includes the length scaling
+    let decay = analysis.decay / params.length.value();
+    gesture.set(&params.amp_env.decay_time, decay);
+}
+
+fn preset_browser(
+    ui: &mut egui::Ui,
+    params: &KickParams,
+    state: &mut EditorState,
+    setter: &ParamSetter,
+) {
+    ui.horizontal(|ui| {
+        ui.label("Search");
+        ui.text_edit_singleline(&mut state.preset_query);
+        let mut preview = params.settings.read().unwrap().preview_preset_changes;
+        if ui
+            .checkbox(&mut preview, "Preview Changes")
+            .on_hover_text("Show what a preset changes before loading it")
+            .changed()
+        {
+            params.settings.write().unwrap().preview_preset_changes = preview;
+        }
+    });
+    ui.horizontal_wrapped(|ui| {
+        if ui
+            .selectable_label(state.preset_tag.is_none(), "all")
+            .clicked()
+        {
+            state.preset_tag = None;
+        }
+        for tag in presets::all_tags(state.presets()) {
+            let selected = state.preset_tag.as_deref() == Some(tag.as_str());
+            if ui.selectable_label(selected, &tag).clicked() {
+                state.preset_tag = match selected {
+                    true => None,
+                    false => Some(tag),
+                };
+            }
+        }
+    });
+
+    egui::ScrollArea::vertical()
+        .id_source("preset_list")
+        .max_height(120.0)
+        .show(ui, |ui| {
+            let preview = params.settings.read().unwrap().preview_preset_changes;
+            let mut loaded = None;
+            let mut diff = None;
+            for preset in state.presets() {
+                if !preset.matches(&state.preset_query, state.preset_tag.as_deref()) {
+                    continue;
+                }
+                let is_current = state.current_preset.as_deref() == Some(preset.name.as_str());
+                let response = ui
+                    .selectable_label(is_current, &preset.name)
+                    .on_hover_text(preset_summary(preset));
+                if response.clicked() {
+                    match preview {
+                        true => diff = Some(preset_diff(params, setter, preset)),
+                        false => loaded = Some(load_preset(params, setter, preset)),
+                    }
+                }
+            }
+            if let Some(loaded) = loaded {
+                state.preset_loaded(loaded);
+            }
+            if diff.is_some() {
+                state.preset_diff = diff;
+            }
+        });
+    preset_diff_ui(ui, params, state, setter);
+}
+
+/// Compares the current state with what loading `preset` would set.
+fn preset_diff(params: &KickParams, setter: &ParamSetter, preset: &Preset) -> PresetDiff {
+    let current = setter.raw_context.get_state();
+    let target = preset_state_to_load(params, setter, preset);
+    let mut groups: Vec<DiffGroup> = PARAM_GROUPS
+        .iter()
+        .map(|(name, _)| *name)
+        .chain([presets::OTHER_GROUP, FIELDS_GROUP])
+        .map(|name| DiffGroup {
+            name,
+            selected: true,
+            changes: Vec::new(),
+        })
+        .collect();
+    let mut add = |group: &str, change: DiffChange| {
+        if let Some(group) = groups.iter_mut().find(|other| other.name == group) {
+            group.changes.push(change);
+        }
+    };
+
+    for (id, ptr, _) in params.param_map() {
+        let Some(to) = target.params.get(&id) else {
+            continue;
+        };
+        let from = current.params.get(&id);
+        if from.is_some_and(|from| presets::same_value(from, to)) {
+            continue;
+        }
+        // SAFETY: the pointers in the param map live as long as `params` does
+        let (name, from, to) = unsafe {
+            let format = |value: &ParamValue| {
+                let plain = match value {
+                    ParamValue::F32(value) => *value,
+                    ParamValue::I32(value) => *value as f32,
+                    ParamValue::Bool(value) => *value as i32 as f32,
+                    ParamValue::String(value) => return value.clone(),
+                };
+                ptr.normalized_value_to_string(ptr.preview_normalized(plain), true)
+            };
+            let from = from.map_or_else(|| String::from("-"), format);
+            (ptr.name().to_owned(), from, format(to))
+        };
+        add(
+            presets::param_group(&id),
+            DiffChange {
+                id,
+                is_field: false,
+                name,
+                from,
+                to,
+            },
+        );
+    }
+    for (key, value) in &target.fields {
+        if current.fields.get(key) == Some(value) {
+            continue;
+        }
+        let name = match key.as_str() {
+            "pitch-breakpoints" => "Pitch Breakpoints",
+            "velocity-curve" => "Custom Velocity Curve",
+            "expression" => "Mod Expression",
+            "generative" => "Generative Rules",
+            key => key,
+        };
+        add(
+            presets::field_group(key),
+            DiffChange {
+                id: key.clone(),
+                is_field: true,
+                name: name.to_owned(),
+                from: String::new(),
+                to: String::from("changed"),
+            },
+        );
+    }
+
+    groups.retain(|group| !group.changes.is_empty());
+    PresetDiff {
+        preset_name: preset.name.clone(),
+        target,
+        groups,
+    }
+}
+
+/// Lists what the previewed preset would change, and loads all of it or only the ticked groups.
+fn preset_diff_ui(
+    ui: &mut egui::Ui,
+    params: &KickParams,
+    state: &mut EditorState,
+    setter: &ParamSetter,
+) {
+    let Some(diff) = &mut state.preset_diff else {
+        return;
+    };
+    let mut close = false;
+    let mut load_all = false;
+    ui.group(|ui| {
+        ui.strong(format!("Changes from {}", diff.preset_name));
+        if diff.groups.is_empty() {
+            ui.label("Nothing would change.");
+        }
+        for group in &mut diff.groups {
+            let text = format!("{} ({})", group.name, group.changes.len());
+            ui.checkbox(&mut group.selected, text);
+            ui.push_id(group.name, |ui| {
+                ui.collapsing("Details", |ui| {
+                    egui::Grid::new("diff").show(ui, |ui| {
+                        for change in &group.changes {
+                            ui.label(&change.name);
+                            match change.is_field {
+                                true => ui.label(&change.to),
+                                false => ui.label(format!("{} → {}", change.from, change.to)),
+                            };
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        }
+        ui.horizontal(|ui| {
+            load_all = ui.button("Load All").clicked();
+            let any_selected = diff.groups.iter().any(|group| group.selected);
+            if ui
+                .add_enabled(any_selected, egui::Button::new("Apply Selected"))
+                .clicked()
+            {
+                let mut state = setter.raw_context.get_state();
+                for change in diff
+                    .groups
+                    .iter()
+                    .filter(|group| group.selected)
+                    .flat_map(|group| &group.changes)
+                {
+                    match change.is_field {
+                        true => {
+                            if let Some(value) = diff.target.fields.get(&change.id) {
+                                state.fields.insert(change.id.clone(), value.clone());
+                            }
+                        }
+                        false => {
+                            if let Some(value) = diff.target.params.get(&change.id) {
+                                state
+                                    .params
+                                    .insert(change.id.clone(), presets::clone_value(value));
+                            }
+                        }
+                    }
+                }
+                setter.raw_context.set_state(state);
+                close = true;
+            }
+            close |= ui.button("Cancel").clicked();
+        });
+    });
+
+    if load_all {
+        let name = diff.preset_name.clone();
+        let preset = state.presets().find(|preset| preset.name == name);
+        let loaded = preset.map(|preset| load_preset(params, setter, preset));
+        if let Some(loaded) = loaded {
+            state.preset_loaded(loaded);
+        }
+        close = true;
+    }
+    if close {
+        state.preset_diff = None;
+    }
+}
+
+fn morph_ui(ui: &mut egui::Ui, params: &KickParams, state: &EditorState, setter: &ParamSetter) {
+    ui.collapsing("Morph", |ui| {
+        egui::Grid::new("morph").show(ui, |ui| {
+            param_row(ui, &params.morph, setter);
+        });
+        let mut slots = params.morph_slots.write().unwrap();
+        let slots = &mut *slots;
+        let mut changed = false;
+        for (label, slot) in [("A", &mut slots.a), ("B", &mut slots.b)] {
+            ui.horizontal(|ui| {
+                ui.label(format!("Slot {label}"));
+                let selected = slot
+                    .as_ref()
+                    .map_or_else(|| String::from("Empty"), |slot| slot.name.clone());
+                egui::ComboBox::from_id_source(("morph_slot", label))
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        for preset in state.presets() {
+                            if ui.selectable_label(false, &preset.name).clicked() {
+                                *slot = Some(Snapshot::from_preset(preset));
+                                changed = true;
+                            }
+                        }
+                    });
+                if slot.is_some() && ui.button("Clear").clicked() {
+                    *slot = None;
+                    changed = true;
+                }
+            });
+        }
+        if changed {
+            slots.update_table(params, MORPH_PARAM_ID);
+        }
+        if slots.table.is_empty() {
+            ui.label("Fill both slots to start morphing.");
+        }
+    });
+}
+
+fn scenes_ui(ui: &mut egui::Ui, params: &KickParams, setter: &ParamSetter) {
+    ui.collapsing("Scenes", |ui| {
+        egui::Grid::new("scene_params").show(ui, |ui| {
+            param_row(ui, &params.scene, setter);
+            param_row(ui, &params.scene_fade, setter);
+            param_row(ui, &params.scene_notes, setter);
+            param_row(ui, &params.scene_base_note, setter);
+        });
+        let mut scenes = params.scenes.write().unwrap();
+        let mut changed = false;
+        egui::Grid::new("scenes").show(ui, |ui| {
+            for scene in 0..SCENE_COUNT {
+                let slot = &mut scenes.slots[scene];
+                ui.label(format!("{}", scene + 1));
+                ui.label(match slot {
+                    Some(_) => "Stored",
+                    None => "Empty",
+                });
+                let store = ui.button("Store").on_hover_text(
+                    "Stores the continuous voice parameters. Switches, modes, and the effects \
+                     keep their live values when the scene is recalled.",
+                );
+                if store.clicked() {
+                    let state = setter.raw_context.get_state();
+                    *slot = Some(Snapshot::from_state(format!("Scene {}", scene + 1), &state));
+                    changed = true;
+                }
+                if slot.is_some() && ui.button("Clear").clicked() {
+                    *slot = None;
+                    changed = true;
+                }
+                ui.end_row();
+            }
+        });
+        if changed {
+            scenes.update_table(params, MORPH_PARAM_ID);
+        }
+    });
+}
+
+/// Hands a note the audio thread learned over to the kit slot that was waiting for it.
+fn apply_learned_kit_note(params: &KickParams, shared: &SharedState) {
+    let Ok(note) = u8::try_from(shared.kit_learned_note.swap(-1, Ordering::Acquire)) else {
+        return;
+    };
+    let slot = shared.kit_learned_slot.swap(-1, Ordering::Relaxed);
+    if let Ok(slot) = usize::try_from(slot) {
+        if let Some(slot_note) = params.kit.write().unwrap().notes.get_mut(slot) {
+            *slot_note = Some(note);
+        }
+    }
+}
+
+fn kit_ui(ui: &mut egui::Ui, params: &KickParams, shared: &SharedState, setter: &ParamSetter) {
+    ui.collapsing("Kit", |ui| {
+        egui::Grid::new("kit_params").show(ui, |ui| {
+            param_row(ui, &params.kit_mode, setter);
+            param_row(ui, &params.kit_base_note, setter);
+        });
+        let format_note = formatters::v2s_i32_note_formatter();
+        let mut kit = params.kit.write().unwrap();
+        let mut changed = false;
+        let learn_slot = shared.kit_learn_slot.load(Ordering::Relaxed);
+        egui::Grid::new("kit").show(ui, |ui| {
+            for slot in 0..KIT_SLOTS {
+                ui.label(format!("{}", slot + 1));
+                let note =
+                    kit.notes[slot].map_or(params.kit_base_note.value() + slot as i32, i32::from);
+                ui.label(format_note(note));
+                let learning = learn_slot == slot as i32;
+                let learn = ui
+                    .selectable_label(learning, "Learn")
+                    .on_hover_text("Play the note that should trigger this slot");
+                if learn.clicked() {
+                    let learn_slot = match learning {
+                        true => -1,
+                        false => slot as i32,
+                    };
+                    shared.kit_learn_slot.store(learn_slot, Ordering::Relaxed);
+                }
+                match kit.notes[slot] {
+                    Some(_) => {
+                        let reset = ui
+                            .small_button("Reset")
+                            .on_hover_text("Go back to the note counting up from Kit Base Note");
+                        if reset.clicked() {
+                            kit.notes[slot] = None;
+                        }
+                    }
+                    None => {
+                        ui.label("");
+                    }
+                }
+                ui.add(
+                    egui::DragValue::new(&mut kit.choke_groups[slot])
+                        .clamp_range(0..=KIT_SLOTS as u8)
+                        .prefix("Choke "),
+                )
+                .on_hover_text("Slots in the same choke group cut each other off. 0 is none.");
+                let patch = &mut kit.slots[slot];
+                ui.label(match patch {
+                    Some(_) => "Stored",
+                    None => "Empty",
+                });
+                if ui.button("Store").clicked() {
+                    let state = setter.raw_context.get_state();
+                    *patch = Some(Snapshot::from_state(format!("Kit {}", slot + 1), &state));
+                    changed = true;
+                }
+                if patch.is_some() && ui.button("Clear").clicked() {
+                    *patch = None;
+                    changed = true;
+                }
+                ui.end_row();
+            }
+        });
+        if changed {
+            kit.update_table(params, MORPH_PARAM_ID);
+        }
+    });
+}
+
+fn locks_ui(ui: &mut egui::Ui, params: &KickParams) {
+    ui.collapsing("Parameter Locks", |ui| {
+        ui.label("Locked parameters keep their values when a preset is loaded.");
+        let mut locked = params.locked_params.write().unwrap();
+        if !locked.is_empty() && ui.button("Unlock All").clicked() {
+            locked.clear();
+        }
+        egui::ScrollArea::vertical()
+            .id_source("locks")
+            .max_height(160.0)
+            .show(ui, |ui| {
+                for (id, ptr, _) in params.param_map() {
+                    let mut is_locked = locked.contains(&id);
+                    // SAFETY: the pointers in the param map live as long as `params` does
+                    let name = unsafe { ptr.name() };
+                    if ui.checkbox(&mut is_locked, name).changed() {
+                        match is_locked {
+                            true => locked.insert(id),
+                            false => locked.remove(&id),
+                        };
+                    }
+                }
+            });
+    });
+}
+
+fn expression_ui(
+    ui: &mut egui::Ui,
+    params: &KickParams,
+    state: &mut EditorState,
+    setter: &ParamSetter,
+) {
+    ui.collapsing("Mod Expression", |ui| {
+        let text_id = ui.make_persistent_id("expression");
+        // picks up expressions from restored states and presets while it isn't being typed in
+        if !ui.ctx().memory(|memory| memory.has_focus(text_id)) {
+            state.expression_text = params.expression.read().unwrap().source.clone();
+        }
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut state.expression_text)
+                .id(text_id)
+                .hint_text("vel * env(pitch) + 0.1 * rand()")
+                .desired_width(320.0),
+        );
+        if response.changed() {
+            let compiled = params
+                .expression
+                .write()
+                .unwrap()
+                .set_source(&state.expression_text);
+            state.expression_error = compiled.err();
+        }
+        if response.lost_focus() {
+            state.expression_error = None;
+        }
+        if let Some(error) = &state.expression_error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+        ui.weak(
+            "Worked out at the start of each hit from vel (0 to 1), note, hit, rand(), \
+             env(amp), and env(pitch), with + - * / ^, min, max, and abs.",
+        );
+        egui::Grid::new("expression").show(ui, |ui| {
+            param_row(ui, &params.expression_dest, setter);
+        });
+    });
+}
+
+fn generative_ui(ui: &mut egui::Ui, params: &KickParams, setter: &ParamSetter) {
+    ui.collapsing("Generative", |ui| {
+        egui::Grid::new("generative_params").show(ui, |ui| {
+            param_row(ui, &params.generative, setter);
+        });
+        let mut generative = params.generative_rules.write().unwrap();
+        for (index, slot) in generative.rules.iter_mut().enumerate() {
+            let Some(rule) = slot else {
+                continue;
+            };
+            let mut remove = false;
+            ui.push_id(index, |ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("target")
+                        .selected_text(rule.target.name())
+                        .show_ui(ui, |ui| {
+                            for target in Target::ALL {
+                                ui.selectable_value(&mut rule.target, target, target.name());
+                            }
+                        });
+                    egui::ComboBox::from_id_source("pattern")
+                        .selected_text(rule.pattern.kind_name())
+                        .show_ui(ui, |ui| {
+                            for (name, pattern) in Pattern::KINDS {
+                                let selected = rule.pattern.kind_name() == name;
+                                if ui.selectable_label(selected, name).clicked() && !selected {
+                                    rule.pattern = pattern;
+                                }
+                            }
+                        });
+                    pattern_ui(ui, &mut rule.pattern);
+                    remove = ui.small_button("Remove").clicked();
+                });
+            });
+            if remove {
+                *slot = None;
+            }
+        }
+        let free = generative.rules.iter_mut().find(|rule| rule.is_none());
+        if ui
+            .add_enabled(free.is_some(), egui::Button::new("Add Rule"))
+            .on_hover_text(format!("Up to {MAX_RULES} rules, whose offsets add up"))
+            .clicked()
+        {
+            if let Some(free) = free {
+                *free = Some(Rule {
+                    target: Target::Tune,
+                    pattern: Pattern::KINDS[0].1,
+                });
+            }
+        }
+    });
+}
+
+/// The settings of one rule's pattern. Offsets are in the units of the rule's target.
+fn pattern_ui(ui: &mut egui::Ui, pattern: &mut Pattern) {
+    match pattern {
+        Pattern::Cycle { values, len } => {
+            for value in &mut values[..*len] {
+                ui.add(egui::DragValue::new(value).speed(0.1).max_decimals(2));
+            }
+            if *len < MAX_CYCLE_LEN && ui.small_button("+").clicked() {
+                values[*len] = 0.0;
+                *len += 1;
+            }
+            if *len > 1 && ui.small_button("-").clicked() {
+                *len -= 1;
+            }
+        }
+        Pattern::DrunkWalk { step, range } => {
+            ui.add(
+                egui::DragValue::new(step)
+                    .clamp_range(0.0..=12.0)
+                    .speed(0.05)
+                    .prefix("Step "),
+            );
+            ui.add(
+                egui::DragValue::new(range)
+                    .clamp_range(0.0..=24.0)
+                    .speed(0.1)
+                    .prefix("Range "),
+            );
+        }
+        Pattern::Euclidean {
+            pulses,
+            steps,
+            rotation,
+            amount,
+        } => {
+            ui.add(
+                egui::DragValue::new(steps)
+                    .clamp_range(1..=32)
+                    .prefix("Steps "),
+            );
+            ui.add(
+                egui::DragValue::new(pulses)
+                    .clamp_range(0..=*steps)
+                    .prefix("Pulses "),
+            );
+            ui.add(
+                egui::DragValue::new(rotation)
+                    .clamp_range(0..=*steps - 1)
+                    .prefix("Rotate "),
+            );
+            ui.add(
+                egui::DragValue::new(amount)
+                    .speed(0.1)
+                    .max_decimals(2)
+                    .prefix("Amount "),
+            );
+        }
+    }
+}
+
+/// The most recent MIDI events, newest at the top. Events are only logged while this is open.
+fn midi_monitor_ui(ui: &mut egui::Ui, shared: &SharedState) {
+    let log = &shared.midi_log;
+    let response = ui.collapsing("MIDI Monitor", |ui| {
+        if ui.button("Clear").clicked() {
+            log.clear();
+        }
+        let format_note = formatters::v2s_i32_note_formatter();
+        let now = Instant::now();
+        egui::ScrollArea::vertical()
+            .id_source("midi_monitor")
+            .max_height(160.0)
+            .show(ui, |ui| {
+                egui::Grid::new("midi_log").striped(true).show(ui, |ui| {
+                    ui.label("Event");
+                    ui.label("Ch");
+                    ui.label("Number");
+                    ui.label("Value");
+                    ui.label("Offset");
+                    ui.label("Age");
+                    ui.end_row();
+                    for entry in log.entries().iter().rev() {
+                        ui.label(entry.kind.name());
+                        ui.label(format!("{}", entry.channel + 1));
+                        ui.label(match (entry.kind, entry.number) {
+                            (
+                                MidiKind::NoteOn | MidiKind::NoteOff | MidiKind::PolyPressure,
+                                Some(note),
+                            ) => format!("{} ({})", format_note(note as i32), note),
+                            (_, Some(number)) => format!("{number}"),
+                            (_, None) => String::new(),
+                        });
+                        ui.label(match entry.value {
+                            Some(value) => format!("{value:.3}"),
+                            None => String::new(),
+                        });
+                        ui.label(format!("{} smp", entry.timing));
+                        ui.label(format!(
+                            "{:.1} s",
+                            now.duration_since(entry.received).as_secs_f32()
+                        ));
+                        ui.end_row();
+                    }
+                });
+            });
+    });
+    log.enabled
+        .store(response.body_returned.is_some(), Ordering::Relaxed);
+}
+
+fn patch_clipboard_ui(ui: &mut egui::Ui, state: &mut EditorState, setter: &ParamSetter) {
+    ui.horizontal(|ui| {
+        if ui.button("Copy Patch").clicked() {
+            state.clipboard_status = match presets::encode_patch(setter.raw_context.get_state()) {
+                Ok(code) => {
+                    ui.ctx().output_mut(|output| output.copied_text = code);
+                    Some(String::from("Copied to clipboard"))
+                }
+                Err(err) => Some(format!("Could not copy patch: {err}")),
+            };
+        }
+
+        // egui can't read the clipboard on its own, so we wait for the host to hand us a paste
+        if ui
+            .selectable_label(state.awaiting_paste, "Paste")
+            .on_hover_text("Paste a patch or a section")
+            .clicked()
+        {
+            state.awaiting_paste = !state.awaiting_paste;
+            state.clipboard_status = match state.awaiting_paste {
+                true => Some(String::from("Press Ctrl+V to paste")),
+                false => None,
+            };
+        }
+        if state.awaiting_paste {
+            let pasted = ui.ctx().input(|input| {
+                input.events.iter().find_map(|event| match event {
+                    egui::Event::Paste(text) => Some(text.clone()),
+                    _ => None,
+                })
+            });
+            if let Some(text) = pasted {
+                state.awaiting_paste = false;
+                state.clipboard_status = match presets::is_section_code(&text) {
+                    true => Some(paste_section(setter, state, &text)),
+                    false => match presets::decode_patch(&text) {
+                        Ok(mut patch) => {
+                            keep_current_params(
+                                setter,
+                                &mut patch,
+                                NON_PATCH_PARAMS.iter().copied(),
+                            );
+                            setter.raw_context.set_state(patch);
+                            state.current_preset = None;
+                            Some(String::from("Pasted patch"))
+                        }
+                        Err(err) => Some(format!("Could not paste patch: {err}")),
+                    },
+                };
+            }
+        }
+
+        if let Some(status) = &state.clipboard_status {
+            ui.label(status);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source("clipboard_section")
+            .selected_text(state.clipboard_section)
+            .show_ui(ui, |ui| {
+                let sections = PARAM_GROUPS.iter().map(|(name, _)| *name);
+                for section in sections.chain([FIELDS_GROUP]) {
+                    ui.selectable_value(&mut state.clipboard_section, section, section);
+                }
+            });
+        if ui.button("Copy Section").clicked() {
+            let section = state.clipboard_section;
+            state.clipboard_status =
+                match presets::encode_section(section, setter.raw_context.get_state()) {
+                    Ok(code) => {
+                        ui.ctx().output_mut(|output| output.copied_text = code);
+                        Some(format!("Copied {section} to clipboard"))
+                    }
+                    Err(err) => Some(format!("Could not copy {section}: {err}")),
+                };
+        }
+    });
+}
+
+/// Overwrites the current values of everything in a copied section, leaving the rest of the patch
+/// alone. Returns the status to show.
+fn paste_section(setter: &ParamSetter, state: &mut EditorState, code: &str) -> String {
+    let section = match presets::decode_section(code) {
+        Ok(section) => section,
+        Err(err) => return format!("Could not paste section: {err}"),
+    };
+    let mut current = setter.raw_context.get_state();
+    for (id, value) in section.state.params {
+        if !NON_PATCH_PARAMS.contains(&id.as_str()) {
+            current.params.insert(id, value);
+        }
+    }
+    current.fields.extend(section.state.fields);
+    setter.raw_context.set_state(current);
+    state.current_preset = None;
+    format!("Pasted {}", section.section)
+}
+
+fn user_preset_ui(
+    ui: &mut egui::Ui,
+    params: &KickParams,
+    async_executor: &AsyncExecutor<KickSynth>,
+    state: &mut EditorState,
+    setter: &ParamSetter,
+) {
+    ui.collapsing("Save Preset", |ui| {
+        egui::Grid::new("save_preset").show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut state.new_preset_name);
+            ui.end_row();
+            ui.label("Author");
+            ui.text_edit_singleline(&mut state.new_preset_author);
+            ui.end_row();
+            ui.label("Tags");
+            ui.text_edit_singleline(&mut state.new_preset_tags);
+            ui.end_row();
+            ui.label("Description");
+            ui.text_edit_multiline(&mut state.new_preset_description);
+            ui.end_row();
+        });
+
+        if ui.button("Save").clicked() {
+            save_preset(params, async_executor, state, setter);
+        }
+    });
+}
+
+fn settings_ui(
+    ui: &mut egui::Ui,
+    params: &KickParams,
+    async_executor: &AsyncExecutor<KickSynth>,
+    state: &mut EditorState,
+) {
+    ui.collapsing("Settings", |ui| {
+        let mut settings = params.settings.read().unwrap().clone();
+        egui::Grid::new("settings").show(ui, |ui| {
+            ui.label("UI Scale");
+            ui.add(egui::Slider::new(&mut settings.ui_scale, 0.5..=2.0));
+            ui.end_row();
+            ui.label("Theme");
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut settings.theme, Theme::Dark, "Dark");
+                ui.selectable_value(&mut settings.theme, Theme::Light, "Light");
+            });
+            ui.end_row();
+            ui.label("Audition Note");
+            ui.add(egui::DragValue::new(&mut settings.audition_note).clamp_range(0..=127));
+            ui.end_row();
+            ui.label("Arrow Key Step");
+            ui.horizontal(|ui| {
+                let steps = &mut settings.keyboard_steps;
+                for (step, prefix) in [(&mut steps.fine, ""), (&mut steps.coarse, "Shift ")] {
+                    let mut percent = *step * 100.0;
+                    let changed = ui
+                        .add(
+                            egui::DragValue::new(&mut percent)
+                                .clamp_range(0.1..=50.0)
+                                .speed(0.1)
+                                .prefix(prefix)
+                                .suffix("%"),
+                        )
+                        .changed();
+                    if changed {
+                        *step = percent / 100.0;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("How far the arrow keys move a focused control, out of its range");
+            ui.end_row();
+            ui.label("Knob Drag");
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut settings.knob_drag, KnobDrag::Linear, "Linear");
+                ui.selectable_value(&mut settings.knob_drag, KnobDrag::Circular, "Circular");
+            });
+            ui.end_row();
+            ui.label("Knob Sensitivity");
+            ui.add(egui::Slider::new(&mut settings.knob_sensitivity, 0.25..=4.0).logarithmic(true));
+            ui.end_row();
+            ui.label("Preset Folder");
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut state.user_dir_input);
+                if ui.button("Set").clicked() {
+                    let dir = state.user_dir_input.trim();
+                    settings.preset_dir = match dir.is_empty() {
+                        true => None,
+                        false => Some(PathBuf::from(dir)),
+                    };
+                }
+            });
+            ui.end_row();
+        });
+
+        if ui.button("Save as Default").clicked() {
+            async_executor.execute_background(Task::SaveSettings(settings.clone()));
+        }
+
+        let old_settings = params.settings.read().unwrap().clone();
+        if settings != old_settings {
+            apply_settings(ui.ctx(), &settings);
+            let dir_changed = settings.preset_dir != old_settings.preset_dir;
+            *params.settings.write().unwrap() = settings;
+            if dir_changed {
+                state.user_presets.clear();
+                scan_user_presets(params, async_executor, state, true);
+            }
+        }
+    });
+}
+
+fn save_preset(
+    params: &KickParams,
+    async_executor: &AsyncExecutor<KickSynth>,
+    state: &mut EditorState,
+    setter: &ParamSetter,
+) {
+    let Some(dir) = user_preset_dir(params) else {
+        state.toast(Severity::Error, String::from("No preset folder is set"));
+        return;
+    };
+
+    let mut preset = Preset::from_state(setter.raw_context.get_state());
+    preset.name = state.new_preset_name.trim().to_owned();
+    preset.author = state.new_preset_author.trim().to_owned();
+    preset.description = state.new_preset_description.clone();
+    preset.tags = state
+        .new_preset_tags
+        .split(',')
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    state.current_preset = Some(preset.name.clone());
+    async_executor.execute_background(Task::SavePreset { preset, dir });
+}
+
+/// Hands `preset` to the host. Returns its name, and a warning if it came from another version.
+fn load_preset(
+    params: &KickParams,
+    setter: &ParamSetter,
+    preset: &Preset,
+) -> (String, Option<String>) {
+    let state = preset_state_to_load(params, setter, preset);
+    setter.raw_context.set_state(state);
+    (preset.name.clone(), preset.version_mismatch())
+}
+
+/// The preset's state with the locked and non-patch parameters kept at their current values.
+fn preset_state_to_load(params: &KickParams, setter: &ParamSetter, preset: &Preset) -> PluginState {
+    let mut state = preset.to_state();
+    let locked = params.locked_params.read().unwrap();
+    let kept = locked.iter().map(String::as_str);
+    keep_current_params(
+        setter,
+        &mut state,
+        kept.chain(NON_PATCH_PARAMS.iter().copied()),
+    );
+    state
+}
+
+/// Overwrites the parameters in `ids` in a state about to be loaded with their current values.
+fn keep_current_params<'a>(
+    setter: &ParamSetter,
+    state: &mut PluginState,
+    ids: impl Iterator<Item = &'a str>,
+) {
+    let mut current = setter.raw_context.get_state();
+    for id in ids {
+        if let Some(value) = current.params.remove(id) {
+            state.params.insert(id.to_owned(), value);
+        }
+    }
+}
+
+/// Turns problems noticed by the audio thread or in the current parameters into notifications.
+fn check_warnings(params: &KickParams, shared: &SharedState, state: &mut EditorState) {
+    if shared.clipped.swap(false, Ordering::Relaxed)
+        && state
+            .last_clip_warning
+            .is_none_or(|last| last.elapsed() >= CLIP_WARNING_INTERVAL)
+    {
+        state.last_clip_warning = Some(Instant::now());
+        state.toast(
+            Severity::Warning,
+            String::from("The output is clipping, try lowering Output Gain or enabling Auto Level"),
+        );
+    }
+
+    let sweep_rising = params.start_freq_mode.value() == StartFreqMode::Absolute
+        && params.start_freq.value() < params.end_freq.value();
+    if sweep_rising && !state.sweep_was_rising {
+        state.toast(
+            Severity::Info,
+            String::from("Start Freq is below End Freq, so the pitch sweeps upwards"),
+        );
+    }
+    state.sweep_was_rising = sweep_rising;
+}
+
+fn toasts(egui_ctx: &egui::Context, state: &mut EditorState) {
+    state
+        .toasts
+        .retain(|(_, shown_at)| shown_at.elapsed() < TOAST_DURATION);
+    if state.toasts.is_empty() {
+        return;
+    }
+
+    egui::Area::new("toasts")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+        .show(egui_ctx, |ui| {
+            for (notification, _) in &state.toasts {
+                let text = egui::RichText::new(&notification.message);
+                let text = match notification.severity {
+                    Severity::Info => text,
+                    Severity::Warning => text.color(egui::Color32::YELLOW),
+                    Severity::Error => text.color(egui::Color32::LIGHT_RED),
+                };
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(text);
+                });
+            }
+        });
+}
+
+fn preset_summary(preset: &Preset) -> String {
+    let mut summary = format!("by {}", preset.author);
+    if !preset.tags.is_empty() {
+        summary += &format!("\n{}", preset.tags.join(", "));
+    }
+    if !preset.description.is_empty() {
+        summary += &format!("\n\n{}", preset.description);
+    }
+    summary
+}
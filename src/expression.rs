@@ -0,0 +1,361 @@
+//! The mod expression: a small formula like `vel * env(pitch) + 0.1 * rand()`, compiled in the
+//! editor and worked out once per hit on the audio thread.
+
+use serde::{Deserialize, Serialize};
+
+use crate::rng::Rng;
+
+/// Most operations an expression can compile to. Fixed so programs can be copied to the audio
+/// thread without allocating.
+const MAX_OPS: usize = 64;
+
+/// The expression as typed, along with the program it compiles to.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Expression {
+    pub source: String,
+    /// Rebuilt with `compile` rather than saved.
+    #[serde(skip)]
+    pub program: Program,
+}
+
+impl Expression {
+    /// Compiles `source`, keeping the previous expression if it doesn't compile.
+    pub fn set_source(&mut self, source: &str) -> Result<(), String> {
+        self.program = Program::compile(source)?;
+        self.source = source.to_owned();
+        Ok(())
+    }
+
+    /// Needs to be called after the state is restored. A saved expression that no longer compiles
+    /// is left silent rather than dropped.
+    pub fn compile(&mut self) {
+        self.program = Program::compile(&self.source).unwrap_or_default();
+    }
+}
+
+/// What an expression can look at when a hit starts.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct HitInputs {
+    /// The shaped velocity, from 0 to 1.
+    pub velocity: f32,
+    /// The MIDI note number.
+    pub note: f32,
+    /// How many hits have been played.
+    pub hit: f32,
+    /// Where the last hit's amp and pitch envelopes had gotten to, from 0 to 1.
+    pub amp_env: f32,
+    pub pitch_env: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Op {
+    Const(f32),
+    Velocity,
+    Note,
+    Hit,
+    AmpEnv,
+    PitchEnv,
+    Rand,
+    Neg,
+    Abs,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Min,
+    Max,
+}
+
+impl Op {
+    /// How many values the op pops off the stack.
+    fn arity(self) -> usize {
+        match self {
+            Op::Const(_)
+            | Op::Velocity
+            | Op::Note
+            | Op::Hit
+            | Op::AmpEnv
+            | Op::PitchEnv
+            | Op::Rand => 0,
+            Op::Neg | Op::Abs => 1,
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Pow | Op::Min | Op::Max => 2,
+        }
+    }
+}
+
+/// An expression compiled to a stack machine program. An empty program evaluates to 0.
+#[derive(Copy, Clone, Debug)]
+pub struct Program {
+    ops: [Op; MAX_OPS],
+    len: usize,
+}
+
+impl Default for Program {
+    fn default() -> Self {
+        Self {
+            ops: [Op::Const(0.0); MAX_OPS],
+            len: 0,
+        }
+    }
+}
+
+impl Program {
+    fn compile(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            program: Program::default(),
+        };
+        if parser.tokens.is_empty() {
+            return Ok(Program::default());
+        }
+        // this also bounds how deep the parser can recurse
+        if parser.tokens.len() > 4 * MAX_OPS {
+            return Err(String::from("The expression is too long"));
+        }
+        parser.expr()?;
+        match parser.tokens.get(parser.position) {
+            Some(token) => Err(format!("Unexpected {}", token.describe())),
+            None => Ok(parser.program),
+        }
+    }
+
+    /// Runs the program. Results that aren't finite come out as 0.
+    pub(crate) fn eval(&self, inputs: &HitInputs, rng: &mut Rng) -> f32 {
+        if self.len == 0 {
+            return 0.0;
+        }
+        // every op pushes one value, so the stack can't get deeper than the program is long. The
+        // extra slot lets every op read two values, whether it uses them or not.
+        let mut stack = [0.0; MAX_OPS + 1];
+        let mut depth = 0;
+        for &op in &self.ops[..self.len] {
+            depth -= op.arity();
+            let [a, b] = [stack[depth], stack[depth + 1]];
+            stack[depth] = match op {
+                Op::Const(value) => value,
+                Op::Velocity => inputs.velocity,
+                Op::Note => inputs.note,
+                Op::Hit => inputs.hit,
+                Op::AmpEnv => inputs.amp_env,
+                Op::PitchEnv => inputs.pitch_env,
+                Op::Rand => rng.next_f32(),
+                Op::Neg => -a,
+                Op::Abs => a.abs(),
+                Op::Add => a + b,
+                Op::Sub => a - b,
+                Op::Mul => a * b,
+                Op::Div => a / b,
+                Op::Pow => a.powf(b),
+                Op::Min => a.min(b),
+                Op::Max => a.max(b),
+            };
+            depth += 1;
+        }
+        Some(stack[0])
+            .filter(|value| value.is_finite())
+            .unwrap_or(0.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Name(String),
+    Symbol(char),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(value) => format!("number {value}"),
+            Token::Name(name) => format!("'{name}'"),
+            Token::Symbol(symbol) => format!("'{symbol}'"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let number = &source[start..end];
+            let value = number
+                .parse()
+                .map_err(|_| format!("'{number}' isn't a number"))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(source[start..end].to_owned()));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("Unexpected '{c}'"));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser that writes out ops as it goes. From lowest to highest precedence:
+/// `+ -`, `* /`, unary `-`, then `^`, which is right associative.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    program: Program,
+}
+
+impl Parser {
+    fn peek_symbol(&self) -> Option<char> {
+        match self.tokens.get(self.position) {
+            Some(Token::Symbol(symbol)) => Some(*symbol),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.peek_symbol() == Some(symbol) {
+            true => {
+                self.position += 1;
+                Ok(())
+            }
+            false => Err(match self.tokens.get(self.position) {
+                Some(token) => format!("Expected '{symbol}' but found {}", token.describe()),
+                None => format!("Expected '{symbol}'"),
+            }),
+        }
+    }
+
+    fn push(&mut self, op: Op) -> Result<(), String> {
+        if self.program.len == MAX_OPS {
+            return Err(String::from("The expression is too long"));
+        }
+        self.program.ops[self.program.len] = op;
+        self.program.len += 1;
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<(), String> {
+        self.term()?;
+        while let Some(symbol @ ('+' | '-')) = self.peek_symbol() {
+            self.position += 1;
+            self.term()?;
+            self.push(match symbol {
+                '+' => Op::Add,
+                _ => Op::Sub,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn term(&mut self) -> Result<(), String> {
+        self.unary()?;
+        while let Some(symbol @ ('*' | '/')) = self.peek_symbol() {
+            self.position += 1;
+            self.unary()?;
+            self.push(match symbol {
+                '*' => Op::Mul,
+                _ => Op::Div,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<(), String> {
+        match self.peek_symbol() == Some('-') {
+            true => {
+                self.position += 1;
+                self.unary()?;
+                self.push(Op::Neg)
+            }
+            false => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<(), String> {
+        self.atom()?;
+        if self.peek_symbol() == Some('^') {
+            self.position += 1;
+            self.unary()?;
+            self.push(Op::Pow)?;
+        }
+        Ok(())
+    }
+
+    fn atom(&mut self) -> Result<(), String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| String::from("The expression ends too early"))?;
+        self.position += 1;
+        match token {
+            Token::Number(value) => self.push(Op::Const(value)),
+            Token::Symbol('(') => {
+                self.expr()?;
+                self.expect(')')
+            }
+            Token::Name(name) if self.peek_symbol() == Some('(') => {
+                self.position += 1;
+                self.call(&name)?;
+                self.expect(')')
+            }
+            Token::Name(name) => match name.as_str() {
+                "vel" => self.push(Op::Velocity),
+                "note" => self.push(Op::Note),
+                "hit" => self.push(Op::Hit),
+                _ => Err(format!("Unknown variable '{name}'")),
+            },
+            token => Err(format!("Unexpected {}", token.describe())),
+        }
+    }
+
+    /// Parses the arguments to a function, up to its closing bracket.
+    fn call(&mut self, name: &str) -> Result<(), String> {
+        match name {
+            "rand" => self.push(Op::Rand),
+            "env" => {
+                let op = match self.tokens.get(self.position) {
+                    Some(Token::Name(env)) if env == "amp" => Op::AmpEnv,
+                    Some(Token::Name(env)) if env == "pitch" => Op::PitchEnv,
+                    _ => return Err(String::from("env() takes amp or pitch")),
+                };
+                self.position += 1;
+                self.push(op)
+            }
+            "abs" => {
+                self.expr()?;
+                self.push(Op::Abs)
+            }
+            "min" | "max" => {
+                self.expr()?;
+                self.expect(',')?;
+                self.expr()?;
+                self.push(match name {
+                    "min" => Op::Min,
+                    _ => Op::Max,
+                })
+            }
+            _ => Err(format!("Unknown function '{name}'")),
+        }
+    }
+}
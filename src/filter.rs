@@ -0,0 +1,108 @@
+use core::f32;
+
+/// Transposed direct form II biquad.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// Coefficients are normalized so that `a0` is 1.
+    pub(crate) fn new(b: [f32; 3], a: [f32; 2]) -> Self {
+        Self {
+            b0: b[0],
+            b1: b[1],
+            b2: b[2],
+            a1: a[0],
+            a2: a[1],
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// A lowpass from the Audio EQ Cookbook.
+    pub(crate) fn lowpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let omega = f32::consts::TAU * freq / sample_rate;
+        let alpha = omega.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        let b1 = (1.0 - omega.cos()) / a0;
+        Self::new(
+            [b1 / 2.0, b1, b1 / 2.0],
+            [-2.0 * omega.cos() / a0, (1.0 - alpha) / a0],
+        )
+    }
+
+    /// A highpass from the Audio EQ Cookbook.
+    pub(crate) fn highpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let omega = f32::consts::TAU * freq / sample_rate;
+        let alpha = omega.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        let b1 = -(1.0 + omega.cos()) / a0;
+        Self::new(
+            [-b1 / 2.0, b1, -b1 / 2.0],
+            [-2.0 * omega.cos() / a0, (1.0 - alpha) / a0],
+        )
+    }
+
+    /// One second order section of an analog lowpass prototype with its passband edge at 1 rad/s,
+    /// with a notch at `sqrt(zero)` rad/s and poles at `pole` rad/s with a Q of `q`. The bilinear
+    /// transform is warped so the edge lands on `cutoff`, and the section passes DC at unity gain.
+    pub(crate) fn analog_lowpass(
+        zero: f32,
+        pole: f32,
+        q: f32,
+        cutoff: f32,
+        sample_rate: f32,
+    ) -> Self {
+        let c = (f32::consts::PI * cutoff / sample_rate).tan().recip();
+        let a0 = c * c + pole / q * c + pole * pole;
+        let gain = pole * pole / zero / a0;
+        let b0 = gain * (c * c + zero);
+        Self::new(
+            [b0, gain * 2.0 * (zero - c * c), b0],
+            [
+                2.0 * (pole * pole - c * c) / a0,
+                (c * c - pole / q * c + pole * pole) / a0,
+            ],
+        )
+    }
+
+    /// An allpass from the Audio EQ Cookbook.
+    pub(crate) fn allpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let omega = f32::consts::TAU * freq / sample_rate;
+        let alpha = omega.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        let a1 = -2.0 * omega.cos() / a0;
+        let a2 = (1.0 - alpha) / a0;
+        Self::new([a2, a1, 1.0], [a1, a2])
+    }
+
+    /// Takes on the coefficients of `other` while keeping this filter's state, so it can be retuned
+    /// while it's running.
+    pub(crate) fn set_coefficients(&mut self, other: &Biquad) {
+        *self = Self {
+            z1: self.z1,
+            z2: self.z2,
+            ..*other
+        };
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    pub(crate) fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
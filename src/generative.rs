@@ -0,0 +1,188 @@
+//! Generative variation: simple rules that work out offsets for each hit as it starts, so a
+//! repeating pattern doesn't play back identically every time.
+
+use serde::{Deserialize, Serialize};
+
+use crate::rng::Rng;
+
+pub const MAX_RULES: usize = 4;
+pub const MAX_CYCLE_LEN: usize = 16;
+/// Furthest a hit's tune or sweep can be offset either way, in semitones.
+const MAX_PITCH_OFFSET: f32 = 48.0;
+/// Quietest and loudest a hit's level can be offset to, in decibels.
+const MIN_LEVEL_OFFSET: f32 = -60.0;
+const MAX_LEVEL_OFFSET: f32 = 24.0;
+
+/// The rules, saved with the patch.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Generative {
+    pub rules: [Option<Rule>; MAX_RULES],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub target: Target,
+    pub pattern: Pattern,
+}
+
+/// What a rule offsets. Tune and Sweep are in semitones, and Level is in decibels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Target {
+    Tune,
+    Sweep,
+    Level,
+}
+
+impl Target {
+    pub const ALL: [Target; 3] = [Target::Tune, Target::Sweep, Target::Level];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Tune => "Tune",
+            Target::Sweep => "Sweep",
+            Target::Level => "Level",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    /// Steps through the first `len` values, one per hit.
+    Cycle {
+        values: [f32; MAX_CYCLE_LEN],
+        len: usize,
+    },
+    /// Moves a random amount of up to `step` either way on each hit, staying within `range` of 0.
+    DrunkWalk { step: f32, range: f32 },
+    /// Spreads `pulses` hits as evenly as possible over every `steps` hits, offsetting those by
+    /// `amount` and leaving the rest alone.
+    Euclidean {
+        pulses: u32,
+        steps: u32,
+        rotation: u32,
+        amount: f32,
+    },
+}
+
+impl Pattern {
+    /// A starting point for each kind of pattern.
+    pub const KINDS: [(&'static str, Pattern); 3] = [
+        (
+            "Cycle",
+            Pattern::Cycle {
+                values: [0.0; MAX_CYCLE_LEN],
+                len: 2,
+            },
+        ),
+        (
+            "Drunk Walk",
+            Pattern::DrunkWalk {
+                step: 0.5,
+                range: 2.0,
+            },
+        ),
+        (
+            "Euclidean",
+            Pattern::Euclidean {
+                pulses: 3,
+                steps: 8,
+                rotation: 0,
+                amount: 3.0,
+            },
+        ),
+    ];
+
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Pattern::Cycle { .. } => "Cycle",
+            Pattern::DrunkWalk { .. } => "Drunk Walk",
+            Pattern::Euclidean { .. } => "Euclidean",
+        }
+    }
+}
+
+/// Offsets for one hit, summed over every rule with the same target.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct HitOffsets {
+    /// Semitones.
+    pub tune: f32,
+    /// Semitones.
+    pub sweep: f32,
+    /// Decibels.
+    pub level: f32,
+}
+
+impl HitOffsets {
+    pub(crate) fn add(&mut self, target: Target, offset: f32) {
+        match target {
+            Target::Tune => self.tune += offset,
+            Target::Sweep => self.sweep += offset,
+            Target::Level => self.level += offset,
+        }
+    }
+
+    /// The offsets kept to a usable range, so runaway rules or expressions can't detune or blow
+    /// up the filters. Offsets that aren't numbers at all are dropped.
+    pub(crate) fn clamped(self) -> Self {
+        let clamp = |offset: f32, min: f32, max: f32| match offset.is_nan() {
+            true => 0.0,
+            false => offset.clamp(min, max),
+        };
+        Self {
+            tune: clamp(self.tune, -MAX_PITCH_OFFSET, MAX_PITCH_OFFSET),
+            sweep: clamp(self.sweep, -MAX_PITCH_OFFSET, MAX_PITCH_OFFSET),
+            level: clamp(self.level, MIN_LEVEL_OFFSET, MAX_LEVEL_OFFSET),
+        }
+    }
+}
+
+/// Where each rule's drunk walk has wandered to, kept on the audio thread.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct GenerativeState {
+    walks: [f32; MAX_RULES],
+}
+
+impl GenerativeState {
+    pub(crate) fn reset(&mut self) {
+        self.walks = [0.0; MAX_RULES];
+    }
+
+    /// Works out the offsets for hit number `hit`, counting from 0.
+    pub(crate) fn next(&mut self, generative: &Generative, hit: u64, rng: &mut Rng) -> HitOffsets {
+        let mut offsets = HitOffsets::default();
+        for (rule, walk) in generative.rules.iter().zip(&mut self.walks) {
+            let Some(rule) = rule else {
+                continue;
+            };
+            let offset = match rule.pattern {
+                Pattern::Cycle { values, len } => match len.min(MAX_CYCLE_LEN) {
+                    0 => 0.0,
+                    len => values[(hit % len as u64) as usize],
+                },
+                Pattern::DrunkWalk { step, range } => {
+                    *walk = (*walk + step * (2.0 * rng.next_f32() - 1.0)).clamp(-range, range);
+                    *walk
+                }
+                Pattern::Euclidean {
+                    pulses,
+                    steps,
+                    rotation,
+                    amount,
+                } => match u64::from(steps) {
+                    0 => 0.0,
+                    steps => {
+                        let pulses = u64::from(pulses);
+                        let step = (hit + u64::from(rotation)) % steps;
+                        // a hit is a pulse wherever the running count of pulses ticks over
+                        match (step * pulses) % steps < pulses {
+                            true => amount,
+                            false => 0.0,
+                        }
+                    }
+                },
+            };
+            offsets.add(rule.target, offset);
+        }
+        offsets
+    }
+}
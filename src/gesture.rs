@@ -0,0 +1,42 @@
+use nih_plug::prelude::*;
+
+/// Ends the automation gesture for one parameter.
+type EndGesture<'a> = Box<dyn FnOnce() + 'a>;
+
+/// Changes to any number of parameters that the host should record as a single edit. Every
+/// parameter's automation gesture stays open until the whole group is finished, which happens when
+/// this is dropped.
+pub struct Gesture<'a> {
+    setter: &'a ParamSetter<'a>,
+    /// Parameters with a gesture in progress, keyed by address, with how to end each one.
+    open: Vec<(*const (), EndGesture<'a>)>,
+}
+
+impl<'a> Gesture<'a> {
+    pub fn new(setter: &'a ParamSetter<'a>) -> Self {
+        Self {
+            setter,
+            open: Vec::new(),
+        }
+    }
+
+    pub fn set<P: Param>(&mut self, param: &'a P, value: P::Plain) {
+        // a parameter set twice in one group only gets one gesture
+        let key = param as *const P as *const ();
+        if !self.open.iter().any(|(open, _)| *open == key) {
+            self.setter.begin_set_parameter(param);
+            let setter = self.setter;
+            self.open
+                .push((key, Box::new(move || setter.end_set_parameter(param))));
+        }
+        self.setter.set_parameter(param, value);
+    }
+}
+
+impl Drop for Gesture<'_> {
+    fn drop(&mut self) {
+        for (_, end) in self.open.drain(..) {
+            end();
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Importers for kick patches that weren't made with this plugin.
+//!
+//! # Generic kick schema
+//!
+//! A plain JSON description of a sine kick that other tools and hand-written libraries can target:
+//!
+//! ```json
+//! {
+//!     "name": "Warehouse",
+//!     "author": "someone",
+//!     "tags": ["techno"],
+//!     "description": "optional free text",
+//!     "pitch": { "start_hz": 900.0, "end_hz": 48.0, "decay_ms": 40.0 },
+//!     "amp": { "attack_ms": 0.0, "hold_ms": 0.0, "decay_ms": 600.0, "release_ms": 300.0 },
+//!     "start_phase": 0.25,
+//!     "gain_db": 0.0
+//! }
+//! ```
+//!
+//! `name`, `pitch` and `amp` are required, everything else is optional. The pitch sweep is
+//! exponential from `start_hz` to `end_hz`. `start_phase` is the oscillator phase at the start of a
+//! hit in cycles. Values outside of what this plugin can represent are clamped.
+
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::presets::Preset;
+use crate::{AhdsrParams, KickParams};
+
+#[derive(Debug, Deserialize)]
+struct GenericKick {
+    name: String,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    description: String,
+    pitch: GenericPitch,
+    amp: GenericAmp,
+    #[serde(default = "default_start_phase")]
+    start_phase: f32,
+    #[serde(default)]
+    gain_db: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenericPitch {
+    start_hz: f32,
+    end_hz: f32,
+    decay_ms: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenericAmp {
+    #[serde(default)]
+    attack_ms: f32,
+    #[serde(default)]
+    hold_ms: f32,
+    decay_ms: f32,
+    #[serde(default)]
+    release_ms: f32,
+}
+
+fn default_start_phase() -> f32 {
+    0.25
+}
+
+/// Reads a patch in the generic kick schema described in the module docs.
+pub(crate) fn import_generic(json: &str) -> Result<Preset, String> {
+    let kick: GenericKick = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+    // clamping through the real parameters keeps this in sync with their ranges
+    let defaults = KickParams::default();
+    let mut params = BTreeMap::new();
+    let mut set = |id: &str, param: &FloatParam, value: f32| {
+        let value = param.preview_plain(param.preview_normalized(value));
+        params.insert(String::from(id), ParamValue::F32(value));
+    };
+
+    let mut set_env =
+        |prefix: &str, env: &AhdsrParams, [attack, hold, decay, release]: [f32; 4]| {
+            set(
+                &format!("{prefix}_attack"),
+                &env.attack_time,
+                attack / 1000.0,
+            );
+            set(&format!("{prefix}_hold"), &env.hold_time, hold / 1000.0);
+            set(&format!("{prefix}_decay"), &env.decay_time, decay / 1000.0);
+            set(&format!("{prefix}_sustain"), &env.sustain_level, 0.0);
+            set(
+                &format!("{prefix}_release"),
+                &env.release_time,
+                release / 1000.0,
+            );
+        };
+    set_env(
+        "amp_env",
+        &defaults.amp_env,
+        [
+            kick.amp.attack_ms,
+            kick.amp.hold_ms,
+            kick.amp.decay_ms,
+            kick.amp.release_ms,
+        ],
+    );
+    // the sweep is over once the pitch envelope reaches zero, so release just mirrors decay
+    set_env(
+        "pitch_env",
+        &defaults.pitch_env,
+        [0.0, 0.0, kick.pitch.decay_ms, kick.pitch.decay_ms],
+    );
+
+    set("length", &defaults.length, 1.0);
+    set("start_freq", &defaults.start_freq, kick.pitch.start_hz);
+    set(
+        "start_offset",
+        &defaults.start_offset,
+        defaults.start_offset.default_plain_value(),
+    );
+    set("end_freq", &defaults.end_freq, kick.pitch.end_hz);
+    set(
+        "phase_offset",
+        &defaults.phase_offset,
+        kick.start_phase.rem_euclid(1.0),
+    );
+    set(
+        "output_gain",
+        &defaults.output_gain,
+        util::db_to_gain(kick.gain_db),
+    );
+    params.insert(String::from("link_decays"), ParamValue::Bool(false));
+    params.insert(String::from("auto_level"), ParamValue::Bool(false));
+    params.insert(
+        String::from("start_freq_mode"),
+        ParamValue::String(String::from("absolute")),
+    );
+
+    Ok(Preset {
+        name: kick.name,
+        author: kick.author,
+        tags: kick.tags,
+        description: kick.description,
+        state: PluginState {
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            params,
+            fields: BTreeMap::new(),
+        },
+        path: None,
+    })
+}
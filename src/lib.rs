@@ -1,19 +1,255 @@
+use analysis::{MatchEq, ReferenceAnalysis};
+use atomic_float::AtomicF32;
+use breakpoint::{Breakpoint, BreakpointEnvelope};
 use core::f32;
+use expression::{Expression, HitInputs, Program};
+use filter::Biquad;
+use generative::{Generative, GenerativeState, HitOffsets, Target};
+use meter::{
+    ChainLevels, ChainNode, ChainPeaks, CorrelationMeter, CpuLoad, CpuMeter, HitCapture,
+    HitMeasurement, HitMeter, HitRecorder, PeakHistory, PeakHistoryRecorder,
+};
+use midi_monitor::MidiLog;
+use morph::{
+    Kit, MorphSlots, MorphTable, Overrides, SceneTable, Scenes, Snapshot, KIT_SLOTS, SCENE_COUNT,
+};
+use multiband::{Band, Multiband};
 use nih_plug::prelude::*;
-use nih_plug_egui::{create_egui_editor, egui, widgets, EguiState};
-use std::sync::Arc;
+use nih_plug_egui::EguiState;
+use notes::NoteStack;
+use notifications::{Notifications, Severity};
+use null_test::NullTest;
+use presets::{DirFingerprint, Preset};
+use reverse::{DelayLine, Swell, SwellRender};
+use rng::Rng;
+use saturation::AnalogClip;
+use settings::Settings;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use subsonic::{Subsonic, SUBSONIC_MAX_HZ, SUBSONIC_MIN_HZ};
+
+mod analysis;
+mod breakpoint;
+#[cfg(feature = "crash-log")]
+mod crash_log;
+mod editor;
+mod expression;
+mod filter;
+mod generative;
+mod gesture;
+mod import;
+mod meter;
+mod midi_monitor;
+mod morph;
+mod multiband;
+mod notes;
+mod notifications;
+mod null_test;
+mod offline;
+mod presets;
+mod reverse;
+mod rng;
+mod saturation;
+mod settings;
+mod smoothing;
+mod subsonic;
+
+/// Peak level that auto-level aims each rendered hit at.
+const AUTO_LEVEL_TARGET_DB: f32 = -3.0;
+/// Longest stretch of a hit that gets rendered when measuring its level.
+const LEVEL_MEASUREMENT_SECONDS: f32 = 2.0;
+/// Longest stretch of a played hit that is kept around for analysis.
+const CAPTURE_SECONDS: f32 = 2.0;
+/// How much of the tail after the pitch sweep is used to detect its pitch.
+const TAIL_ANALYSIS_SECONDS: f32 = 0.5;
+/// A reference kick on the sidechain input starts being recorded once it gets louder than this.
+const REFERENCE_ONSET_DB: f32 = -40.0;
+/// The reference recording ends once the sidechain input stays quieter than this for
+/// `REFERENCE_SILENCE_SECONDS`.
+const REFERENCE_SILENCE_DB: f32 = -60.0;
+const REFERENCE_SILENCE_SECONDS: f32 = 0.1;
+/// How far from the start of a bar a hit can be and still get downbeat accents.
+const DOWNBEAT_TOLERANCE_BEATS: f64 = 0.125;
+/// DC coupled interfaces like the ES-8 map ±10 V to ±1.0.
+const CV_VOLTS_PER_UNIT: f32 = 10.0;
+/// The gate has to fall below this fraction of the threshold before it can trigger again, so noise
+/// around the threshold doesn't retrigger.
+const CV_GATE_HYSTERESIS: f32 = 0.5;
+/// Time constant of the first, fast stage of the 909 engine's pitch sweep.
+const TR909_FAST_SWEEP_SECONDS: f32 = 0.004;
+/// Length of the pulse at the start of the 909 engine's click.
+const TR909_PULSE_SECONDS: f32 = 0.001;
+/// Time constant of the noise burst in the 909 engine's click.
+const TR909_NOISE_SECONDS: f32 = 0.005;
+/// The morph amount can't be morphed itself.
+const MORPH_PARAM_ID: &str = "morph";
+/// Where the engines' tilt EQs pivot.
+const TILT_PIVOT_HZ: f32 = 500.0;
+/// Where Solo Body lowpasses the output.
+const SOLO_BODY_HZ: f32 = 150.0;
+/// Where Solo Click highpasses the output.
+const SOLO_CLICK_HZ: f32 = 1000.0;
+/// Most hits that can ring out at once in Poly mode. Also needs to be enough for every kit slot
+/// to have a voice.
+const MAX_VOICES: usize = 8;
+/// Most notes that can be waiting on quantize or humanize at once, which is enough for every voice
+/// to be struck twice while the first hits are still held back.
+const MAX_PENDING_NOTES: usize = 2 * MAX_VOICES;
+/// Furthest humanize can move a hit either way.
+const HUMANIZE_MAX_MS: f32 = 20.0;
+/// Stands in for a MIDI note on hits triggered by the CV gate.
+const CV_TRIGGER_NOTE: u8 = 60;
+/// The Dry output followed by an output for each kit slot.
+const MONO_AUX_OUTPUTS: [NonZeroU32; 1 + KIT_SLOTS] = [new_nonzero_u32(1); 1 + KIT_SLOTS];
+const STEREO_AUX_OUTPUTS: [NonZeroU32; 1 + KIT_SLOTS] = [new_nonzero_u32(2); 1 + KIT_SLOTS];
+const AUX_OUTPUT_NAMES: [&str; 1 + KIT_SLOTS] = [
+    "Dry", "Kit 1", "Kit 2", "Kit 3", "Kit 4", "Kit 5", "Kit 6", "Kit 7", "Kit 8",
+];
+/// How long choked kit slots and voices killed by the transport stopping take to fade out.
+const CHOKE_SECONDS: f32 = 0.005;
+/// How long the last sample of a voice cut off by a new hit takes to fade out.
+const KILL_RAMP_SAMPLES: u32 = 32;
+/// Voices cut off closer than this to a zero crossing don't need fading out.
+const KILL_RAMP_THRESHOLD: f32 = 1e-4;
+/// Longest reverse swell, which is also the most latency the swell can add.
+const REVERSE_MAX_SECONDS: f32 = 1.0;
+/// Longest the body can be held back behind the clicks.
+const BODY_DELAY_MAX_MS: f32 = 5.0;
+/// Room in each voice's body delay, enough for `BODY_DELAY_MAX_MS` at 192 kHz.
+const BODY_DELAY_MAX_SAMPLES: usize = 1024;
+/// Room in each layer's alignment delay.
+const LAYER_DELAY_MAX_SAMPLES: usize = 512;
+/// Voices render at least this fast, and are decimated down to lower host sample rates, so the top
+/// of the sweep and the clicks don't alias.
+const MIN_VOICE_SAMPLE_RATE: f32 = 44100.0;
+/// Most times a voice renders for each host sample, which reaches 44.1 kHz from 11.025 kHz.
+const MAX_OVERSAMPLING: usize = 4;
+/// Where the decimation filter's passband ends, relative to the host's Nyquist frequency. Its
+/// stopband starts by the Nyquist frequency.
+const DECIMATION_CUTOFF: f32 = 0.8;
+/// An eighth order elliptic lowpass with its passband edge at 1 rad/s, as the squared zero
+/// frequency, pole frequency, and pole Q of each second order section. It ripples by 0.1 dB in the
+/// passband and is 67 dB down from 1.25 rad/s on.
+const DECIMATION_SECTIONS: [(f32, f32, f32); 4] = [
+    (25.976_98, 0.492_644_5, 0.607_911_2),
+    (3.688_536, 0.760_892_4, 1.435_647),
+    (1.975_901, 0.944_390_7, 3.741_045),
+    (1.598_499, 1.020_622, 14.438_52),
+];
 
 pub struct KickSynth {
     pub params: Arc<KickParams>,
+    shared: Arc<SharedState>,
     sample_rate: f32,
 
-    osc_state: OscillatorState,
-    pitch_env_state: AhdsrState,
-    amp_env_state: AhdsrState,
+    voices: [VoiceSlot; MAX_VOICES],
+    /// The voice playing the most recent hit.
+    current_voice: usize,
+    /// Counts triggered voices, for stealing the oldest one.
+    voice_age: u64,
+    /// The last copy of the pitch breakpoints that could be read without blocking.
+    pitch_breakpoints: BreakpointEnvelope,
+    /// The last copy of the custom velocity curve that could be read without blocking.
+    velocity_curve: BreakpointEnvelope,
+    /// The last copy of the morph table that could be read without blocking.
+    morph_table: MorphTable,
+    /// The last copy of the scene table that could be read without blocking.
+    scene_table: SceneTable,
+    /// The last copy of `SharedState::program_table` that could be read without blocking.
+    program_table: SceneTable,
+    /// The last copy of the kit table that could be read without blocking.
+    kit_table: SceneTable,
+    /// The last copies of the kit's choke groups and learned notes that could be read without
+    /// blocking.
+    kit_choke_groups: [u8; KIT_SLOTS],
+    kit_notes: [Option<u8>; KIT_SLOTS],
+    /// The last copy of the compiled mod expression that could be read without blocking.
+    expression: Program,
+    /// The last copy of the generative rules that could be read without blocking.
+    generative: Generative,
+    /// The last audition note from the settings that could be read without blocking.
+    audition_note: u8,
+    /// Whether the saved default settings have been looked for. Only the first initialize does,
+    /// so initializing again never replaces the settings the instance already has.
+    settings_loaded: bool,
+    generative_state: GenerativeState,
+    /// The voice parameters of each filled kit slot, worked out once per block.
+    kit_params: [Option<VoiceParams>; KIT_SLOTS],
+    /// The recalled scene, or `None` when playing the live parameters.
+    scene: Option<usize>,
+    previous_scene: Option<usize>,
+    /// Progress of the fade from `previous_scene` to `scene`, from 0 to 1.
+    scene_fade: f32,
+    /// The Scene param's value when it last recalled a scene.
+    last_scene_param: i32,
+    /// The host's tempo as of the current block.
+    tempo: Option<f64>,
+    /// Hits since the transport started playing, for accents.
+    hit_count: u64,
+    was_playing: bool,
+    last_level_measurement: Option<(VoiceParams, f32)>,
+    hit_meter: HitMeter,
+    hit_recorder: HitRecorder,
+    reference_recorder: HitRecorder,
+    null_test: NullTest,
+    reference_silent_samples: usize,
+    cpu_meter: CpuMeter,
+    layer_correlation: CorrelationMeter,
+    peak_history: PeakHistoryRecorder,
 
     last_midi_note: Option<u8>,
+    /// Whether the body and click solo keyswitches are held.
+    held_solos: [bool; 2],
     midi_frequency: f32,
+    /// Velocity of the current note after the velocity response was applied.
     midi_velocity: f32,
+    /// Channel or poly pressure on the current note.
+    pressure: Smoother<f32>,
+    modwheel: Smoother<f32>,
+    pending_notes: [Option<PendingNote>; MAX_PENDING_NOTES],
+    /// The note a latched audition is holding.
+    audition_holding: Option<u8>,
+    /// Notes held down in Legato mode.
+    held_notes: NoteStack,
+    /// The note the tail is tuned to in Legato mode, partway through a glide.
+    bass_note: f32,
+    /// The note being glided to.
+    bass_target: f32,
+    /// Whether the CV gate is above its threshold, and is holding the current hit.
+    cv_gate_high: bool,
+    /// Holds the CV gate and pitch back by the humanize latency, so CV hits stay on time.
+    cv_delay: [DelayLine; 2],
+    rng: Rng,
+    /// The seed `rng` was last seeded with.
+    rng_seed: i32,
+    swell: Swell,
+    /// The gain of everything coming out of the delays while Kill on stop fades it out, or `None`.
+    kill_fade: Option<f32>,
+    /// Delays each channel of the hits by `latency` samples.
+    hit_delay: [DelayLine; 2],
+    /// Delays each kit output by `latency` plus the subsonic filter's latency, so they line up
+    /// with the main output.
+    kit_delays: [[DelayLine; 2]; KIT_SLOTS],
+    /// Delays the dry output by the subsonic filter's latency, so it lines up with the main output.
+    dry_delay: [DelayLine; 2],
+    /// The delay the reverse swell needs, in samples.
+    latency: u32,
+    /// The latency last reported to the host, which includes the subsonic filter's and humanize's.
+    reported_latency: u32,
+    last_swell_render: Option<(VoiceParams, f32, f32)>,
+    /// The parameters the voices played with on the last sample.
+    last_voice_params: Option<VoiceParams>,
+    /// The master saturator for each channel.
+    analog_clip: [AnalogClip; 2],
+    multiband: [Multiband; 2],
+    solo_body_filters: [Biquad; 2],
+    solo_click_filters: [Biquad; 2],
+    subsonic: [Subsonic; 2],
+    #[cfg(feature = "crash-log")]
+    crash_log: crash_log::Recorder,
 }
 
 #[derive(Params)]
@@ -28,15 +264,175 @@ pub struct AhdsrParams {
     pub sustain_level: FloatParam,
     #[id = "release"]
     pub release_time: FloatParam,
+    #[id = "gate_mode"]
+    pub mode: EnumParam<EnvelopeMode>,
 }
 
-#[derive(Copy, Clone, Debug)]
+/// State that isn't a parameter but still needs to be visible outside the audio thread.
+pub struct SharedState {
+    level_gain: AtomicF32,
+    level_measurement_pending: AtomicBool,
+    last_hit: HitMeasurement,
+    last_hit_capture: Mutex<HitCapture>,
+    /// Perceived pitch of the last hit's tail in Hz, or 0 if none could be detected.
+    tail_pitch: AtomicF32,
+    /// Set by the editor to record the next kick that comes in on the sidechain input.
+    reference_listening: AtomicBool,
+    reference_capture: Mutex<HitCapture>,
+    reference_analysis: Mutex<Option<ReferenceAnalysis>>,
+    /// Set by the editor to play the reference inverted under each hit. Not saved with the patch.
+    null_test: AtomicBool,
+    /// Brings the reference to the level of the last hit for the null test.
+    null_test_gain: AtomicF32,
+    match_eq: Mutex<Option<MatchEq>>,
+    /// A fresh scan of the user preset directory, waiting to be picked up by the editor.
+    user_presets: Mutex<Option<Vec<Preset>>>,
+    user_preset_fingerprint: Mutex<DirFingerprint>,
+    audition_requested: AtomicBool,
+    /// Set by the editor while a latched audition should keep holding its note.
+    audition_held: AtomicBool,
+    /// A note played from a performance pad that hasn't been played yet, or -1.
+    pad_note: AtomicI32,
+    pad_velocity: AtomicF32,
+    /// Set by the editor to only listen to the lows, for tuning the tail. Not saved with the
+    /// patch.
+    solo_body: AtomicBool,
+    /// Set by the editor to only listen to the highs, for judging the click.
+    solo_click: AtomicBool,
+    /// The last MIDI program change that hasn't been loaded yet, or -1.
+    requested_program: AtomicI32,
+    /// The float values of the preset picked by `requested_program`, which the audio thread plays
+    /// until the editor loads the preset for real. Empty otherwise.
+    program_table: RwLock<SceneTable>,
+    /// The kit slot waiting to learn its note from the next note on, or -1.
+    kit_learn_slot: AtomicI32,
+    /// The note the audio thread heard for `kit_learned_slot`, waiting for the editor, or -1.
+    kit_learned_note: AtomicI32,
+    /// The slot that was learning when `kit_learned_note` came in.
+    kit_learned_slot: AtomicI32,
+    notifications: Notifications,
+    /// Set by the audio thread whenever the output goes over 0 dBFS.
+    clipped: AtomicBool,
+    /// Set by the audio thread whenever a hit's end frequency falls below the subsonic filter's
+    /// cutoff.
+    below_subsonic: AtomicBool,
+    chain_levels: ChainLevels,
+    /// How well the engines' lows line up, from -1 (cancelling) to 1 (reinforcing).
+    layer_correlation: AtomicF32,
+    peak_history: PeakHistory,
+    cpu_load: CpuLoad,
+    swell_render: SwellRender,
+    midi_log: MidiLog,
+}
+
+impl Default for SharedState {
+    fn default() -> Self {
+        Self {
+            level_gain: AtomicF32::new(1.0),
+            level_measurement_pending: AtomicBool::new(false),
+            last_hit: Default::default(),
+            last_hit_capture: Default::default(),
+            tail_pitch: AtomicF32::new(0.0),
+            reference_listening: AtomicBool::new(false),
+            reference_capture: Default::default(),
+            reference_analysis: Default::default(),
+            null_test: AtomicBool::new(false),
+            null_test_gain: AtomicF32::new(1.0),
+            match_eq: Default::default(),
+            user_presets: Default::default(),
+            user_preset_fingerprint: Default::default(),
+            audition_requested: AtomicBool::new(false),
+            audition_held: AtomicBool::new(false),
+            pad_note: AtomicI32::new(-1),
+            pad_velocity: AtomicF32::new(1.0),
+            solo_body: AtomicBool::new(false),
+            solo_click: AtomicBool::new(false),
+            requested_program: AtomicI32::new(-1),
+            program_table: Default::default(),
+            kit_learn_slot: AtomicI32::new(-1),
+            kit_learned_note: AtomicI32::new(-1),
+            kit_learned_slot: AtomicI32::new(-1),
+            notifications: Default::default(),
+            clipped: AtomicBool::new(false),
+            below_subsonic: AtomicBool::new(false),
+            chain_levels: ChainLevels::default(),
+            layer_correlation: AtomicF32::new(0.0),
+            peak_history: PeakHistory::default(),
+            cpu_load: Default::default(),
+            swell_render: Default::default(),
+            midi_log: Default::default(),
+        }
+    }
+}
+
+impl SharedState {
+    /// Updates everything that compares the last hit to the reference.
+    fn compare_to_reference(&self) {
+        let hit = self.last_hit_capture.lock().unwrap();
+        let reference = self.reference_capture.lock().unwrap();
+        if let Some(gain) = analysis::level_match(&hit.samples, &reference.samples) {
+            self.null_test_gain.store(gain, Ordering::Relaxed);
+        }
+        *self.match_eq.lock().unwrap() =
+            analysis::match_eq(&hit.samples, &reference.samples, hit.sample_rate);
+    }
+
+    fn scan_user_presets(&self, dir: &Path, force: bool) {
+        let fingerprint = presets::dir_fingerprint(dir);
+        let mut known_fingerprint = self.user_preset_fingerprint.lock().unwrap();
+        if force || *known_fingerprint != fingerprint {
+            *known_fingerprint = fingerprint;
+            let presets = presets::scan_dir(dir, |path, err| {
+                self.notifications.push(
+                    Severity::Warning,
+                    format!("Could not load {}: {err}", path.display()),
+                )
+            });
+            *self.user_presets.lock().unwrap() = Some(presets);
+        }
+    }
+}
+
+// tasks are sent from the audio thread, where boxing the large variant would allocate
+#[allow(clippy::large_enum_variant)]
+pub enum Task {
+    MeasureLevel {
+        params: VoiceParams,
+        sample_rate: f32,
+    },
+    RenderSwell {
+        params: VoiceParams,
+        sample_rate: f32,
+        length: f32,
+    },
+    AnalyzeLastHit,
+    AnalyzeReference,
+    /// Rescans the user preset directory if anything in it changed, or unconditionally if `force`
+    /// is set.
+    ScanUserPresets {
+        dir: PathBuf,
+        force: bool,
+    },
+    SavePreset {
+        preset: Preset,
+        dir: PathBuf,
+    },
+    SaveSettings(Settings),
+    /// Starts a new instance from the saved default settings, unless it restored its own.
+    LoadSettings,
+    /// Looks up the preset picked by a program change and hands its values to the audio thread.
+    LoadProgram(usize),
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct AhdsrValues {
     pub attack: f32,
     pub hold: f32,
     pub decay: f32,
     pub sustain: f32,
     pub release: f32,
+    /// Skips the sustain and release, decaying to silence whether or not the note is held.
+    pub one_shot: bool,
 }
 
 impl AhdsrValues {
@@ -46,13 +442,22 @@ impl AhdsrValues {
     fn ahdr(attack: f32, hold: f32, decay: f32, release: f32) -> Self {
         Self::ahdsr(attack, hold, decay, 1.0, release)
     }
-    fn ahdsr(attack: f32, hold: f32, decay: f32, sustain: f32, release: f32) -> Self {
+    const fn ahdsr(attack: f32, hold: f32, decay: f32, sustain: f32, release: f32) -> Self {
         Self {
             attack,
             hold,
             decay,
             sustain,
             release,
+            one_shot: false,
+        }
+    }
+
+    /// The level the decay heads towards.
+    fn sustain_level(&self) -> f32 {
+        match self.one_shot {
+            true => 0.0,
+            false => self.sustain,
         }
     }
     fn time_for_stage(&self, stage: AhdsrStage) -> Option<f32> {
@@ -68,6 +473,17 @@ impl AhdsrValues {
 }
 
 impl AhdsrParams {
+    fn values_with(&self, value: impl Fn(&FloatParam) -> f32, time_scale: f32) -> AhdsrValues {
+        AhdsrValues {
+            attack: value(&self.attack_time),
+            hold: value(&self.hold_time),
+            decay: time_scale * value(&self.decay_time),
+            sustain: value(&self.sustain_level),
+            release: time_scale * value(&self.release_time),
+            one_shot: self.mode.value() == EnvelopeMode::OneShot,
+        }
+    }
+
     pub fn new(
         prefix: &str,
         factor: f32,
@@ -86,8 +502,9 @@ impl AhdsrParams {
                 },
             )
             .with_unit(" s")
-            .with_smoother(SmoothingStyle::Linear(5.0))
-            .with_value_to_string(Arc::new(|value| format!("{value:.2}"))),
+            .with_smoother(smoothing::time())
+            .with_value_to_string(Arc::new(|value| format!("{value:.2}")))
+            .with_string_to_value(s2v_f32_seconds()),
             hold_time: FloatParam::new(
                 format!("{prefix}Hold Time"),
                 default_values.hold,
@@ -98,8 +515,9 @@ impl AhdsrParams {
                 },
             )
             .with_unit(" s")
-            .with_smoother(SmoothingStyle::Linear(5.0))
-            .with_value_to_string(Arc::new(|value| format!("{value:.2}"))),
+            .with_smoother(smoothing::time())
+            .with_value_to_string(Arc::new(|value| format!("{value:.2}")))
+            .with_string_to_value(s2v_f32_seconds()),
             decay_time: FloatParam::new(
                 format!("{prefix}Decay Time"),
                 default_values.decay,
@@ -110,8 +528,9 @@ impl AhdsrParams {
                 },
             )
             .with_unit(" s")
-            .with_smoother(SmoothingStyle::Linear(5.0))
-            .with_value_to_string(Arc::new(|value| format!("{value:.2}"))),
+            .with_smoother(smoothing::time())
+            .with_value_to_string(Arc::new(|value| format!("{value:.2}")))
+            .with_string_to_value(s2v_f32_seconds()),
             release_time: FloatParam::new(
                 format!("{prefix}Release Time"),
                 default_values.release,
@@ -122,13 +541,15 @@ impl AhdsrParams {
                 },
             )
             .with_unit(" s")
-            .with_smoother(SmoothingStyle::Linear(5.0))
-            .with_value_to_string(Arc::new(|value| format!("{value:.2}"))),
+            .with_smoother(smoothing::time())
+            .with_value_to_string(Arc::new(|value| format!("{value:.2}")))
+            .with_string_to_value(s2v_f32_seconds()),
             sustain_level: FloatParam::new(
                 "Sustain Value",
                 1.0,
                 FloatRange::Linear { min: 0.0, max: 1.0 },
             ),
+            mode: EnumParam::new(format!("{prefix}Gate Mode"), EnvelopeMode::Gated),
         }
     }
 }
@@ -145,31 +566,924 @@ impl Default for AhdsrParams {
     }
 }
 
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StartFreqMode {
+    #[id = "absolute"]
+    #[name = "Absolute"]
+    Absolute,
+    /// Start Freq is ignored, and the sweep instead starts a fixed number of semitones above End
+    /// Freq.
+    #[id = "relative"]
+    #[name = "Relative"]
+    Relative,
+}
+
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EnvelopeMode {
+    /// Holds at the sustain level until the note is released.
+    #[id = "gated"]
+    #[name = "Gated"]
+    Gated,
+    /// Attack, hold, and decay to silence, however long the note is held.
+    #[id = "one_shot"]
+    #[name = "One-Shot"]
+    OneShot,
+}
+
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PitchEnvMode {
+    #[id = "ahdsr"]
+    #[name = "AHDSR"]
+    Ahdsr,
+    /// The sweep follows the breakpoint envelope drawn in the editor.
+    #[id = "breakpoint"]
+    #[name = "Breakpoint"]
+    Breakpoint,
+}
+
+/// How the body of the kick is synthesized.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Engine {
+    /// A sine wave swept from Start Freq to End Freq by the pitch envelope.
+    #[id = "sweep"]
+    #[name = "Sine Sweep"]
+    Sweep,
+    /// A resonant filter struck by a pulse, like the TR-808's bridged-T network. The pitch
+    /// envelope bends the resonance, the amp decay sets how long it rings, and the amp release
+    /// fades it out after a note off.
+    #[id = "bridged_t"]
+    #[name = "Bridged-T"]
+    BridgedT,
+    /// A shaped triangle with a noisy click, like the TR-909. End Freq tunes it, the pitch decay
+    /// sets how long its two stage sweep down from Start Freq takes, the amp decay sets its decay,
+    /// and Click sets its attack.
+    #[id = "tr909"]
+    #[name = "909"]
+    Tr909,
+    /// Experimental. A square membrane simulated as a grid of masses and springs, tuned so its
+    /// lowest mode follows the pitch sweep.
+    #[id = "membrane"]
+    #[name = "Membrane"]
+    Membrane,
+}
+
+/// How the pitch moves between Start Freq and End Freq as the pitch envelope falls, independent of
+/// the envelope's own shape.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SweepLaw {
+    /// Straight line in hertz, which spends most of the sweep near the bottom.
+    #[id = "linear"]
+    #[name = "Linear"]
+    Linear,
+    /// Drops quickly in pitch and then settles, more so with more Sweep Curve.
+    #[id = "exponential"]
+    #[name = "Exponential"]
+    Exponential,
+    /// Hangs near the top in pitch before dropping, more so with more Sweep Curve.
+    #[id = "logarithmic"]
+    #[name = "Logarithmic"]
+    Logarithmic,
+}
+
+impl SweepLaw {
+    /// The frequency for a pitch envelope value of `env`, where 1 is the start of the sweep.
+    fn frequency(self, env: f32, curve: f32, start_freq: f32, end_freq: f32) -> f32 {
+        let exponent = 1.0 + 4.0 * curve;
+        // the bent laws interpolate in pitch rather than in hertz
+        let pitch_lerp = |t: f32| end_freq * f32::powf(start_freq / end_freq, t);
+        match self {
+            SweepLaw::Linear => lerp(env, end_freq, start_freq),
+            SweepLaw::Exponential => pitch_lerp(env.max(0.0).powf(exponent)),
+            SweepLaw::Logarithmic => pitch_lerp(1.0 - (1.0 - env).max(0.0).powf(exponent)),
+        }
+    }
+}
+
+/// Delays incoming notes to the next grid line while the host transport is playing.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TriggerQuantize {
+    #[id = "off"]
+    #[name = "Off"]
+    Off,
+    #[id = "sixteenth"]
+    #[name = "1/16"]
+    Sixteenth,
+    #[id = "eighth"]
+    #[name = "1/8"]
+    Eighth,
+}
+
+impl TriggerQuantize {
+    fn grid_beats(self) -> Option<f64> {
+        match self {
+            TriggerQuantize::Off => None,
+            TriggerQuantize::Sixteenth => Some(0.25),
+            TriggerQuantize::Eighth => Some(0.5),
+        }
+    }
+}
+
+/// A note length for tempo synced times.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SyncDivision {
+    #[id = "1/32"]
+    #[name = "1/32"]
+    ThirtySecond,
+    #[id = "1/16"]
+    #[name = "1/16"]
+    Sixteenth,
+    #[id = "1/8"]
+    #[name = "1/8"]
+    Eighth,
+    #[id = "1/8d"]
+    #[name = "1/8 Dotted"]
+    DottedEighth,
+    #[id = "1/4"]
+    #[name = "1/4"]
+    Quarter,
+    #[id = "1/2"]
+    #[name = "1/2"]
+    Half,
+}
+
+impl SyncDivision {
+    fn beats(self) -> f64 {
+        match self {
+            SyncDivision::ThirtySecond => 0.125,
+            SyncDivision::Sixteenth => 0.25,
+            SyncDivision::Eighth => 0.5,
+            SyncDivision::DottedEighth => 0.75,
+            SyncDivision::Quarter => 1.0,
+            SyncDivision::Half => 2.0,
+        }
+    }
+}
+
+/// How the reverse swell gets a head start on the hit it leads into.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ReverseTrigger {
+    /// Every hit plays its own swell, and is delayed by the longest a swell can get. The delay is
+    /// reported to the host as latency, so hits still line up with the notes that play them. It
+    /// stays the same whatever the Reverse Length, so changing that doesn't interrupt playback.
+    #[id = "latency"]
+    #[name = "Latency"]
+    Latency,
+    /// Playing Reverse Note starts the swell right away, so it has to be placed before the hit.
+    #[id = "note"]
+    #[name = "Early Note"]
+    Note,
+}
+
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SubsonicSlope {
+    #[id = "12"]
+    #[name = "12 dB/oct"]
+    Db12,
+    #[id = "24"]
+    #[name = "24 dB/oct"]
+    Db24,
+    #[id = "48"]
+    #[name = "48 dB/oct"]
+    Db48,
+}
+
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SubsonicPhase {
+    #[id = "minimum"]
+    #[name = "Minimum"]
+    Minimum,
+    /// Leaves the phase of everything above the cutoff alone, but adds latency. A lot of it at the
+    /// lowest cutoffs and steepest slopes.
+    #[id = "linear"]
+    #[name = "Linear"]
+    Linear,
+}
+
+/// How notes are given voices.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VoiceMode {
+    /// Every note gets its own voice, so hits ring out over each other.
+    #[id = "poly"]
+    #[name = "Poly"]
+    Poly,
+    /// Every note retriggers the one voice.
+    #[id = "mono"]
+    #[name = "Mono"]
+    Mono,
+    /// One voice tuned to the played note, with last note priority and glides between held notes,
+    /// for 808 style bass lines.
+    #[id = "legato"]
+    #[name = "Legato"]
+    Legato,
+}
+
+/// What happens to hits still playing when the host transport stops.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StopAction {
+    #[id = "nothing"]
+    #[name = "Nothing"]
+    Nothing,
+    /// Lets the hits ring out through their release.
+    #[id = "release"]
+    #[name = "Release"]
+    Release,
+    /// Fades out the hits right away, along with the reverse swell and anything held back by the
+    /// latency.
+    #[id = "kill"]
+    #[name = "Kill"]
+    Kill,
+}
+
+/// Which hits get the accent boost.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccentMode {
+    #[id = "off"]
+    #[name = "Off"]
+    Off,
+    /// Counts hits since the transport started playing.
+    #[id = "every_nth"]
+    #[name = "Every Nth Hit"]
+    EveryNth,
+    #[id = "downbeats"]
+    #[name = "Downbeats"]
+    Downbeats,
+}
+
+/// What the mod expression's result sets for each hit: semitones of tune or of sweep, or decibels
+/// of level.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ExpressionDest {
+    #[id = "tune"]
+    #[name = "Tune"]
+    Tune,
+    #[id = "sweep"]
+    #[name = "Sweep"]
+    Sweep,
+    #[id = "level"]
+    #[name = "Level"]
+    Level,
+}
+
+/// What the modwheel controls. At full depth it covers an octave of tune, two octaves of sweep,
+/// half to double the amp decay, or 12 dB of level.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ModwheelDest {
+    #[id = "tune"]
+    #[name = "Tune"]
+    Tune,
+    #[id = "sweep"]
+    #[name = "Sweep"]
+    Sweep,
+    #[id = "decay"]
+    #[name = "Decay"]
+    Decay,
+    #[id = "level"]
+    #[name = "Level"]
+    Level,
+}
+
+/// How incoming note velocities are shaped before anything else sees them.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VelocityCurve {
+    /// Soft playing still comes out fairly loud.
+    #[id = "soft"]
+    #[name = "Soft"]
+    Soft,
+    #[id = "linear"]
+    #[name = "Linear"]
+    Linear,
+    /// Only hard playing reaches the top of the range.
+    #[id = "hard"]
+    #[name = "Hard"]
+    Hard,
+    /// Follows the curve drawn in the editor.
+    #[id = "custom"]
+    #[name = "Custom"]
+    Custom,
+}
+
+impl VelocityCurve {
+    fn apply(self, velocity: f32, custom: &BreakpointEnvelope) -> f32 {
+        match self {
+            VelocityCurve::Soft => velocity.sqrt(),
+            VelocityCurve::Linear => velocity,
+            VelocityCurve::Hard => velocity * velocity,
+            VelocityCurve::Custom => custom.value_at(velocity),
+        }
+    }
+}
+
+/// A note held back by trigger quantization or humanize.
+#[derive(Copy, Clone, Debug)]
+struct PendingNote {
+    note: u8,
+    velocity: f32,
+    /// Samples left until the note plays.
+    delay: usize,
+    /// How long the note was held for, once it was released.
+    released_after: Option<usize>,
+    held: usize,
+}
+
 #[derive(Params)]
 pub struct KickParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<EguiState>,
+    #[persist = "settings"]
+    settings: Arc<RwLock<Settings>>,
+    /// Interpolates the continuous voice parameters from the preset in morph slot A at 0% to the
+    /// one in slot B at 100%. Does nothing until both slots are filled.
+    #[id = "morph"]
+    pub morph: FloatParam,
+    #[persist = "morph"]
+    pub morph_slots: Arc<RwLock<MorphSlots>>,
+    /// Recalls one of the stored scenes, or the live parameters when off. Scenes only hold the
+    /// continuous voice parameters, so switches, modes, and the effects after the voice keep their
+    /// live values.
+    #[id = "scene"]
+    pub scene: IntParam,
+    /// How long recalling a scene takes to fade over.
+    #[id = "scene_fade"]
+    pub scene_fade: FloatParam,
+    /// Recalls scenes with the eight notes starting at Scene Base Note, instead of playing them.
+    #[id = "scene_notes"]
+    pub scene_notes: BoolParam,
+    #[id = "scene_base_note"]
+    pub scene_base_note: IntParam,
+    /// Solos the body while C is held, and the click while C# is held, in Keyswitch Octave.
+    /// Neither plays a hit.
+    #[id = "solo_keyswitches"]
+    pub solo_keyswitches: BoolParam,
+    #[id = "keyswitch_octave"]
+    pub keyswitch_octave: IntParam,
+    #[persist = "scenes"]
+    pub scenes: Arc<RwLock<Scenes>>,
+    /// Plays the patch in each kit slot from the eight notes starting at Kit Base Note, each on
+    /// its own voice and aux output. Other notes are ignored.
+    #[id = "kit_mode"]
+    pub kit_mode: BoolParam,
+    #[id = "kit_base_note"]
+    pub kit_base_note: IntParam,
+    #[persist = "kit"]
+    pub kit: Arc<RwLock<Kit>>,
+    /// IDs of the parameters that keep their current values when a preset is loaded.
+    #[persist = "locks"]
+    pub locked_params: Arc<RwLock<BTreeSet<String>>>,
+    #[id = "engine"]
+    pub engine: EnumParam<Engine>,
+    /// Runs alongside the first engine, faded in with Engine Blend.
+    #[id = "engine_b"]
+    pub engine_b: EnumParam<Engine>,
+    /// From only the first engine at 0% to only Engine B at 100%.
+    #[id = "engine_blend"]
+    pub engine_blend: FloatParam,
+    #[id = "engine_a_tune"]
+    pub engine_a_tune: FloatParam,
+    #[id = "engine_b_tune"]
+    pub engine_b_tune: FloatParam,
+    /// Only has an effect on stereo outputs.
+    #[id = "engine_a_pan"]
+    pub engine_a_pan: FloatParam,
+    #[id = "engine_b_pan"]
+    pub engine_b_pan: FloatParam,
+    /// Tilts the engine's spectrum around 500 Hz. Positive values are brighter.
+    #[id = "engine_a_tilt"]
+    pub engine_a_tilt: FloatParam,
+    #[id = "engine_b_tilt"]
+    pub engine_b_tilt: FloatParam,
+    /// Flips an engine's polarity, for lining up its lows with the other engine's.
+    #[id = "engine_a_invert"]
+    pub engine_a_invert: BoolParam,
+    #[id = "engine_b_invert"]
+    pub engine_b_invert: BoolParam,
+    /// Holds an engine back by a number of samples, also for lining up the engines.
+    #[id = "engine_a_delay"]
+    pub engine_a_delay: IntParam,
+    #[id = "engine_b_delay"]
+    pub engine_b_delay: IntParam,
     #[nested(id_prefix = "amp_env")]
     amp_env: AhdsrParams,
+    /// Gives the amp envelope a short attack and release even when they're set to 0, so hits that
+    /// start away from a zero crossing don't click.
+    #[id = "anti_click"]
+    pub anti_click: BoolParam,
+    /// Lets envelope changes reach hits that are already playing. Otherwise each hit keeps the
+    /// envelope times and sustain it started with.
+    #[id = "live_envelopes"]
+    pub live_envelopes: BoolParam,
     #[nested(id_prefix = "pitch_env")]
     pitch_env: AhdsrParams,
+    #[id = "pitch_env_mode"]
+    pub pitch_env_mode: EnumParam<PitchEnvMode>,
+    #[persist = "pitch-breakpoints"]
+    pub pitch_breakpoints: Arc<RwLock<BreakpointEnvelope>>,
+    /// Smooths the pitch envelope with a one pole lag, rounding off its corners the way an analog
+    /// envelope would. Off at 0 ms.
+    #[id = "pitch_lag"]
+    pub pitch_lag: FloatParam,
+    #[id = "link_decays"]
+    pub link_decays: BoolParam,
+    /// Makes the pitch sweep last Pitch Sync Length at the host's tempo, in place of the pitch
+    /// decay or the breakpoint envelope's own length.
+    #[id = "pitch_sync"]
+    pub pitch_sync: BoolParam,
+    #[id = "pitch_sync_length"]
+    pub pitch_sync_length: EnumParam<SyncDivision>,
+    /// Level of the kick played backwards, leading into the hit. Off at 0%.
+    #[id = "reverse"]
+    pub reverse: FloatParam,
+    /// How much of the kick the swell plays backwards.
+    #[id = "reverse_length"]
+    pub reverse_length: FloatParam,
+    #[id = "reverse_trigger"]
+    pub reverse_trigger: EnumParam<ReverseTrigger>,
+    /// Starts the swell in Early Note mode, instead of playing a hit.
+    #[id = "reverse_note"]
+    pub reverse_note: IntParam,
+    #[id = "length"]
+    pub length: FloatParam,
+    #[id = "start_freq_mode"]
+    pub start_freq_mode: EnumParam<StartFreqMode>,
     #[id = "start_freq"]
     pub start_freq: FloatParam,
+    #[id = "start_offset"]
+    pub start_offset: FloatParam,
     #[id = "end_freq"]
     pub end_freq: FloatParam,
+    /// Plays End Freq as the nearest note, and snaps it there whenever it's changed in the editor.
+    #[id = "snap_end_freq"]
+    pub snap_end_freq: BoolParam,
+    /// Keeps modulation, velocity, and tuning from sweeping the kick below Sweep Floor.
+    #[id = "sweep_end_clamp"]
+    pub sweep_end_clamp: BoolParam,
+    #[id = "sweep_floor"]
+    pub sweep_floor: FloatParam,
+    #[id = "sweep_law"]
+    pub sweep_law: EnumParam<SweepLaw>,
+    /// How strongly the exponential and logarithmic laws bend the sweep. Linear ignores it.
+    #[id = "sweep_curve"]
+    pub sweep_curve: FloatParam,
     #[id = "phase_offset"]
     pub phase_offset: FloatParam,
+    /// Works out each hit's starting phase from its start frequency and amp attack, so the sweep's
+    /// first peak lands where the attack does. Replaces Phase Offset.
+    #[id = "align_peak"]
+    pub align_peak: BoolParam,
+    /// Moves each hit's starting phase by a random amount of up to this fraction of a cycle.
+    #[id = "phase_random"]
+    pub phase_random: FloatParam,
+    /// The note that key follow is measured from.
+    #[id = "key_follow_center"]
+    pub key_follow_center: IntParam,
+    /// Moves the starting phase by this fraction of a cycle for each octave above Key Follow
+    /// Center.
+    #[id = "phase_key_follow"]
+    pub phase_key_follow: FloatParam,
+    /// Changes each hit's level for each octave above Key Follow Center.
+    #[id = "level_key_follow"]
+    pub level_key_follow: FloatParam,
+    /// How much softer notes flatten the pitch sweep. At 100% the sweep's depth follows velocity
+    /// all the way down to no sweep at all.
+    #[id = "velocity_sweep_depth"]
+    pub velocity_sweep_depth: FloatParam,
+    /// Shortens the pitch sweep for notes above Key Follow Center, halving it every octave at 1.
+    /// Negative values lengthen it instead.
+    #[id = "key_sweep_time"]
+    pub key_sweep_time: FloatParam,
+    /// Soft clips each voice on its own, before voices are mixed together.
+    #[id = "voice_clip"]
+    pub voice_clip: BoolParam,
+    #[id = "voice_drive"]
+    pub voice_drive: FloatParam,
+    /// From a gentle rounded clip at 0% to a nearly hard clip at 100%.
+    #[id = "voice_character"]
+    pub voice_character: FloatParam,
+    /// Level of the 909 engine's attack click.
+    #[id = "click"]
+    pub click: FloatParam,
+    /// Mixes in the amp envelope's rising slope as a click, so faster attacks click harder.
+    #[id = "attack_click"]
+    pub attack_click: FloatParam,
+    /// Lowpasses the attack click. Brighter settings make it shorter.
+    #[id = "attack_click_tone"]
+    pub attack_click_tone: FloatParam,
+    /// Holds the engines back behind the attack click and crackle, loosening the attack.
+    #[id = "body_delay"]
+    pub body_delay: FloatParam,
+    /// Fades the engines in from when Body Delay runs out, softening the attack.
+    #[id = "body_fade"]
+    pub body_fade: FloatParam,
+    /// Level of a layer of random clicks and pops, following the amp envelope.
+    #[id = "crackle"]
+    pub crackle: FloatParam,
+    /// Average number of crackles per second.
+    #[id = "crackle_density"]
+    pub crackle_density: FloatParam,
+    /// Lowpasses the crackles.
+    #[id = "crackle_tone"]
+    pub crackle_tone: FloatParam,
+    /// How far hard strikes push the membrane engine's pitch up before it settles.
+    #[id = "membrane_tension"]
+    pub membrane_tension: FloatParam,
+    /// From a two second ring down to 50 ms.
+    #[id = "membrane_damping"]
+    pub membrane_damping: FloatParam,
+    /// Where the membrane engine is struck, from the center to near the rim.
+    #[id = "strike_position"]
+    pub strike_position: FloatParam,
+    #[id = "output_gain"]
+    pub output_gain: FloatParam,
+    /// Gain staging for the project, applied last. Belongs to the instance rather than the patch,
+    /// so it stays put while browsing presets.
+    #[id = "output_trim"]
+    pub output_trim: FloatParam,
+    /// Saturates the output like tape or a transformer. Off at 0%.
+    #[id = "analog_clip"]
+    pub analog_clip: FloatParam,
+    /// Blends between the synth before and after Multiband and Analog Clip.
+    #[id = "fx_mix"]
+    pub fx_mix: FloatParam,
+    /// Sends the synth from before the output effects to the Dry aux output, for processing it in
+    /// parallel outside the plugin.
+    #[id = "dry_output"]
+    pub dry_output: BoolParam,
+    /// A steep highpass on the output, for keeping subsonic rumble off club systems. Its settings
+    /// can't be automated, since changing the linear phase filter clears it and changes the latency.
+    #[id = "subsonic"]
+    pub subsonic: BoolParam,
+    #[id = "subsonic_cutoff"]
+    pub subsonic_cutoff: FloatParam,
+    #[id = "subsonic_slope"]
+    pub subsonic_slope: EnumParam<SubsonicSlope>,
+    #[id = "subsonic_phase"]
+    pub subsonic_phase: EnumParam<SubsonicPhase>,
+    /// Splits the output at Crossover so each band can be driven on its own.
+    #[id = "multiband"]
+    pub multiband: BoolParam,
+    #[id = "multiband_crossover"]
+    pub multiband_crossover: FloatParam,
+    #[id = "multiband_low_drive"]
+    pub multiband_low_drive: FloatParam,
+    #[id = "multiband_low_level"]
+    pub multiband_low_level: FloatParam,
+    #[id = "multiband_high_drive"]
+    pub multiband_high_drive: FloatParam,
+    #[id = "multiband_high_level"]
+    pub multiband_high_level: FloatParam,
+    #[id = "auto_level"]
+    pub auto_level: BoolParam,
+    #[id = "quantize"]
+    pub quantize: EnumParam<TriggerQuantize>,
+    /// Moves each MIDI hit by a random amount of up to this much either way. While it's on, hits
+    /// are held back by the most it can move them and that's reported as latency, so they can
+    /// land ahead of the grid as well as behind it. Legato mode isn't humanized, but its notes are
+    /// held back all the same.
+    #[id = "humanize"]
+    pub humanize: FloatParam,
+    #[id = "voice_mode"]
+    pub voice_mode: EnumParam<VoiceMode>,
+    /// How long Legato mode takes to glide between held notes.
+    #[id = "glide"]
+    pub glide: FloatParam,
+    /// Restarts the pitch envelope on notes played legato, so every note thumps instead of only
+    /// the first one.
+    #[id = "legato_pitch_retrigger"]
+    pub legato_pitch_retrigger: BoolParam,
+    /// What to do with hits still playing when the host transport stops.
+    #[id = "on_stop"]
+    pub on_stop: EnumParam<StopAction>,
+    #[id = "accent_mode"]
+    pub accent_mode: EnumParam<AccentMode>,
+    #[id = "accent_every"]
+    pub accent_every: IntParam,
+    #[id = "accent_level"]
+    pub accent_level: FloatParam,
+    /// Raises the start of the pitch sweep on accented hits.
+    #[id = "accent_pitch"]
+    pub accent_pitch: FloatParam,
+    /// How far full aftertouch bends the whole sweep.
+    #[id = "pressure_tune"]
+    pub pressure_tune: FloatParam,
+    /// Load presets from the bank when MIDI program changes come in.
+    #[id = "program_change"]
+    pub program_change: BoolParam,
+    #[id = "modwheel_dest"]
+    pub modwheel_dest: EnumParam<ModwheelDest>,
+    /// Bipolar, so the modwheel can also pull its destination down.
+    #[id = "modwheel_depth"]
+    pub modwheel_depth: FloatParam,
+    /// A formula worked out at the start of each hit, for routings the other mod sources don't
+    /// cover.
+    #[persist = "expression"]
+    pub expression: Arc<RwLock<Expression>>,
+    #[id = "expression_dest"]
+    pub expression_dest: EnumParam<ExpressionDest>,
+    /// Offsets each hit by the generative rules.
+    #[id = "generative"]
+    pub generative: BoolParam,
+    #[persist = "generative"]
+    pub generative_rules: Arc<RwLock<Generative>>,
+    /// Seeds everything randomized, so renders can be reproduced.
+    #[id = "seed"]
+    pub seed: IntParam,
+    /// Starts the random sequence over from the seed every time the transport starts playing.
+    #[id = "reseed_on_play"]
+    pub reseed_on_play: BoolParam,
+    /// Plays every note at full velocity.
+    #[id = "fixed_velocity"]
+    pub fixed_velocity: BoolParam,
+    #[id = "velocity_curve"]
+    pub velocity_curve: EnumParam<VelocityCurve>,
+    /// Maps velocity (as time, from 0 to 1) to the velocity the synth uses.
+    #[persist = "velocity-curve"]
+    pub custom_velocity_curve: Arc<RwLock<BreakpointEnvelope>>,
+    /// How much quieter velocity 1 plays than velocity 127. The velocities in between are spaced
+    /// evenly in decibels, after the velocity curve.
+    #[id = "dynamic_range"]
+    pub dynamic_range: FloatParam,
+    /// Plays a hit on each rising edge of the first Trigger CV channel, held for as long as the
+    /// gate stays high.
+    #[id = "cv_trigger"]
+    pub cv_trigger: BoolParam,
+    #[id = "cv_threshold"]
+    pub cv_threshold: FloatParam,
+    /// Transposes CV triggered hits by the second Trigger CV channel at one volt per octave, with
+    /// 0 V leaving the tune alone.
+    #[id = "cv_pitch"]
+    pub cv_pitch: BoolParam,
+}
+
+/// Plain values of everything a voice needs to render a hit, so the same voice code can run from
+/// either the live smoothed parameters or from a snapshot on another thread.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VoiceParams {
+    engine: Engine,
+    engine_b: Engine,
+    engine_blend: f32,
+    /// Semitones for engines A and B.
+    engine_tune: [f32; 2],
+    /// From -1 (left) to 1 (right) for engines A and B.
+    engine_pan: [f32; 2],
+    /// Decibels for engines A and B.
+    engine_tilt: [f32; 2],
+    /// 1, or -1 for inverted, for engines A and B.
+    engine_polarity: [f32; 2],
+    /// Samples for engines A and B.
+    engine_delay: [usize; 2],
+    amp_env: AhdsrValues,
+    pitch_env: AhdsrValues,
+    anti_click: bool,
+    live_envelopes: bool,
+    start_freq: f32,
+    end_freq: f32,
+    /// The lowest frequency the voice plays, or 0 when it isn't clamped.
+    sweep_floor: f32,
+    phase_offset: f32,
+    sweep_law: SweepLaw,
+    sweep_curve: f32,
+    /// Gain into the voice's soft clipper, or `None` when it's bypassed.
+    voice_drive: Option<f32>,
+    voice_character: f32,
+    click: f32,
+    attack_click: f32,
+    attack_click_tone: f32,
+    /// Seconds.
+    body_delay: f32,
+    /// Seconds.
+    body_fade: f32,
+    crackle: f32,
+    crackle_density: f32,
+    crackle_tone: f32,
+    membrane_tension: f32,
+    membrane_damping: f32,
+    strike_position: f32,
+    /// Replaces the pitch envelope when set.
+    pitch_breakpoints: Option<BreakpointEnvelope>,
+    /// Seconds.
+    pitch_lag: f32,
+}
+
+impl VoiceParams {
+    /// The starting phase that puts the sine's first peak at the top of the amp envelope's attack,
+    /// taking the sweep to still be at its start frequency until then.
+    fn peak_aligned_phase(&self) -> f32 {
+        let attack = match self.anti_click {
+            true => f32::max(self.amp_env.attack, MIN_ATTACK_SECONDS),
+            false => self.amp_env.attack,
+        };
+        0.25 - self.start_freq * attack
+    }
+
+    /// Scales the depth of the pitch sweep in semitones by `depth`, and its length by `time`.
+    fn scale_sweep(&mut self, depth: f32, time: f32) {
+        if depth != 1.0 {
+            self.start_freq = self.end_freq * f32::powf(self.start_freq / self.end_freq, depth);
+        }
+        if time != 1.0 {
+            self.pitch_env.attack *= time;
+            self.pitch_env.hold *= time;
+            self.pitch_env.decay *= time;
+            self.pitch_env.release *= time;
+            if let Some(breakpoints) = &mut self.pitch_breakpoints {
+                *breakpoints = breakpoints.scaled(time);
+            }
+        }
+    }
+
+    /// How long it takes the pitch to reach the end of its sweep.
+    fn sweep_time(&self) -> f32 {
+        match &self.pitch_breakpoints {
+            Some(breakpoints) => breakpoints.duration(),
+            None => self.pitch_env.attack + self.pitch_env.hold + self.pitch_env.decay,
+        }
+    }
+}
+
+impl KickParams {
+    /// The persisted breakpoints can't be safely read from the audio thread, so callers pass in a
+    /// copy of them.
+    fn voice_params_with(
+        &self,
+        value: impl Fn(&FloatParam) -> f32,
+        pitch_breakpoints: &BreakpointEnvelope,
+    ) -> VoiceParams {
+        let length = value(&self.length);
+        let amp_env = self.amp_env.values_with(&value, length);
+        // with linked decays, the pitch envelope stretches along with the amp envelope's decay
+        // relative to where it sits by default.
+        let pitch_time_scale = match self.link_decays.value() {
+            true => amp_env.decay / self.amp_env.decay_time.default_plain_value(),
+            false => 1.0,
+        };
+        let pitch_env = self.pitch_env.values_with(&value, pitch_time_scale);
+
+        let end_freq = match self.snap_end_freq.value() {
+            true => nearest_note_freq(value(&self.end_freq)),
+            false => value(&self.end_freq),
+        };
+        let start_freq = value(&self.start_freq);
+        let start_offset = value(&self.start_offset);
+        let start_freq = match self.start_freq_mode.value() {
+            StartFreqMode::Absolute => start_freq,
+            StartFreqMode::Relative => end_freq * semitones_to_ratio(start_offset),
+        };
+
+        let pitch_breakpoints = match self.pitch_env_mode.value() {
+            PitchEnvMode::Ahdsr => None,
+            PitchEnvMode::Breakpoint => Some(pitch_breakpoints.scaled(pitch_time_scale)),
+        };
+
+        VoiceParams {
+            engine: self.engine.value(),
+            engine_b: self.engine_b.value(),
+            engine_blend: value(&self.engine_blend),
+            engine_tune: [value(&self.engine_a_tune), value(&self.engine_b_tune)],
+            engine_pan: [value(&self.engine_a_pan), value(&self.engine_b_pan)],
+            engine_tilt: [value(&self.engine_a_tilt), value(&self.engine_b_tilt)],
+            engine_polarity: [&self.engine_a_invert, &self.engine_b_invert].map(|invert| {
+                match invert.value() {
+                    true => -1.0,
+                    false => 1.0,
+                }
+            }),
+            engine_delay: [&self.engine_a_delay, &self.engine_b_delay]
+                .map(|delay| delay.value() as usize),
+            amp_env,
+            pitch_env,
+            start_freq,
+            end_freq,
+            sweep_floor: match self.sweep_end_clamp.value() {
+                true => value(&self.sweep_floor),
+                false => 0.0,
+            },
+            phase_offset: value(&self.phase_offset),
+            sweep_law: self.sweep_law.value(),
+            sweep_curve: value(&self.sweep_curve),
+            voice_drive: match self.voice_clip.value() {
+                true => Some(value(&self.voice_drive)),
+                false => None,
+            },
+            voice_character: value(&self.voice_character),
+            click: value(&self.click),
+            attack_click: value(&self.attack_click),
+            attack_click_tone: value(&self.attack_click_tone),
+            body_delay: value(&self.body_delay) / 1000.0,
+            body_fade: value(&self.body_fade) / 1000.0,
+            crackle: value(&self.crackle),
+            crackle_density: value(&self.crackle_density),
+            crackle_tone: value(&self.crackle_tone),
+            membrane_tension: value(&self.membrane_tension),
+            membrane_damping: value(&self.membrane_damping),
+            strike_position: value(&self.strike_position),
+            pitch_breakpoints,
+            pitch_lag: value(&self.pitch_lag) / 1000.0,
+            anti_click: self.anti_click.value(),
+            live_envelopes: self.live_envelopes.value(),
+        }
+    }
+
+    /// Advances the smoothers of every parameter the voice uses. Only call this from the audio
+    /// thread.
+    fn next_voice_params(
+        &self,
+        pitch_breakpoints: &BreakpointEnvelope,
+        overrides: &Overrides,
+    ) -> VoiceParams {
+        self.voice_params_with(
+            |param| overrides.value(param, param.smoothed.next()),
+            pitch_breakpoints,
+        )
+    }
+
+    /// Advances the smoothers of every parameter the voice uses by `steps` samples at once. Only
+    /// call this from the audio thread.
+    fn skip_voice_params(&self, steps: u32, pitch_breakpoints: &BreakpointEnvelope) {
+        self.voice_params_with(|param| param.smoothed.next_step(steps), pitch_breakpoints);
+    }
+
+    /// The current target values, without touching any smoothers.
+    fn voice_params(
+        &self,
+        pitch_breakpoints: &BreakpointEnvelope,
+        overrides: &Overrides,
+    ) -> VoiceParams {
+        self.voice_params_with(
+            |param| overrides.value(param, param.value()),
+            pitch_breakpoints,
+        )
+    }
 }
 
 impl Default for KickSynth {
     fn default() -> Self {
         Self {
-            params: Default::default(),
+            params: Arc::new(KickParams::default()),
+            shared: Default::default(),
             sample_rate: 0.0,
-            osc_state: Default::default(),
+            voices: [VoiceSlot::default(); MAX_VOICES],
+            current_voice: 0,
+            voice_age: 0,
+            pitch_breakpoints: Default::default(),
+            velocity_curve: Default::default(),
+            morph_table: Default::default(),
+            scene_table: Default::default(),
+            program_table: Default::default(),
+            kit_table: Default::default(),
+            kit_choke_groups: [0; KIT_SLOTS],
+            kit_notes: [None; KIT_SLOTS],
+            expression: Program::default(),
+            generative: Generative::default(),
+            audition_note: Settings::default().audition_note,
+            settings_loaded: false,
+            generative_state: GenerativeState::default(),
+            kit_params: [None; KIT_SLOTS],
+            scene: None,
+            previous_scene: None,
+            scene_fade: 1.0,
+            last_scene_param: 0,
+            tempo: None,
+            hit_count: 0,
+            was_playing: false,
+            last_level_measurement: None,
+            hit_meter: Default::default(),
+            hit_recorder: Default::default(),
+            reference_recorder: Default::default(),
+            null_test: Default::default(),
+            reference_silent_samples: 0,
+            cpu_meter: Default::default(),
+            layer_correlation: Default::default(),
+            peak_history: Default::default(),
             midi_frequency: 200.0,
             midi_velocity: 0.0,
-            pitch_env_state: Default::default(),
-            amp_env_state: Default::default(),
+            pressure: Smoother::new(SmoothingStyle::Linear(20.0)),
+            modwheel: Smoother::new(SmoothingStyle::Linear(20.0)),
             last_midi_note: None,
+            held_solos: [false; 2],
+            pending_notes: [None; MAX_PENDING_NOTES],
+            audition_holding: None,
+            held_notes: NoteStack::default(),
+            bass_note: 36.0,
+            bass_target: 36.0,
+            cv_gate_high: false,
+            cv_delay: Default::default(),
+            rng: Rng::new(0),
+            rng_seed: 0,
+            swell: Swell::default(),
+            kill_fade: None,
+            hit_delay: Default::default(),
+            kit_delays: Default::default(),
+            dry_delay: Default::default(),
+            latency: 0,
+            reported_latency: 0,
+            last_swell_render: None,
+            last_voice_params: None,
+            analog_clip: Default::default(),
+            multiband: Default::default(),
+            solo_body_filters: Default::default(),
+            solo_click_filters: Default::default(),
+            subsonic: Default::default(),
+            #[cfg(feature = "crash-log")]
+            crash_log: Default::default(),
         }
     }
 }
@@ -177,6 +1491,166 @@ impl Default for KickSynth {
 impl Default for KickParams {
     fn default() -> Self {
         Self {
+            editor_state: editor::default_state(),
+            settings: Default::default(),
+            morph: FloatParam::new("Morph", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(smoothing::mix())
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            morph_slots: Default::default(),
+            scene: IntParam::new(
+                "Scene",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: SCENE_COUNT as i32,
+                },
+            )
+            .with_value_to_string(Arc::new(|value| match value {
+                0 => String::from("Off"),
+                scene => scene.to_string(),
+            })),
+            scene_fade: FloatParam::new(
+                "Scene Fade",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_string_to_value(s2v_f32_ms()),
+            scene_notes: BoolParam::new("Scene Notes", false),
+            scene_base_note: IntParam::new(
+                "Scene Base Note",
+                24,
+                IntRange::Linear { min: 0, max: 120 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            scenes: Default::default(),
+            solo_keyswitches: BoolParam::new("Solo Keyswitches", false),
+            keyswitch_octave: IntParam::new(
+                "Keyswitch Octave",
+                0,
+                IntRange::Linear { min: -1, max: 9 },
+            )
+            .with_value_to_string(Arc::new(|octave| format!("C{octave}")))
+            .with_string_to_value(Arc::new(|string| {
+                let string = string.trim();
+                let string = string.strip_prefix(['C', 'c']).unwrap_or(string);
+                string.parse().ok()
+            })),
+            kit_mode: BoolParam::new("Kit Mode", false),
+            kit_base_note: IntParam::new(
+                "Kit Base Note",
+                36,
+                IntRange::Linear { min: 0, max: 120 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            kit: Default::default(),
+            locked_params: Default::default(),
+            engine: EnumParam::new("Engine A", Engine::Sweep),
+            engine_b: EnumParam::new("Engine B", Engine::BridgedT),
+            engine_blend: FloatParam::new(
+                "Engine Blend",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(smoothing::mix())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            engine_a_tune: FloatParam::new(
+                "Engine A Tune",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_smoother(smoothing::pitch())
+            .with_value_to_string(v2s_f32_semitones())
+            .with_string_to_value(s2v_f32_semitones()),
+            engine_b_tune: FloatParam::new(
+                "Engine B Tune",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_smoother(smoothing::pitch())
+            .with_value_to_string(v2s_f32_semitones())
+            .with_string_to_value(s2v_f32_semitones()),
+            engine_a_pan: FloatParam::new(
+                "Engine A Pan",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_panning())
+            .with_string_to_value(formatters::s2v_f32_panning()),
+            engine_b_pan: FloatParam::new(
+                "Engine B Pan",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_panning())
+            .with_string_to_value(formatters::s2v_f32_panning()),
+            engine_a_tilt: FloatParam::new(
+                "Engine A Tilt",
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_smoother(smoothing::decibels())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_db()),
+            engine_b_tilt: FloatParam::new(
+                "Engine B Tilt",
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_smoother(smoothing::decibels())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_db()),
+            engine_a_invert: BoolParam::new("Engine A Invert", false),
+            engine_b_invert: BoolParam::new("Engine B Invert", false),
+            engine_a_delay: IntParam::new(
+                "Engine A Delay",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: LAYER_DELAY_MAX_SAMPLES as i32 - 1,
+                },
+            )
+            .with_unit(" smp"),
+            engine_b_delay: IntParam::new(
+                "Engine B Delay",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: LAYER_DELAY_MAX_SAMPLES as i32 - 1,
+                },
+            )
+            .with_unit(" smp"),
             amp_env: AhdsrParams::new(
                 "Amp ",
                 FloatRange::skew_factor(-2.0),
@@ -184,6 +1658,8 @@ impl Default for KickParams {
                 AhdsrValues::ahdr_all(10.0),
                 AhdsrValues::ahdsr(0.0, 0.0, 0.5, 0.0, 0.5),
             ),
+            anti_click: BoolParam::new("Anti-Click", false),
+            live_envelopes: BoolParam::new("Live Envelopes", false),
             pitch_env: AhdsrParams::new(
                 "Pitch ",
                 FloatRange::skew_factor(-2.0),
@@ -191,6 +1667,57 @@ impl Default for KickParams {
                 AhdsrValues::ahdr_all(1.0),
                 AhdsrValues::ahdsr(0.0, 0.0, 0.025, 0.0, 0.025),
             ),
+            pitch_env_mode: EnumParam::new("Pitch Env Mode", PitchEnvMode::Ahdsr),
+            pitch_breakpoints: Default::default(),
+            pitch_lag: FloatParam::new(
+                "Pitch Lag",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 50.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_smoother(smoothing::time())
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_ms()),
+            link_decays: BoolParam::new("Link Decays", false),
+            pitch_sync: BoolParam::new("Pitch Sync", false),
+            pitch_sync_length: EnumParam::new("Pitch Sync Length", SyncDivision::Eighth),
+            reverse: FloatParam::new("Reverse", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(smoothing::amount())
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            reverse_length: FloatParam::new(
+                "Reverse Length",
+                250.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: REVERSE_MAX_SECONDS * 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_string_to_value(s2v_f32_ms()),
+            reverse_trigger: EnumParam::new("Reverse Trigger", ReverseTrigger::Latency),
+            reverse_note: IntParam::new("Reverse Note", 35, IntRange::Linear { min: 0, max: 127 })
+                .with_value_to_string(formatters::v2s_i32_note_formatter())
+                .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            length: FloatParam::new(
+                "Length",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 4.0,
+                    factor: FloatRange::skew_factor(-1.2),
+                },
+            )
+            .with_smoother(smoothing::time())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            start_freq_mode: EnumParam::new("Start Freq Mode", StartFreqMode::Absolute),
             start_freq: FloatParam::new(
                 "Start Freq",
                 1000.0,
@@ -200,8 +1727,20 @@ impl Default for KickParams {
                     factor: FloatRange::skew_factor(-2.0),
                 },
             )
+            .with_smoother(smoothing::frequency())
             .with_value_to_string(formatters::v2s_f32_hz_then_khz_with_note_name(0, true))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            .with_string_to_value(s2v_f32_hz_or_note()),
+            start_offset: FloatParam::new(
+                "Start Offset",
+                48.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 72.0,
+                },
+            )
+            .with_smoother(smoothing::pitch())
+            .with_value_to_string(v2s_f32_semitones())
+            .with_string_to_value(s2v_f32_semitones()),
             end_freq: FloatParam::new(
                 "End Freq",
                 41.0,
@@ -211,14 +1750,435 @@ impl Default for KickParams {
                     factor: FloatRange::skew_factor(-2.0),
                 },
             )
+            .with_smoother(smoothing::frequency())
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz_with_note_name(0, true))
+            .with_string_to_value(s2v_f32_hz_or_note()),
+            snap_end_freq: BoolParam::new("Snap End Freq", false),
+            sweep_end_clamp: BoolParam::new("Sweep End Clamp", false),
+            sweep_floor: FloatParam::new(
+                "Sweep Floor",
+                30.0,
+                FloatRange::Linear {
+                    min: 20.0,
+                    max: 60.0,
+                },
+            )
+            .with_smoother(smoothing::frequency())
             .with_value_to_string(formatters::v2s_f32_hz_then_khz_with_note_name(0, true))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            .with_string_to_value(s2v_f32_hz_or_note()),
+            sweep_law: EnumParam::new("Sweep Law", SweepLaw::Linear),
+            sweep_curve: FloatParam::new(
+                "Sweep Curve",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
             phase_offset: FloatParam::new(
                 "Phase Offset",
                 0.25,
                 FloatRange::Linear { min: 0.0, max: 1.0 },
             )
             .with_step_size(0.01),
+            align_peak: BoolParam::new("Align Peak to Attack", false),
+            phase_random: FloatParam::new(
+                "Phase Random",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_step_size(0.01),
+            key_follow_center: IntParam::new(
+                "Key Follow Center",
+                36,
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            phase_key_follow: FloatParam::new(
+                "Phase Key Follow",
+                0.0,
+                FloatRange::Linear {
+                    min: -0.5,
+                    max: 0.5,
+                },
+            )
+            .with_step_size(0.01)
+            .with_unit(" /oct"),
+            level_key_follow: FloatParam::new(
+                "Level Key Follow",
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_step_size(0.1)
+            .with_unit(" dB/oct"),
+            velocity_sweep_depth: FloatParam::new(
+                "Velocity to Sweep Depth",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            key_sweep_time: FloatParam::new(
+                "Key to Sweep Time",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_step_size(0.01)
+            .with_unit(" /oct"),
+            output_gain: FloatParam::new(
+                "Output Gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 12.0),
+                },
+            )
+            .with_smoother(smoothing::gain())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            output_trim: FloatParam::new(
+                "Output Trim",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 24.0),
+                },
+            )
+            .with_smoother(smoothing::gain())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            analog_clip: FloatParam::new(
+                "Analog Clip",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(smoothing::mix())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            fx_mix: FloatParam::new("FX Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(smoothing::mix())
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            dry_output: BoolParam::new("Dry Output", false),
+            subsonic: BoolParam::new("Subsonic Filter", false),
+            subsonic_cutoff: FloatParam::new(
+                "Subsonic Cutoff",
+                30.0,
+                FloatRange::Linear {
+                    min: SUBSONIC_MIN_HZ,
+                    max: SUBSONIC_MAX_HZ,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(s2v_f32_hz_or_note())
+            .non_automatable(),
+            subsonic_slope: EnumParam::new("Subsonic Slope", SubsonicSlope::Db24).non_automatable(),
+            subsonic_phase: EnumParam::new("Subsonic Phase", SubsonicPhase::Minimum)
+                .non_automatable(),
+            multiband: BoolParam::new("Multiband", false),
+            multiband_crossover: FloatParam::new(
+                "Crossover",
+                120.0,
+                FloatRange::Skewed {
+                    min: 40.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(s2v_f32_hz_or_note()),
+            multiband_low_drive: FloatParam::new(
+                "Low Band Drive",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 24.0),
+                },
+            )
+            .with_smoother(smoothing::gain())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            multiband_low_level: FloatParam::new(
+                "Low Band Level",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 12.0),
+                },
+            )
+            .with_smoother(smoothing::gain())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            multiband_high_drive: FloatParam::new(
+                "High Band Drive",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 24.0),
+                },
+            )
+            .with_smoother(smoothing::gain())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            multiband_high_level: FloatParam::new(
+                "High Band Level",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 12.0),
+                },
+            )
+            .with_smoother(smoothing::gain())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            voice_clip: BoolParam::new("Voice Clip", false),
+            voice_drive: FloatParam::new(
+                "Voice Drive",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 24.0),
+                },
+            )
+            .with_smoother(smoothing::gain())
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            voice_character: FloatParam::new(
+                "Voice Character",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            attack_click: FloatParam::new(
+                "Attack Click",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            attack_click_tone: FloatParam::new(
+                "Attack Click Tone",
+                4000.0,
+                FloatRange::Skewed {
+                    min: 500.0,
+                    max: 16000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(smoothing::frequency())
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(s2v_f32_hz_or_note()),
+            body_delay: FloatParam::new(
+                "Body Delay",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: BODY_DELAY_MAX_MS,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.01)
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_string_to_value(s2v_f32_ms()),
+            body_fade: FloatParam::new("Body Fade", 0.0, FloatRange::Linear { min: 0.0, max: 5.0 })
+                .with_unit(" ms")
+                .with_step_size(0.01)
+                .with_value_to_string(formatters::v2s_f32_rounded(2))
+                .with_string_to_value(s2v_f32_ms()),
+            crackle: FloatParam::new("Crackle", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(smoothing::amount())
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            crackle_density: FloatParam::new(
+                "Crackle Density",
+                50.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(smoothing::frequency())
+            .with_unit(" /s")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            crackle_tone: FloatParam::new(
+                "Crackle Tone",
+                6000.0,
+                FloatRange::Skewed {
+                    min: 500.0,
+                    max: 16000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(smoothing::frequency())
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(s2v_f32_hz_or_note()),
+            click: FloatParam::new("Click", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(smoothing::amount())
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            membrane_tension: FloatParam::new(
+                "Membrane Tension",
+                0.3,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            membrane_damping: FloatParam::new(
+                "Membrane Damping",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            strike_position: FloatParam::new(
+                "Strike Position",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            auto_level: BoolParam::new("Auto Level", false),
+            quantize: EnumParam::new("Quantize", TriggerQuantize::Off),
+            humanize: FloatParam::new(
+                "Humanize",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: HUMANIZE_MAX_MS,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1)
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_ms()),
+            voice_mode: EnumParam::new("Voice Mode", VoiceMode::Mono),
+            glide: FloatParam::new(
+                "Glide",
+                50.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .with_string_to_value(s2v_f32_ms()),
+            legato_pitch_retrigger: BoolParam::new("Legato Pitch Retrigger", false),
+            on_stop: EnumParam::new("On Stop", StopAction::Nothing),
+            accent_mode: EnumParam::new("Accent Mode", AccentMode::Off),
+            accent_every: IntParam::new("Accent Every", 4, IntRange::Linear { min: 2, max: 16 })
+                .with_unit(" hits"),
+            accent_level: FloatParam::new(
+                "Accent Level",
+                util::db_to_gain(3.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 12.0),
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            accent_pitch: FloatParam::new(
+                "Accent Pitch",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 24.0,
+                },
+            )
+            .with_value_to_string(v2s_f32_semitones())
+            .with_string_to_value(s2v_f32_semitones()),
+            pressure_tune: FloatParam::new(
+                "Pressure Tune",
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_smoother(smoothing::pitch())
+            .with_value_to_string(v2s_f32_semitones())
+            .with_string_to_value(s2v_f32_semitones()),
+            program_change: BoolParam::new("Program Change", false),
+            modwheel_dest: EnumParam::new("Modwheel Dest", ModwheelDest::Tune),
+            modwheel_depth: FloatParam::new(
+                "Modwheel Depth",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(smoothing::amount())
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            expression: Default::default(),
+            expression_dest: EnumParam::new("Expression Dest", ExpressionDest::Tune),
+            generative: BoolParam::new("Generative", false),
+            generative_rules: Default::default(),
+            seed: IntParam::new("Seed", 0, IntRange::Linear { min: 0, max: 9999 }),
+            reseed_on_play: BoolParam::new("Reseed On Play", true),
+            fixed_velocity: BoolParam::new("Fixed Velocity", false),
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::Linear),
+            custom_velocity_curve: Arc::new(RwLock::new(BreakpointEnvelope::from(vec![
+                Breakpoint::new(0.0, 0.0, 0.0),
+                Breakpoint::new(1.0, 1.0, 0.0),
+            ]))),
+            dynamic_range: FloatParam::new(
+                "Dynamic Range",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(s2v_f32_db()),
+            cv_trigger: BoolParam::new("CV Trigger", false),
+            cv_threshold: FloatParam::new(
+                "CV Threshold",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 5.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" V")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            cv_pitch: BoolParam::new("CV Pitch", false),
         }
     }
 }
@@ -230,85 +2190,1621 @@ impl Plugin for KickSynth {
     const EMAIL: &'static str = "";
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: None,
-        main_output_channels: NonZeroU32::new(1),
-        ..AudioIOLayout::const_default()
-    }];
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: None,
+            main_output_channels: NonZeroU32::new(1),
+            aux_input_ports: &[new_nonzero_u32(1), new_nonzero_u32(2)],
+            aux_output_ports: &MONO_AUX_OUTPUTS,
+            names: PortNames {
+                aux_inputs: &["Reference", "Trigger CV"],
+                aux_outputs: &AUX_OUTPUT_NAMES,
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: None,
+            main_output_channels: NonZeroU32::new(1),
+            aux_input_ports: &[new_nonzero_u32(1)],
+            aux_output_ports: &MONO_AUX_OUTPUTS,
+            names: PortNames {
+                aux_inputs: &["Reference"],
+                aux_outputs: &AUX_OUTPUT_NAMES,
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: None,
+            main_output_channels: NonZeroU32::new(1),
+            aux_output_ports: &MONO_AUX_OUTPUTS,
+            names: PortNames {
+                aux_outputs: &AUX_OUTPUT_NAMES,
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        // the same again in stereo, for the engine pans
+        AudioIOLayout {
+            main_input_channels: None,
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(1), new_nonzero_u32(2)],
+            aux_output_ports: &STEREO_AUX_OUTPUTS,
+            names: PortNames {
+                aux_inputs: &["Reference", "Trigger CV"],
+                aux_outputs: &AUX_OUTPUT_NAMES,
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: None,
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(1)],
+            aux_output_ports: &STEREO_AUX_OUTPUTS,
+            names: PortNames {
+                aux_inputs: &["Reference"],
+                aux_outputs: &AUX_OUTPUT_NAMES,
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: None,
+            main_output_channels: NonZeroU32::new(2),
+            aux_output_ports: &STEREO_AUX_OUTPUTS,
+            names: PortNames {
+                aux_outputs: &AUX_OUTPUT_NAMES,
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+    ];
 
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = Task;
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    // channel pressure is only sent with `MidiCCs`
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
-    // fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
-    // }
-
-    fn initialize(
-        &mut self,
-        audio_io_layout: &AudioIOLayout,
-        buffer_config: &BufferConfig,
-        context: &mut impl InitContext<Self>,
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        let shared = self.shared.clone();
+        Box::new(move |task| match task {
+            Task::MeasureLevel {
+                params,
+                sample_rate,
+            } => {
+                let hit = offline::render_hit(&params, sample_rate, LEVEL_MEASUREMENT_SECONDS);
+                let peak = offline::peak(&hit);
+                let gain = match peak > 0.0 {
+                    true => util::db_to_gain(AUTO_LEVEL_TARGET_DB) / peak,
+                    false => 1.0,
+                };
+                shared.level_gain.store(gain, Ordering::Relaxed);
+                shared
+                    .level_measurement_pending
+                    .store(false, Ordering::Release);
+            }
+            Task::RenderSwell {
+                params,
+                sample_rate,
+                length,
+            } => {
+                let swell = reverse::render_swell(&params, sample_rate, length);
+                *shared.swell_render.samples.lock().unwrap() = swell;
+                shared.swell_render.ready.store(true, Ordering::Release);
+                shared.swell_render.pending.store(false, Ordering::Release);
+            }
+            Task::AnalyzeLastHit => {
+                let capture = shared.last_hit_capture.lock().unwrap();
+                let tail_len = (TAIL_ANALYSIS_SECONDS * capture.sample_rate) as usize;
+                let tail_start = usize::min(capture.sweep_end, capture.samples.len());
+                let tail_end = usize::min(tail_start + tail_len, capture.samples.len());
+                let pitch = analysis::detect_pitch(
+                    &capture.samples[tail_start..tail_end],
+                    capture.sample_rate,
+                );
+                shared
+                    .tail_pitch
+                    .store(pitch.unwrap_or(0.0), Ordering::Relaxed);
+                drop(capture);
+                shared.compare_to_reference();
+            }
+            Task::AnalyzeReference => {
+                let capture = shared.reference_capture.lock().unwrap();
+                let analysis = analysis::analyze_reference(&capture.samples, capture.sample_rate);
+                *shared.reference_analysis.lock().unwrap() = analysis;
+                drop(capture);
+                shared.compare_to_reference();
+            }
+            Task::ScanUserPresets { dir, force } => shared.scan_user_presets(&dir, force),
+            Task::SavePreset { preset, dir } => {
+                match presets::save(&preset, &dir) {
+                    Ok(path) => shared.notifications.push(
+                        Severity::Info,
+                        format!("Saved preset to {}", path.display()),
+                    ),
+                    Err(err) => shared
+                        .notifications
+                        .push(Severity::Error, format!("Could not save preset: {err}")),
+                }
+                shared.scan_user_presets(&dir, true);
+            }
+            Task::SaveSettings(settings) => match settings.save_global() {
+                Ok(()) => shared.notifications.push(
+                    Severity::Info,
+                    String::from("New instances will start with these settings"),
+                ),
+                Err(err) => shared
+                    .notifications
+                    .push(Severity::Error, format!("Could not save settings: {err}")),
+            },
+            Task::LoadSettings => {
+                let defaults = Settings::load_global().unwrap_or_else(|err| {
+                    shared
+                        .notifications
+                        .push(Severity::Warning, format!("Could not load settings: {err}"));
+                    Settings::default()
+                });
+                // settings that aren't the built in ones came from a restored state
+                let mut settings = params.settings.write().unwrap();
+                if *settings == Settings::default() {
+                    *settings = defaults;
+                }
+            }
+            Task::LoadProgram(program) => {
+                let user_presets = match params.settings.read().unwrap().preset_dir() {
+                    Some(dir) => presets::scan_dir(&dir, |_, _| {}),
+                    None => Vec::new(),
+                };
+                let Some(preset) = presets::factory_presets()
+                    .into_iter()
+                    .chain(user_presets)
+                    .nth(program)
+                else {
+                    return;
+                };
+                let mut snapshot = Snapshot::from_preset(&preset);
+                let locked = params.locked_params.read().unwrap();
+                snapshot.values.retain(|id, _| !locked.contains(id));
+                let table = SceneTable::from_snapshot(snapshot, params.as_ref(), MORPH_PARAM_ID);
+                // the editor takes the request under the same lock, so a program it has already
+                // loaded can't be overridden again afterwards
+                let mut program_table = shared.program_table.write().unwrap();
+                if shared.requested_program.load(Ordering::Relaxed) == program as i32 {
+                    *program_table = table;
+                }
+            }
+        })
+    }
+
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.shared.clone(), async_executor)
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
-        self.sample_rate = buffer_config.sample_rate;
-        self.osc_state.sample_rate = buffer_config.sample_rate;
-        self.pitch_env_state.sample_rate = buffer_config.sample_rate;
-        self.amp_env_state.sample_rate = buffer_config.sample_rate;
+        #[cfg(feature = "crash-log")]
+        crash_log::install();
+
+        if !self.settings_loaded {
+            self.settings_loaded = true;
+            context.execute(Task::LoadSettings);
+        }
+
+        // this also runs after the host restores a state, which moves the morph slots around
+        self.params
+            .morph_slots
+            .write()
+            .unwrap()
+            .update_table(self.params.as_ref(), MORPH_PARAM_ID);
+        self.params
+            .scenes
+            .write()
+            .unwrap()
+            .update_table(self.params.as_ref(), MORPH_PARAM_ID);
+        self.params
+            .kit
+            .write()
+            .unwrap()
+            .update_table(self.params.as_ref(), MORPH_PARAM_ID);
+        self.params.expression.write().unwrap().compile();
+        // a restored state replaces whatever a program change picked
+        self.shared.requested_program.store(-1, Ordering::Relaxed);
+        *self.shared.program_table.write().unwrap() = SceneTable::default();
+
+        // hits still ringing when a preset is loaded finish with the parameters they were playing
+        // with, instead of jumping to the new ones
+        if let Some(last_voice_params) = self.last_voice_params {
+            for slot in &mut self.voices {
+                if slot.voice.is_active() {
+                    slot.frozen.get_or_insert(last_voice_params);
+                }
+            }
+        }
+
+        let sample_rate = buffer_config.sample_rate;
+        let rate_changed = sample_rate != self.sample_rate;
+        self.sample_rate = sample_rate;
+        for slot in &mut self.voices {
+            slot.voice.set_sample_rate(sample_rate);
+        }
+        self.hit_meter.set_sample_rate(sample_rate);
+        self.cpu_meter.set_sample_rate(sample_rate);
+        self.layer_correlation.set_sample_rate(sample_rate);
+        // resetting these would cut off tails that are carrying on through a state restore
+        if rate_changed {
+            for analog_clip in &mut self.analog_clip {
+                analog_clip.set_sample_rate(sample_rate);
+            }
+            for multiband in &mut self.multiband {
+                multiband.set_sample_rate(sample_rate);
+            }
+            self.solo_body_filters =
+                [Biquad::lowpass(SOLO_BODY_HZ, f32::consts::FRAC_1_SQRT_2, sample_rate); 2];
+            self.solo_click_filters =
+                [Biquad::highpass(SOLO_CLICK_HZ, f32::consts::FRAC_1_SQRT_2, sample_rate); 2];
+            for subsonic in &mut self.subsonic {
+                subsonic.set_sample_rate(sample_rate);
+            }
+            let swell_len = (REVERSE_MAX_SECONDS * sample_rate) as usize;
+            self.swell.allocate(swell_len);
+            for delay in &mut self.hit_delay {
+                delay.allocate(swell_len);
+            }
+            let kit_delay_len = swell_len + Subsonic::max_latency(sample_rate);
+            for delay in self.kit_delays.iter_mut().flatten() {
+                delay.allocate(kit_delay_len);
+            }
+            for delay in &mut self.dry_delay {
+                delay.allocate(Subsonic::max_latency(sample_rate));
+            }
+            let humanize_len = (HUMANIZE_MAX_MS / 1000.0 * sample_rate).ceil() as usize;
+            for delay in &mut self.cv_delay {
+                delay.allocate(humanize_len);
+            }
+        }
+
+        let capture_len = (CAPTURE_SECONDS * sample_rate) as usize;
+        self.hit_recorder.allocate(capture_len);
+        self.reference_recorder.allocate(capture_len);
+        self.null_test.allocate(capture_len);
+        self.last_swell_render = None;
+        self.latency = self.reverse_latency();
+        for delay in &mut self.hit_delay {
+            delay.set_delay(self.latency as usize);
+        }
+        self.configure_subsonic();
+        let subsonic_latency = self.subsonic_latency();
+        for delay in self.kit_delays.iter_mut().flatten() {
+            delay.set_delay((self.latency + subsonic_latency) as usize);
+        }
+        for delay in &mut self.dry_delay {
+            delay.set_delay(subsonic_latency as usize);
+        }
+        let humanize_latency = self.humanize_latency();
+        for delay in &mut self.cv_delay {
+            delay.set_delay(humanize_latency as usize);
+        }
+        self.reported_latency = self.latency + subsonic_latency + humanize_latency;
+        context.set_latency_samples(self.reported_latency);
+        self.shared.last_hit_capture.lock().unwrap().samples = Vec::with_capacity(capture_len);
+        self.shared.reference_capture.lock().unwrap().samples = Vec::with_capacity(capture_len);
         true
     }
 
-    fn process(
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let started = Instant::now();
+        let status = self.process_block(buffer, aux, context);
+        self.cpu_meter
+            .process(started, buffer.samples(), &self.shared.cpu_load);
+        status
+    }
+}
+
+impl KickSynth {
+    /// Everything `process()` does, split out so the whole block can be timed.
+    fn process_block(
+        &mut self,
+        buffer: &mut Buffer,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // the editor only holds these locks for as long as it takes to copy the envelopes
+        if let Ok(pitch_breakpoints) = self.params.pitch_breakpoints.try_read() {
+            self.pitch_breakpoints = *pitch_breakpoints;
+        }
+        if let Ok(velocity_curve) = self.params.custom_velocity_curve.try_read() {
+            self.velocity_curve = *velocity_curve;
+        }
+        if let Ok(morph_slots) = self.params.morph_slots.try_read() {
+            self.morph_table = morph_slots.table;
+        }
+        if let Ok(scenes) = self.params.scenes.try_read() {
+            self.scene_table = scenes.table;
+        }
+        if let Ok(program_table) = self.shared.program_table.try_read() {
+            self.program_table = *program_table;
+        }
+        if let Ok(kit) = self.params.kit.try_read() {
+            self.kit_table = kit.table;
+            self.kit_choke_groups = kit.choke_groups;
+            self.kit_notes = kit.notes;
+        }
+        if let Ok(expression) = self.params.expression.try_read() {
+            self.expression = expression.program;
+        }
+        if let Ok(generative) = self.params.generative_rules.try_read() {
+            self.generative = *generative;
+        }
+        if let Ok(settings) = self.params.settings.try_read() {
+            self.audition_note = settings.audition_note;
+        }
+        // otherwise a keyswitch held while the toggle goes off would still solo once it's back on
+        if !self.params.solo_keyswitches.value() {
+            self.held_solos = [false; 2];
+        }
+        let kit_mode = self.params.kit_mode.value();
+        for (slot, kit_params) in self.kit_params.iter_mut().enumerate() {
+            *kit_params = match kit_mode && self.kit_table.is_filled(slot) {
+                true => Some(self.params.voice_params_with(
+                    |param| self.kit_table.value(param, slot).unwrap_or(param.value()),
+                    &self.pitch_breakpoints,
+                )),
+                false => None,
+            };
+        }
+        let scene_param = self.params.scene.value();
+        if scene_param != self.last_scene_param {
+            self.last_scene_param = scene_param;
+            self.recall_scene(scene_param.checked_sub(1).map(|scene| scene as usize));
+        }
+
+        #[cfg(feature = "crash-log")]
+        self.crash_log.begin_block(self.voice_params());
+
+        if self.params.auto_level.value() {
+            self.request_level_measurement(context);
+        }
+        if self.params.reverse.value() > 0.0 {
+            self.request_swell_render(context);
+        }
+        self.swell.update(&self.shared.swell_render);
+        let latency = self.reverse_latency();
+        if latency != self.latency {
+            self.latency = latency;
+            for delay in &mut self.hit_delay {
+                delay.set_delay(latency as usize);
+            }
+        }
+        self.configure_subsonic();
+        let subsonic_latency = self.subsonic_latency();
+        for delay in self.kit_delays.iter_mut().flatten() {
+            delay.set_delay((self.latency + subsonic_latency) as usize);
+        }
+        for delay in &mut self.dry_delay {
+            delay.set_delay(subsonic_latency as usize);
+        }
+        let humanize_latency = self.humanize_latency();
+        for delay in &mut self.cv_delay {
+            delay.set_delay(humanize_latency as usize);
+        }
+        let reported_latency = self.latency + subsonic_latency + humanize_latency;
+        if reported_latency != self.reported_latency {
+            self.reported_latency = reported_latency;
+            context.set_latency_samples(reported_latency);
+        }
+        if self.shared.reference_listening.load(Ordering::Relaxed) {
+            if let Some(reference) = aux.inputs.first_mut() {
+                if let Some(channel) = reference.as_slice().first() {
+                    self.listen_for_reference(channel, context);
+                }
+            }
+        }
+
+        if self
+            .shared
+            .audition_requested
+            .swap(false, Ordering::Relaxed)
+        {
+            let note = self.audition_note;
+            self.note_on(note, 1.0, 0, context);
+            let amp_env = self.voice_params().amp_env;
+            let gate_time = amp_env.attack + amp_env.hold + amp_env.decay;
+            self.voices[self.current_voice].gate_remaining =
+                Some((gate_time * self.sample_rate) as usize);
+        }
+        if let Ok(note) = u8::try_from(self.shared.pad_note.swap(-1, Ordering::Acquire)) {
+            let velocity = self.shared.pad_velocity.load(Ordering::Relaxed);
+            self.note_on(note, velocity, 0, context);
+            let amp_env = self.voice_params().amp_env;
+            let gate_time = amp_env.attack + amp_env.hold + amp_env.decay;
+            self.voices[self.current_voice].gate_remaining =
+                Some((gate_time * self.sample_rate) as usize);
+        }
+        let audition_held = self.shared.audition_held.load(Ordering::Relaxed);
+        match (audition_held, self.audition_holding) {
+            (true, None) => {
+                let note = self.audition_note;
+                self.note_on(note, 1.0, 0, context);
+                self.audition_holding = Some(note);
+            }
+            (false, Some(note)) => {
+                self.audition_holding = None;
+                // unless something else has been played since
+                if self.last_midi_note == Some(note) {
+                    self.release_current();
+                }
+            }
+            _ => {}
+        }
+
+        if self.params.voice_mode.value() != VoiceMode::Legato {
+            self.held_notes.clear();
+        }
+
+        let seed = self.params.seed.value();
+        self.tempo = context.transport().tempo;
+        let playing = context.transport().playing;
+        if playing && !self.was_playing {
+            self.hit_count = 0;
+            self.generative_state.reset();
+            self.shared.peak_history.clear();
+            if self.params.reseed_on_play.value() {
+                self.rng = Rng::new(seed as u64);
+            }
+        }
+        if !playing && self.was_playing {
+            self.transport_stopped();
+        }
+        if seed != self.rng_seed {
+            self.rng = Rng::new(seed as u64);
+            self.rng_seed = seed;
+        }
+        self.was_playing = playing;
+
+        let cv = match self.params.cv_trigger.value() {
+            true => aux.inputs.get(1).map(|cv| cv.as_slice_immutable()),
+            false => None,
+        };
+
+        // the dry output is silent unless it's turned on
+        for output in aux.outputs.iter_mut() {
+            for channel in output.as_slice() {
+                channel.fill(0.0);
+            }
+        }
+        let (dry_output, kit_outputs) = match aux.outputs.split_first_mut() {
+            Some((dry_output, kit_outputs)) => (Some(dry_output), kit_outputs),
+            None => (None, &mut [][..]),
+        };
+        let mut dry_output = match self.params.dry_output.value() {
+            true => dry_output.map(|output| output.as_slice()),
+            false => None,
+        };
+
+        let solo_body = self.shared.solo_body.load(Ordering::Relaxed);
+        let solo_click = self.shared.solo_click.load(Ordering::Relaxed);
+        let null_test_gain = self.shared.null_test_gain.load(Ordering::Relaxed);
+        let choke_step = (CHOKE_SECONDS * self.sample_rate).recip();
+        let mut clipped = false;
+        let subsonic_cutoff =
+            Some(self.params.subsonic_cutoff.value()).filter(|_| self.params.subsonic.value());
+        let mut below_subsonic = false;
+        let mut chain_peaks = ChainPeaks::default();
+        self.peak_history
+            .start_block(context.transport(), self.sample_rate);
+        let mut next_event = context.next_event();
+        // nothing can make a sound this block, so skip the voice entirely
+        if next_event.is_none() && cv.is_none() && self.is_idle() {
+            for channel in buffer.as_slice() {
+                channel.fill(0.0);
+            }
+            // the history still has to move along with the transport
+            for sample_id in 0..buffer.samples() {
+                self.peak_history
+                    .process(sample_id, [0.0; 2], &self.shared.peak_history);
+            }
+            self.peak_history.finish_block(&self.shared.peak_history);
+            self.skip_smoothers(buffer.samples());
+            return ProcessStatus::Normal;
+        }
+        for (sample_id, mut channel_samples) in buffer.iter_samples().enumerate() {
+            if let Some(cv) = cv {
+                let gate = self.cv_delay[0].process(cv.first().map_or(0.0, |gate| gate[sample_id]));
+                let pitch =
+                    self.cv_delay[1].process(cv.get(1).map_or(0.0, |pitch| pitch[sample_id]));
+                self.process_cv(gate, pitch, sample_id, context);
+            }
+            while let Some(event) = next_event {
+                if event.timing() > sample_id as u32 {
+                    break;
+                }
+                self.shared.midi_log.push(&event);
+                next_event = context.next_event();
+                let keyswitch = match event {
+                    NoteEvent::NoteOn { note, .. } => {
+                        self.solo_for_note(note).map(|solo| (solo, true))
+                    }
+                    NoteEvent::NoteOff { note, .. } => {
+                        self.solo_for_note(note).map(|solo| (solo, false))
+                    }
+                    _ => None,
+                };
+                if let Some((solo, held)) = keyswitch {
+                    self.held_solos[solo] = held;
+                    continue;
+                }
+                match event {
+                    // a note being learned doesn't play, and learning stops right away so the
+                    // next one does
+                    NoteEvent::NoteOn { note, .. }
+                        if self.shared.kit_learn_slot.load(Ordering::Relaxed) >= 0 =>
+                    {
+                        let slot = self.shared.kit_learn_slot.swap(-1, Ordering::Relaxed);
+                        self.shared.kit_learned_slot.store(slot, Ordering::Relaxed);
+                        self.shared
+                            .kit_learned_note
+                            .store(note as i32, Ordering::Release);
+                    }
+                    NoteEvent::NoteOn { note, .. } if self.scene_for_note(note).is_some() => {
+                        self.recall_scene(self.scene_for_note(note));
+                    }
+                    NoteEvent::NoteOn { note, .. }
+                        if self.params.reverse_trigger.value() == ReverseTrigger::Note
+                            && note as i32 == self.params.reverse_note.value() =>
+                    {
+                        self.swell.start(0);
+                    }
+                    NoteEvent::NoteOff { note, .. }
+                        if self
+                            .pending_notes
+                            .iter()
+                            .flatten()
+                            .any(|pending| pending.note == note) =>
+                    {
+                        for pending in self.pending_notes.iter_mut().flatten() {
+                            if pending.note == note {
+                                pending.released_after.get_or_insert(pending.held);
+                            }
+                        }
+                    }
+                    NoteEvent::NoteOn { note, velocity, .. }
+                        if self.kit_slot_for_note(note).is_some() =>
+                    {
+                        self.schedule_note(note, velocity, 0, sample_id, context);
+                    }
+                    // kit mode only plays the kit notes
+                    NoteEvent::NoteOn { .. } if kit_mode => {}
+                    NoteEvent::NoteOff { note, .. } if kit_mode => self.release_note(note),
+                    NoteEvent::NoteOn { note, velocity, .. }
+                        if self.params.voice_mode.value() == VoiceMode::Legato =>
+                    {
+                        self.schedule_note(note, velocity, 0, sample_id, context);
+                    }
+                    NoteEvent::NoteOff { note, .. }
+                        if self.params.voice_mode.value() == VoiceMode::Legato =>
+                    {
+                        self.legato_note_off(note);
+                    }
+                    NoteEvent::NoteOn { note, velocity, .. } => {
+                        let delay = self.quantize_delay(sample_id, context.transport());
+                        self.schedule_note(note, velocity, delay, sample_id, context);
+                    }
+                    NoteEvent::MidiProgramChange { program, .. }
+                        if self.params.program_change.value() =>
+                    {
+                        self.shared
+                            .requested_program
+                            .store(program as i32, Ordering::Relaxed);
+                        context.execute_background(Task::LoadProgram(program as usize));
+                    }
+                    NoteEvent::MidiCC { cc: 1, value, .. } => {
+                        self.modwheel.set_target(self.sample_rate, value);
+                    }
+                    NoteEvent::MidiChannelPressure { pressure, .. } => {
+                        self.pressure.set_target(self.sample_rate, pressure);
+                    }
+                    NoteEvent::PolyPressure { note, pressure, .. }
+                        if Some(note) == self.last_midi_note =>
+                    {
+                        self.pressure.set_target(self.sample_rate, pressure);
+                    }
+                    NoteEvent::NoteOff { note, .. } => self.release_note(note),
+                    _ => {}
+                }
+            }
+            for index in 0..MAX_PENDING_NOTES {
+                let Some(mut pending) = self.pending_notes[index].take() else {
+                    continue;
+                };
+                match pending.delay {
+                    0 => {
+                        let legato = self.is_legato_note(pending.note);
+                        self.play_note(pending.note, pending.velocity, sample_id, context);
+                        // legato notes have to come off the held notes too, so they can't wait on
+                        // the voice's gate
+                        match (legato, pending.released_after) {
+                            (true, Some(_)) => self.legato_note_off(pending.note),
+                            (true, None) => {}
+                            (false, released_after) => {
+                                self.voices[self.current_voice].gate_remaining = released_after;
+                            }
+                        }
+                    }
+                    _ => {
+                        pending.delay -= 1;
+                        pending.held += 1;
+                        self.pending_notes[index] = Some(pending);
+                    }
+                }
+            }
+            self.advance_gates();
+
+            let (voice_params, modulation_gain) = self.next_modulated_voice_params();
+            self.last_voice_params = Some(voice_params);
+            let output_gain = self.params.output_gain.smoothed.next() * modulation_gain;
+            let mut frame = [0.0; 2];
+            let mut layers = [0.0; 2];
+            let mut kit_frames = [[0.0; 2]; KIT_SLOTS];
+            for slot in &mut self.voices {
+                if !slot.voice.is_active() {
+                    continue;
+                }
+                let choke = slot.choke.map(|choke| choke - choke_step);
+                if choke.is_some_and(|choke| choke <= 0.0) {
+                    slot.voice.stop();
+                    slot.choke = None;
+                    continue;
+                }
+                slot.choke = choke;
+                let mut voice_params = slot
+                    .kit_slot
+                    .and_then(|kit_slot| self.kit_params[kit_slot])
+                    .or(slot.frozen)
+                    .unwrap_or(voice_params);
+                voice_params.scale_sweep(slot.sweep_depth, slot.sweep_time);
+                let tune_ratio = slot.tune_ratio * slot.expression_tune;
+                voice_params.start_freq *= slot.pitch_ratio * tune_ratio;
+                voice_params.end_freq *= tune_ratio;
+                below_subsonic |= subsonic_cutoff.is_some_and(|cutoff| {
+                    f32::max(voice_params.end_freq, voice_params.sweep_floor) < cutoff
+                });
+                let mut voice_frame = slot
+                    .voice
+                    .render(&voice_params)
+                    .map(|sample| output_gain * slot.gain * choke.unwrap_or(1.0) * sample);
+                if let Some((last_frame, remaining)) = slot.kill_ramp {
+                    let fade = remaining as f32 / KILL_RAMP_SAMPLES as f32;
+                    for (sample, last_sample) in voice_frame.iter_mut().zip(last_frame) {
+                        *sample += fade * last_sample;
+                    }
+                    slot.kill_ramp =
+                        Some((last_frame, remaining - 1)).filter(|(_, left)| *left > 0);
+                }
+                slot.last_frame = voice_frame;
+                for (sample, voice_sample) in frame.iter_mut().zip(voice_frame) {
+                    *sample += voice_sample;
+                }
+                let [engine_a, engine_b] = slot.voice.layer_output;
+                chain_peaks.process(ChainNode::EngineA, [engine_a; 2]);
+                chain_peaks.process(ChainNode::EngineB, [engine_b; 2]);
+                layers[0] += engine_a;
+                layers[1] += engine_b;
+                if let Some(kit_frame) = slot
+                    .kit_slot
+                    .and_then(|kit_slot| kit_frames.get_mut(kit_slot))
+                {
+                    for (sample, voice_sample) in kit_frame.iter_mut().zip(voice_frame) {
+                        *sample += voice_sample;
+                    }
+                }
+            }
+            let kill_fade = self.kill_fade.unwrap_or(1.0);
+            for ((output, delays), kit_frame) in kit_outputs
+                .iter_mut()
+                .zip(&mut self.kit_delays)
+                .zip(kit_frames)
+            {
+                let kit_frame = [
+                    delays[0].process(kit_frame[0]),
+                    delays[1].process(kit_frame[1]),
+                ];
+                write_frame(
+                    output.as_slice(),
+                    sample_id,
+                    kit_frame.map(|side| kill_fade * side),
+                );
+            }
+            self.layer_correlation.process(layers);
+            let osc_scample = 0.5 * (frame[0] + frame[1]);
+            let swell = output_gain * self.params.reverse.smoothed.next() * self.swell.next();
+            let mut output_frame = [
+                kill_fade * (self.hit_delay[0].process(frame[0]) + swell),
+                kill_fade * (self.hit_delay[1].process(frame[1]) + swell),
+            ];
+            self.advance_kill_fade(choke_step);
+            let dry_frame = output_frame;
+            chain_peaks.process(ChainNode::Voices, frame);
+            chain_peaks.process(ChainNode::Mixer, dry_frame);
+            if let Some(dry_output) = &mut dry_output {
+                let dry_frame = [
+                    self.dry_delay[0].process(dry_frame[0]),
+                    self.dry_delay[1].process(dry_frame[1]),
+                ];
+                write_frame(dry_output, sample_id, dry_frame);
+            }
+            let low_band = Band {
+                drive: self.params.multiband_low_drive.smoothed.next(),
+                level: self.params.multiband_low_level.smoothed.next(),
+            };
+            let high_band = Band {
+                drive: self.params.multiband_high_drive.smoothed.next(),
+                level: self.params.multiband_high_level.smoothed.next(),
+            };
+            let mut fx_dry_frame = dry_frame;
+            if self.params.multiband.value() {
+                let crossover = self.params.multiband_crossover.value();
+                for ((sample, dry), multiband) in output_frame
+                    .iter_mut()
+                    .zip(&mut fx_dry_frame)
+                    .zip(&mut self.multiband)
+                {
+                    *sample = multiband.process(*sample, crossover, low_band, high_band);
+                    *dry = multiband.align_dry(*dry);
+                }
+                chain_peaks.process(ChainNode::Multiband, output_frame);
+            }
+            let analog_clip = self.params.analog_clip.smoothed.next();
+            if analog_clip > 0.0 {
+                for (sample, clip) in output_frame.iter_mut().zip(&mut self.analog_clip) {
+                    *sample = clip.process(*sample, analog_clip);
+                }
+                chain_peaks.process(ChainNode::AnalogClip, output_frame);
+            }
+            let fx_mix = self.params.fx_mix.smoothed.next();
+            for (sample, dry) in output_frame.iter_mut().zip(fx_dry_frame) {
+                *sample = dry + (*sample - dry) * fx_mix;
+            }
+            chain_peaks.process(ChainNode::FxMix, output_frame);
+            if subsonic_cutoff.is_some() {
+                for (sample, subsonic) in output_frame.iter_mut().zip(&mut self.subsonic) {
+                    *sample = subsonic.process(*sample);
+                }
+                chain_peaks.process(ChainNode::Subsonic, output_frame);
+            }
+            let null_test = null_test_gain * self.null_test.next();
+            for sample in &mut output_frame {
+                *sample += null_test;
+            }
+            let [held_body, held_click] = self.held_solos;
+            if solo_body || held_body {
+                for (sample, filter) in output_frame.iter_mut().zip(&mut self.solo_body_filters) {
+                    *sample = filter.process(*sample);
+                }
+            }
+            if solo_click || held_click {
+                for (sample, filter) in output_frame.iter_mut().zip(&mut self.solo_click_filters) {
+                    *sample = filter.process(*sample);
+                }
+            }
+            self.hit_meter.process(osc_scample);
+            let capture_full = self.hit_recorder.process(osc_scample);
+            let sounding = self.is_sounding();
+            if !sounding {
+                self.hit_meter.stop();
+            }
+            if self.hit_recorder.is_recording() && (capture_full || !sounding) {
+                self.finish_recording(context);
+            }
+
+            let output_trim = self.params.output_trim.smoothed.next();
+            for sample in &mut output_frame {
+                *sample *= output_trim;
+            }
+            clipped |= output_frame.iter().any(|sample| sample.abs() > 1.0);
+            chain_peaks.process(ChainNode::Output, output_frame);
+            self.peak_history
+                .process(sample_id, output_frame, &self.shared.peak_history);
+
+            // mono outputs get both sides of the pan
+            match channel_samples.len() {
+                1 => {
+                    for sample in channel_samples.iter_mut() {
+                        *sample = 0.5 * (output_frame[0] + output_frame[1]);
+                    }
+                }
+                _ => {
+                    for (channel, sample) in channel_samples.iter_mut().enumerate() {
+                        *sample = output_frame[channel.min(1)];
+                    }
+                }
+            }
+        }
+        if clipped {
+            self.shared.clipped.store(true, Ordering::Relaxed);
+        }
+        if below_subsonic {
+            self.shared.below_subsonic.store(true, Ordering::Relaxed);
+        }
+        self.shared.chain_levels.publish(&chain_peaks);
+        self.layer_correlation
+            .publish(&self.shared.layer_correlation);
+        self.peak_history.finish_block(&self.shared.peak_history);
+        self.hit_meter.publish(&self.shared.last_hit);
+        ProcessStatus::KeepAlive
+    }
+
+    fn voice_params(&self) -> VoiceParams {
+        let overrides = self.overrides(self.params.morph.value());
+        let mut voice_params = self
+            .params
+            .voice_params(&self.pitch_breakpoints, &overrides);
+        self.apply_pitch_sync(&mut voice_params);
+        voice_params
+    }
+
+    /// Stretches the pitch sweep to the synced length, if pitch sync is on and the host has a
+    /// tempo.
+    fn apply_pitch_sync(&self, voice_params: &mut VoiceParams) {
+        let Some(tempo) = self.tempo.filter(|_| self.params.pitch_sync.value()) else {
+            return;
+        };
+        let length = (self.params.pitch_sync_length.value().beats() * 60.0 / tempo) as f32;
+        match &mut voice_params.pitch_breakpoints {
+            Some(breakpoints) if breakpoints.duration() > 0.0 => {
+                *breakpoints = breakpoints.scaled(length / breakpoints.duration());
+            }
+            Some(_) => {}
+            None => voice_params.pitch_env.decay = length,
+        }
+    }
+
+    fn overrides(&self, morph_amount: f32) -> Overrides<'_> {
+        Overrides {
+            program: &self.program_table,
+            morph: &self.morph_table,
+            morph_amount,
+            scenes: &self.scene_table,
+            scene: self.scene,
+            previous_scene: self.previous_scene,
+            scene_fade: self.scene_fade,
+        }
+    }
+
+    /// The scene recalled by `note`, if scene notes are on and it's one of them.
+    fn scene_for_note(&self, note: u8) -> Option<usize> {
+        let base_note = self.params.scene_base_note.value();
+        let scene = (note as i32).checked_sub(base_note)?;
+        match self.params.scene_notes.value() && (0..SCENE_COUNT as i32).contains(&scene) {
+            true => Some(scene as usize),
+            false => None,
+        }
+    }
+
+    /// Which solo `note` holds, 0 for the body or 1 for the click, if it's a solo keyswitch.
+    fn solo_for_note(&self, note: u8) -> Option<usize> {
+        if !self.params.solo_keyswitches.value() {
+            return None;
+        }
+        let base_note = 12 * (self.params.keyswitch_octave.value() + 1);
+        match note as i32 - base_note {
+            solo @ (0 | 1) => Some(solo as usize),
+            _ => None,
+        }
+    }
+
+    /// The kit slot played by `note`, or `None` outside of kit mode.
+    fn kit_slot_for_note(&self, note: u8) -> Option<usize> {
+        if !self.params.kit_mode.value() {
+            return None;
+        }
+        let base_note = self.params.kit_base_note.value();
+        (0..KIT_SLOTS).find(|&slot| {
+            let slot_note = self.kit_notes[slot].map_or(base_note + slot as i32, i32::from);
+            slot_note == note as i32
+        })
+    }
+
+    /// Fades out the other kit slots in `kit_slot`'s choke group.
+    fn choke_kit_group(&mut self, kit_slot: usize) {
+        let group = self.kit_choke_groups[kit_slot];
+        if group == 0 {
+            return;
+        }
+        for slot in &mut self.voices {
+            let choked = slot
+                .kit_slot
+                .is_some_and(|other| other != kit_slot && self.kit_choke_groups[other] == group);
+            if choked && slot.voice.is_active() && slot.choke.is_none() {
+                slot.choke = Some(1.0);
+            }
+        }
+    }
+
+    /// Switches to `scene`, or back to the live parameters for `None`. Empty scenes are ignored.
+    fn recall_scene(&mut self, scene: Option<usize>) {
+        if scene.is_some_and(|scene| !self.scene_table.is_filled(scene)) || scene == self.scene {
+            return;
+        }
+        self.previous_scene = self.scene;
+        self.scene = scene;
+        self.scene_fade = 0.0;
+    }
+
+    /// Whether the output is silent until the next event comes in.
+    fn is_idle(&self) -> bool {
+        !self.is_sounding()
+            && self.pending_notes.iter().all(Option::is_none)
+            && self.voices.iter().all(|slot| slot.gate_remaining.is_none())
+            && !self.hit_recorder.is_recording()
+            && !self.swell.is_playing()
+            && !self.null_test.is_playing()
+            && self.hit_delay.iter().all(DelayLine::is_empty)
+            && self.kit_delays.iter().flatten().all(DelayLine::is_empty)
+            && self.dry_delay.iter().all(DelayLine::is_empty)
+            && (!self.params.subsonic.value() || self.subsonic.iter().all(Subsonic::is_settled))
+            && self.kill_fade.is_none()
+    }
+
+    /// Retunes the subsonic filter to the parameters' current values. They aren't smoothed, since
+    /// changing the linear phase filter clears it.
+    fn configure_subsonic(&mut self) {
+        let cutoff = self.params.subsonic_cutoff.value();
+        let slope = self.params.subsonic_slope.value();
+        let phase = self.params.subsonic_phase.value();
+        for subsonic in &mut self.subsonic {
+            subsonic.configure(cutoff, slope, phase);
+        }
+    }
+
+    fn subsonic_latency(&self) -> u32 {
+        match self.params.subsonic.value() {
+            true => self.subsonic[0].latency(),
+            false => 0,
+        }
+    }
+
+    /// How far MIDI hits are held back so humanize can move them either way, in samples. This is
+    /// as far as humanize goes rather than the current amount, so automating it doesn't keep
+    /// changing the latency.
+    fn humanize_latency(&self) -> u32 {
+        match self.params.humanize.value() > 0.0 {
+            true => (HUMANIZE_MAX_MS / 1000.0 * self.sample_rate) as u32,
+            false => 0,
+        }
+    }
+
+    /// The delay needed to fit the longest swell in before each hit, in samples.
+    fn reverse_latency(&self) -> u32 {
+        match self.params.reverse.value() > 0.0
+            && self.params.reverse_trigger.value() == ReverseTrigger::Latency
+        {
+            true => (REVERSE_MAX_SECONDS * self.sample_rate) as u32,
+            false => 0,
+        }
+    }
+
+    /// Moves every smoother along by `samples` samples without processing anything, so parameters
+    /// changed while idle don't ramp in over the start of the next hit.
+    fn skip_smoothers(&mut self, samples: usize) {
+        let steps = samples as u32;
+        let scene_fade_time = self.params.scene_fade.value() / 1000.0;
+        self.scene_fade = match scene_fade_time > 0.0 {
+            true => {
+                (self.scene_fade + samples as f32 / (scene_fade_time * self.sample_rate)).min(1.0)
+            }
+            false => 1.0,
+        };
+        self.params
+            .skip_voice_params(steps, &self.pitch_breakpoints);
+        self.pressure.next_step(steps);
+        self.modwheel.next_step(steps);
+        let params = &self.params;
+        for param in [
+            &params.morph,
+            &params.pressure_tune,
+            &params.modwheel_depth,
+            &params.output_gain,
+            &params.reverse,
+            &params.multiband_low_drive,
+            &params.multiband_low_level,
+            &params.multiband_high_drive,
+            &params.multiband_high_level,
+            &params.analog_clip,
+            &params.fx_mix,
+            &params.output_trim,
+        ] {
+            param.smoothed.next_step(steps);
+        }
+    }
+
+    /// Advances the smoothers and applies accents and MIDI expression, returning the parameters
+    /// for the next sample along with the gain modulation.
+    fn next_modulated_voice_params(&mut self) -> (VoiceParams, f32) {
+        let scene_fade_time = self.params.scene_fade.value() / 1000.0;
+        self.scene_fade = match scene_fade_time > 0.0 {
+            true => (self.scene_fade + (scene_fade_time * self.sample_rate).recip()).min(1.0),
+            false => 1.0,
+        };
+        let overrides = self.overrides(self.params.morph.smoothed.next());
+        let mut voice_params = self
+            .params
+            .next_voice_params(&self.pitch_breakpoints, &overrides);
+        self.apply_pitch_sync(&mut voice_params);
+        let mut gain = 1.0;
+        if self.params.voice_mode.value() == VoiceMode::Legato {
+            let glide_time = self.params.glide.value() / 1000.0;
+            self.bass_note = match glide_time > 0.0 {
+                true => {
+                    let coefficient = 1.0 - f32::exp(-(glide_time * self.sample_rate).recip());
+                    self.bass_note + (self.bass_target - self.bass_note) * coefficient
+                }
+                false => self.bass_target,
+            };
+            let ratio = util::f32_midi_note_to_freq(self.bass_note) / voice_params.end_freq;
+            voice_params.start_freq *= ratio;
+            voice_params.end_freq *= ratio;
+        }
+
+        let pressure_ratio =
+            semitones_to_ratio(self.pressure.next() * self.params.pressure_tune.smoothed.next());
+        voice_params.start_freq *= pressure_ratio;
+        voice_params.end_freq *= pressure_ratio;
+
+        let modwheel = self.modwheel.next() * self.params.modwheel_depth.smoothed.next();
+        match self.params.modwheel_dest.value() {
+            ModwheelDest::Tune => {
+                let ratio = semitones_to_ratio(12.0 * modwheel);
+                voice_params.start_freq *= ratio;
+                voice_params.end_freq *= ratio;
+            }
+            ModwheelDest::Sweep => voice_params.start_freq *= semitones_to_ratio(24.0 * modwheel),
+            ModwheelDest::Decay => {
+                let scale = f32::powf(2.0, modwheel);
+                voice_params.amp_env.decay *= scale;
+                voice_params.amp_env.release *= scale;
+            }
+            ModwheelDest::Level => gain = util::db_to_gain(12.0 * modwheel),
+        }
+
+        (voice_params, gain)
+    }
+
+    fn note_on(
+        &mut self,
+        note: u8,
+        velocity: f32,
+        sample_id: usize,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        #[cfg(feature = "crash-log")]
+        self.crash_log
+            .record(crash_log::Entry::NoteOn { note, velocity });
+
+        self.midi_frequency = util::midi_note_to_freq(note);
+        self.midi_velocity = match self.params.fixed_velocity.value() {
+            true => 1.0,
+            false => self
+                .params
+                .velocity_curve
+                .value()
+                .apply(velocity, &self.velocity_curve),
+        };
+        self.last_midi_note = Some(note);
+        let mut gain = match self.params.auto_level.value() {
+            true => self.shared.level_gain.load(Ordering::Relaxed),
+            false => 1.0,
+        };
+        // velocity 1 sits at the bottom of the range and 127 at the top
+        let velocity_position = ((self.midi_velocity * 127.0 - 1.0) / 126.0).clamp(0.0, 1.0);
+        gain *= util::db_to_gain(-(1.0 - velocity_position) * self.params.dynamic_range.value());
+        let kit_slot = self.kit_slot_for_note(note);
+        // kit notes pick a patch rather than a pitch, so they don't key follow
+        let octaves = match kit_slot {
+            Some(_) => 0.0,
+            None => (note as i32 - self.params.key_follow_center.value()) as f32 / 12.0,
+        };
+        gain *= util::db_to_gain(octaves * self.params.level_key_follow.value());
+        let mut pitch_ratio = 1.0;
+        if self.is_accented(sample_id, context.transport()) {
+            gain *= self.params.accent_level.value();
+            pitch_ratio = semitones_to_ratio(self.params.accent_pitch.value());
+        }
+        let last_voice = &self.voices[self.current_voice].voice;
+        let [amp_env, pitch_env] = match last_voice.is_active() {
+            true => [
+                last_voice.amp_env_state.current,
+                last_voice.pitch_env_state.current,
+            ],
+            false => [0.0; 2],
+        };
+        let inputs = HitInputs {
+            velocity: self.midi_velocity,
+            note: note as f32,
+            hit: self.hit_count as f32,
+            amp_env,
+            pitch_env,
+        };
+        let mut offsets = match self.params.generative.value() {
+            true => self
+                .generative_state
+                .next(&self.generative, self.hit_count, &mut self.rng),
+            false => HitOffsets::default(),
+        };
+        let modulation = self.expression.eval(&inputs, &mut self.rng);
+        let expression_target = match self.params.expression_dest.value() {
+            ExpressionDest::Tune => Target::Tune,
+            ExpressionDest::Sweep => Target::Sweep,
+            ExpressionDest::Level => Target::Level,
+        };
+        offsets.add(expression_target, modulation);
+        let offsets = offsets.clamped();
+        let expression_tune = semitones_to_ratio(offsets.tune);
+        pitch_ratio *= semitones_to_ratio(offsets.sweep);
+        gain *= util::db_to_gain(offsets.level);
+        self.hit_count += 1;
+        let sweep_depth =
+            1.0 - self.params.velocity_sweep_depth.value() * (1.0 - self.midi_velocity);
+        let sweep_time = f32::powf(2.0, -octaves * self.params.key_sweep_time.value());
+        let start_phase = match self.params.align_peak.value() {
+            true => {
+                let mut voice_params = kit_slot
+                    .and_then(|kit_slot| self.kit_params[kit_slot])
+                    .unwrap_or_else(|| self.voice_params());
+                voice_params.scale_sweep(sweep_depth, sweep_time);
+                voice_params.start_freq *= pitch_ratio * expression_tune;
+                voice_params.peak_aligned_phase()
+            }
+            false => self.params.phase_offset.modulated_plain_value(),
+        };
+        let phase_offset = start_phase
+            + octaves * self.params.phase_key_follow.value()
+            + self.params.phase_random.value() * self.rng.next_f32();
+        let crackle_seed = self.rng.next_u64();
+        self.current_voice = self.allocate_voice(note);
+        self.voice_age += 1;
+        let slot = &mut self.voices[self.current_voice];
+        // restarting a voice that's still playing would step straight from wherever it had gotten
+        // to into the new hit, so the old hit's last sample is faded out under the new one
+        let kill_ramp = Some((slot.last_frame, KILL_RAMP_SAMPLES)).filter(|(frame, _)| {
+            slot.voice.is_active()
+                && frame
+                    .iter()
+                    .any(|sample| sample.abs() > KILL_RAMP_THRESHOLD)
+        });
+        *slot = VoiceSlot {
+            note: Some(note),
+            gain,
+            pitch_ratio,
+            tune_ratio: 1.0,
+            expression_tune,
+            sweep_depth,
+            sweep_time,
+            started: self.voice_age,
+            frozen: None,
+            kit_slot,
+            choke: None,
+            kill_ramp,
+            gate_remaining: None,
+            ..*slot
+        };
+        slot.voice
+            .trigger(phase_offset.rem_euclid(1.0), crackle_seed);
+        if let Some(kit_slot) = kit_slot {
+            self.choke_kit_group(kit_slot);
+        }
+        self.hit_meter.start();
+        if self.reverse_latency() > 0 {
+            self.swell.start(self.reverse_latency() as usize);
+        }
+        if self.shared.null_test.load(Ordering::Relaxed) {
+            let latency = self.reverse_latency() + self.subsonic_latency();
+            self.null_test.start(latency as usize);
+        }
+        if self.hit_recorder.is_recording() {
+            self.finish_recording(context);
+        }
+        let sweep_time = self.voice_params().sweep_time();
+        self.hit_recorder
+            .start((sweep_time * self.sample_rate) as usize);
+    }
+
+    /// Picks the voice for a new hit on `note`. Each kit slot has a voice of its own. Otherwise
+    /// Poly mode retriggers the voice already playing the note, or else takes a free voice, or
+    /// else steals the oldest one. The other modes only ever use the first voice.
+    fn allocate_voice(&self, note: u8) -> usize {
+        if let Some(kit_slot) = self.kit_slot_for_note(note) {
+            return kit_slot;
+        }
+        if self.params.voice_mode.value() != VoiceMode::Poly {
+            return 0;
+        }
+        let voices = self.voices.iter().enumerate();
+        voices
+            .clone()
+            .find(|(_, slot)| slot.note == Some(note) && slot.voice.is_active())
+            .or_else(|| voices.clone().find(|(_, slot)| !slot.voice.is_active()))
+            .or_else(|| voices.min_by_key(|(_, slot)| slot.started))
+            .map_or(0, |(index, _)| index)
+    }
+
+    fn is_sounding(&self) -> bool {
+        self.voices.iter().any(|slot| slot.voice.is_active())
+    }
+
+    /// Releases every voice held by `note`.
+    fn release_note(&mut self, note: u8) {
+        if self.last_midi_note == Some(note) {
+            self.last_midi_note = None;
+        }
+        for slot in &mut self.voices {
+            if slot.note == Some(note) {
+                #[cfg(feature = "crash-log")]
+                self.crash_log.record(crash_log::Entry::NoteOff { note });
+                slot.note = None;
+                slot.voice.release();
+            }
+        }
+    }
+
+    /// Releases or cuts off whatever is still playing, depending on On Stop.
+    fn transport_stopped(&mut self) {
+        let action = self.params.on_stop.value();
+        if action == StopAction::Nothing {
+            return;
+        }
+        self.last_midi_note = None;
+        self.pending_notes = [None; MAX_PENDING_NOTES];
+        self.held_notes.clear();
+        for slot in &mut self.voices {
+            slot.gate_remaining = None;
+        }
+        for slot in self.voices.iter_mut().filter(|slot| slot.voice.is_active()) {
+            slot.note = None;
+            match action {
+                StopAction::Kill => slot.choke = slot.choke.or(Some(1.0)),
+                _ => slot.voice.release(),
+            }
+        }
+        if action == StopAction::Kill {
+            self.kill_fade = Some(1.0);
+        }
+    }
+
+    /// Moves Kill on stop's fade along, and drops the swell and everything still held back by the
+    /// latency once it's done.
+    fn advance_kill_fade(&mut self, step: f32) {
+        let Some(fade) = self.kill_fade else {
+            return;
+        };
+        match fade > step {
+            true => self.kill_fade = Some(fade - step),
+            false => {
+                self.kill_fade = None;
+                self.swell.stop();
+                let kit_delays = self.kit_delays.iter_mut().flatten();
+                for delay in self.hit_delay.iter_mut().chain(kit_delays) {
+                    delay.clear();
+                }
+            }
+        }
+    }
+
+    /// Counts down the voices' gates, releasing each voice once its gate runs out.
+    fn advance_gates(&mut self) {
+        for (index, slot) in self.voices.iter_mut().enumerate() {
+            let Some(remaining) = slot.gate_remaining else {
+                continue;
+            };
+            slot.gate_remaining = remaining.checked_sub(1);
+            if remaining == 0 {
+                if index == self.current_voice {
+                    self.last_midi_note = None;
+                }
+                slot.note = None;
+                slot.voice.release();
+            }
+        }
+    }
+
+    /// Releases the voice playing the most recent hit.
+    fn release_current(&mut self) {
+        self.last_midi_note = None;
+        let slot = &mut self.voices[self.current_voice];
+        slot.note = None;
+        slot.voice.release();
+    }
+
+    /// Plays `note` with last note priority. Notes played while another one is still sounding
+    /// glide to the new pitch instead of retriggering, restarting only the pitch envelope if
+    /// Legato Pitch Retrigger is on.
+    fn legato_note_on(
+        &mut self,
+        note: u8,
+        velocity: f32,
+        sample_id: usize,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        let legato = self.held_notes.top().is_some()
+            && self.voices[self.current_voice].voice.envelope_active();
+        self.held_notes.push(note);
+        self.bass_target = note as f32;
+        match legato {
+            true => {
+                self.last_midi_note = Some(note);
+                let slot = &mut self.voices[self.current_voice];
+                slot.note = Some(note);
+                if self.params.legato_pitch_retrigger.value() {
+                    slot.voice.retrigger_pitch();
+                }
+            }
+            false => {
+                self.bass_note = note as f32;
+                self.note_on(note, velocity, sample_id, context);
+            }
+        }
+    }
+
+    /// Glides back to the most recent note that's still held when the current one is let go.
+    /// Letting go of any other note does nothing.
+    fn legato_note_off(&mut self, note: u8) {
+        self.held_notes.remove(note);
+        if Some(note) != self.last_midi_note {
+            return;
+        }
+        match self.held_notes.top() {
+            Some(previous) => {
+                self.bass_target = previous as f32;
+                self.last_midi_note = Some(previous);
+                self.voices[self.current_voice].note = Some(previous);
+            }
+            None => {
+                #[cfg(feature = "crash-log")]
+                self.crash_log.record(crash_log::Entry::NoteOff { note });
+                self.release_current();
+            }
+        }
+    }
+
+    /// Records the next kick on the sidechain input, and hands it off for analysis once it
+    /// finishes.
+    fn listen_for_reference(&mut self, samples: &[f32], context: &mut impl ProcessContext<Self>) {
+        let onset_threshold = util::db_to_gain(REFERENCE_ONSET_DB);
+        let silence_threshold = util::db_to_gain(REFERENCE_SILENCE_DB);
+        let silence_len = (REFERENCE_SILENCE_SECONDS * self.sample_rate) as usize;
+        for &sample in samples {
+            if !self.reference_recorder.is_recording() {
+                if sample.abs() < onset_threshold {
+                    continue;
+                }
+                self.reference_recorder.start(0);
+                self.reference_silent_samples = 0;
+            }
+
+            let full = self.reference_recorder.process(sample);
+            self.reference_silent_samples = match sample.abs() < silence_threshold {
+                true => self.reference_silent_samples + 1,
+                false => 0,
+            };
+            if full || self.reference_silent_samples >= silence_len {
+                let finished = self
+                    .reference_recorder
+                    .finish(&self.shared.reference_capture, self.sample_rate);
+                if finished && self.reference_recorder.len() > silence_len {
+                    self.null_test.load(self.reference_recorder.samples());
+                    self.shared
+                        .reference_listening
+                        .store(false, Ordering::Relaxed);
+                    context.execute_background(Task::AnalyzeReference);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn is_accented(&self, sample_id: usize, transport: &Transport) -> bool {
+        match self.params.accent_mode.value() {
+            AccentMode::Off => false,
+            AccentMode::EveryNth => self
+                .hit_count
+                .is_multiple_of(self.params.accent_every.value() as u64),
+            AccentMode::Downbeats => {
+                let (Some(pos_beats), Some(bar_start), Some(tempo)) = (
+                    transport.pos_beats(),
+                    transport.bar_start_pos_beats(),
+                    transport.tempo,
+                ) else {
+                    return false;
+                };
+                let numerator = transport.time_sig_numerator.unwrap_or(4) as f64;
+                let denominator = transport.time_sig_denominator.unwrap_or(4) as f64;
+                let bar_beats = numerator * 4.0 / denominator;
+
+                let beat = pos_beats + sample_id as f64 * tempo / 60.0 / self.sample_rate as f64;
+                let beat_in_bar = f64::rem_euclid(beat - bar_start, bar_beats);
+                // hits played a little early or late still count as being on the downbeat
+                beat_in_bar < DOWNBEAT_TOLERANCE_BEATS
+                    || bar_beats - beat_in_bar < DOWNBEAT_TOLERANCE_BEATS
+            }
+        }
+    }
+
+    /// Triggers a hit on rising edges of the CV gate and releases it when the gate falls. Both
+    /// signals are in interface units rather than volts.
+    fn process_cv(
+        &mut self,
+        gate: f32,
+        pitch: f32,
+        sample_id: usize,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        let volts = gate * CV_VOLTS_PER_UNIT;
+        let threshold = self.params.cv_threshold.value();
+        match self.cv_gate_high {
+            false if volts >= threshold => {
+                self.note_on(CV_TRIGGER_NOTE, 1.0, sample_id, context);
+                // MIDI note offs shouldn't cut off hits the gate is still holding
+                self.last_midi_note = None;
+                let slot = &mut self.voices[self.current_voice];
+                slot.note = None;
+                self.cv_gate_high = true;
+                if self.params.cv_pitch.value() {
+                    slot.tune_ratio = f32::powf(2.0, pitch * CV_VOLTS_PER_UNIT);
+                }
+            }
+            true if volts < threshold * CV_GATE_HYSTERESIS => {
+                self.cv_gate_high = false;
+                self.release_current();
+            }
+            _ => {}
+        }
+    }
+
+    /// Plays `note` after `delay` samples plus the humanize latency, moved by humanize, or right
+    /// away if that comes to nothing. Once too many notes are waiting, the one due soonest is
+    /// dropped to make room, since playing the new one early would put it out of order.
+    fn schedule_note(
         &mut self,
-        buffer: &mut Buffer,
-        aux: &mut AuxiliaryBuffers,
+        note: u8,
+        velocity: f32,
+        delay: usize,
+        sample_id: usize,
         context: &mut impl ProcessContext<Self>,
-    ) -> ProcessStatus {
-        let mut next_event = context.next_event();
-        for (sample_id, mut channel_samples) in buffer.iter_samples().enumerate() {
-            while let Some(event) = next_event {
-                if event.timing() > sample_id as u32 {
-                    break;
-                }
-                match event {
-                    NoteEvent::NoteOn { note, velocity, .. } => {
-                        self.midi_frequency = util::midi_note_to_freq(note);
-                        self.midi_velocity = velocity;
-                        self.last_midi_note = Some(note);
-                        self.amp_env_state.trigger(true);
-                        self.pitch_env_state.trigger(true);
-                        self.osc_state.phase = self.params.phase_offset.modulated_plain_value();
-                    }
-                    NoteEvent::NoteOff { note, .. } if Some(note) == self.last_midi_note => {
-                        self.last_midi_note = None;
-                        self.amp_env_state.trigger(false);
-                        self.pitch_env_state.trigger(false);
-                    }
-                    _ => {}
-                }
-                next_event = context.next_event();
+    ) {
+        let spread = match self.params.voice_mode.value() {
+            VoiceMode::Legato => 0.0,
+            _ => self.params.humanize.value() / 1000.0 * self.sample_rate,
+        };
+        let offset = match spread > 0.0 {
+            true => (2.0 * self.rng.next_f32() - 1.0) * spread,
+            false => 0.0,
+        };
+        let delay = delay + (self.humanize_latency() as f32 + offset).round() as usize;
+        match delay {
+            0 => self.play_note(note, velocity, sample_id, context),
+            delay => {
+                let index = match self.pending_notes.iter().position(Option::is_none) {
+                    Some(index) => index,
+                    None => (0..MAX_PENDING_NOTES)
+                        .min_by_key(|&index| {
+                            self.pending_notes[index].map_or(0, |pending| pending.delay)
+                        })
+                        .unwrap_or(0),
+                };
+                self.pending_notes[index] = Some(PendingNote {
+                    note,
+                    velocity,
+                    delay,
+                    released_after: None,
+                    held: 0,
+                })
             }
+        }
+    }
 
-            self.pitch_env_state.apply_params(&self.params.pitch_env);
-            self.amp_env_state.apply_params(&self.params.amp_env);
+    /// Starts `note`, or glides to it in Legato mode.
+    fn play_note(
+        &mut self,
+        note: u8,
+        velocity: f32,
+        sample_id: usize,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        match self.is_legato_note(note) {
+            true => self.legato_note_on(note, velocity, sample_id, context),
+            false => self.note_on(note, velocity, sample_id, context),
+        }
+    }
 
-            let pitch_env = self.pitch_env_state.advance();
-            let amp_env = self.amp_env_state.advance();
+    /// Whether `note` is played legato rather than as a hit of its own. Kit notes never are.
+    fn is_legato_note(&self, note: u8) -> bool {
+        self.params.voice_mode.value() == VoiceMode::Legato
+            && self.kit_slot_for_note(note).is_none()
+    }
 
-            let start_freq = self.params.start_freq.smoothed.next();
-            let end_freq = self.params.end_freq.smoothed.next();
-            let freq = lerp(pitch_env, end_freq, start_freq);
+    /// How many samples a note arriving at `sample_id` should wait to land on the quantize grid.
+    fn quantize_delay(&self, sample_id: usize, transport: &Transport) -> usize {
+        let Some(grid) = self.params.quantize.value().grid_beats() else {
+            return 0;
+        };
+        let (Some(pos_beats), Some(tempo)) = (transport.pos_beats(), transport.tempo) else {
+            return 0;
+        };
+        if !transport.playing {
+            return 0;
+        }
 
-            let osc_scample = amp_env * osc_sine(self.osc_state.advance(freq));
+        let samples_per_beat = 60.0 / tempo * self.sample_rate as f64;
+        let beat = pos_beats + sample_id as f64 / samples_per_beat;
+        // notes that are already on the grid, give or take rounding, play right away
+        let next_grid_line = f64::ceil(beat / grid - 1e-6) * grid;
+        (f64::max(next_grid_line - beat, 0.0) * samples_per_beat).round() as usize
+    }
 
-            for sample in channel_samples.iter_mut() {
-                *sample = osc_scample;
-            }
+    fn finish_recording(&mut self, context: &mut impl ProcessContext<Self>) {
+        if self
+            .hit_recorder
+            .finish(&self.shared.last_hit_capture, self.sample_rate)
+        {
+            context.execute_background(Task::AnalyzeLastHit);
         }
-        ProcessStatus::KeepAlive
+    }
+
+    /// Kicks off a background render of the swell if the patch or its length changed since the
+    /// last one.
+    fn request_swell_render(&mut self, context: &mut impl ProcessContext<Self>) {
+        let length = self.params.reverse_length.value() / 1000.0;
+        let render = (self.voice_params(), self.sample_rate, length);
+        if self.last_swell_render == Some(render) {
+            return;
+        }
+        if self
+            .shared
+            .swell_render
+            .pending
+            .swap(true, Ordering::AcqRel)
+        {
+            return;
+        }
+        self.last_swell_render = Some(render);
+        context.execute_background(Task::RenderSwell {
+            params: render.0,
+            sample_rate: render.1,
+            length: render.2,
+        });
+    }
+
+    /// Kicks off a background render of the current patch if it changed since the last one was
+    /// measured, so hits can be leveled before they are played.
+    fn request_level_measurement(&mut self, context: &mut impl ProcessContext<Self>) {
+        let measurement = (self.voice_params(), self.sample_rate);
+        if self.last_level_measurement == Some(measurement) {
+            return;
+        }
+        if self
+            .shared
+            .level_measurement_pending
+            .swap(true, Ordering::AcqRel)
+        {
+            return;
+        }
+        self.last_level_measurement = Some(measurement);
+        context.execute_background(Task::MeasureLevel {
+            params: measurement.0,
+            sample_rate: measurement.1,
+        });
     }
 }
 
@@ -321,24 +3817,577 @@ impl ClapPlugin for KickSynth {
         ClapFeature::Instrument,
         ClapFeature::Drum,
         ClapFeature::Mono,
+        ClapFeature::Stereo,
     ];
 }
 nih_export_clap!(KickSynth);
 
+/// A voice along with everything latched when its hit was triggered.
+#[derive(Copy, Clone, Debug)]
+struct VoiceSlot {
+    voice: Voice,
+    /// The MIDI note holding the voice, if any.
+    note: Option<u8>,
+    /// Auto-level gain, latched so a measurement finishing mid-hit doesn't cause a jump.
+    gain: f32,
+    /// Start freq multiplier from accents.
+    pitch_ratio: f32,
+    /// Whole sweep multiplier from the pitch CV.
+    tune_ratio: f32,
+    /// Whole sweep multiplier from the mod expression and generative rules.
+    expression_tune: f32,
+    /// Sweep depth multiplier from velocity.
+    sweep_depth: f32,
+    /// Sweep length multiplier from the note.
+    sweep_time: f32,
+    /// When the hit was triggered, in `KickSynth::voice_age`.
+    started: u64,
+    /// The parameters the voice keeps playing with after a preset was loaded partway through its
+    /// hit.
+    frozen: Option<VoiceParams>,
+    /// The kit slot whose patch the voice is playing, in kit mode.
+    kit_slot: Option<usize>,
+    /// The gain of a choked voice as it fades out, from 1 down to 0.
+    choke: Option<f32>,
+    /// The last frame the voice put out.
+    last_frame: [f32; 2],
+    /// The last frame of a hit that was cut off to start a new one, and how many samples it has
+    /// left to fade out over.
+    kill_ramp: Option<([f32; 2], u32)>,
+    /// Samples left until the voice is released on its own, for auditions and for quantized
+    /// notes that were let go of before they played.
+    gate_remaining: Option<usize>,
+}
+
+impl Default for VoiceSlot {
+    fn default() -> Self {
+        Self {
+            voice: Voice::default(),
+            note: None,
+            gain: 1.0,
+            pitch_ratio: 1.0,
+            tune_ratio: 1.0,
+            expression_tune: 1.0,
+            sweep_depth: 1.0,
+            sweep_time: 1.0,
+            started: 0,
+            frozen: None,
+            kit_slot: None,
+            choke: None,
+            last_frame: [0.0; 2],
+            kill_ramp: None,
+            gate_remaining: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Voice {
+    sample_rate: f32,
+    /// Engines A and B.
+    layers: [Layer; 2],
+    pitch_env_state: AhdsrState,
+    /// The pitch envelope after the lag, or `None` until the first sample of a hit.
+    lagged_pitch_env: Option<f32>,
+    amp_env_state: AhdsrState,
+    /// Set on trigger until the envelopes have taken their values for the hit.
+    envelopes_pending: bool,
+    time_since_trigger: f64,
+    /// Like `time_since_trigger`, but also restarted by `retrigger_pitch()`.
+    time_since_pitch_trigger: f64,
+    last_amp_env: f32,
+    /// The lowpassed attack click.
+    attack_click: f32,
+    crackle_noise: Rng,
+    /// The lowpassed crackle.
+    crackle: f32,
+    body_delay: BodyDelay,
+    /// Holds each engine back for lining them up.
+    layer_delays: [LayerDelay; 2],
+    /// Samples until the body and layer delays have played out what's left in them, once the amp
+    /// envelope has finished.
+    delay_tail: usize,
+    /// The last sample out of each engine, for the chain view.
+    layer_output: [f32; 2],
+    /// How many samples the voice renders for each one it puts out.
+    oversampling: usize,
+    /// An eighth order lowpass for each channel, for bringing oversampled voices back down.
+    decimators: [[Biquad; 4]; 2],
+}
+
+impl Voice {
+    /// Sets the host's sample rate. The voice renders at a multiple of it if it's below
+    /// `MIN_VOICE_SAMPLE_RATE`.
+    fn set_sample_rate(&mut self, host_sample_rate: f32) {
+        self.oversampling = usize::clamp(
+            (MIN_VOICE_SAMPLE_RATE / host_sample_rate).ceil() as usize,
+            1,
+            MAX_OVERSAMPLING,
+        );
+        let sample_rate = host_sample_rate * self.oversampling as f32;
+        let cutoff = DECIMATION_CUTOFF * 0.5 * host_sample_rate;
+        let sections = DECIMATION_SECTIONS
+            .map(|(zero, pole, q)| Biquad::analog_lowpass(zero, pole, q, cutoff, sample_rate));
+        self.decimators = [sections; 2];
+        self.sample_rate = sample_rate;
+        for layer in &mut self.layers {
+            layer.osc_state.sample_rate = sample_rate;
+        }
+        self.pitch_env_state.sample_rate = sample_rate;
+        self.amp_env_state.sample_rate = sample_rate;
+    }
+
+    fn trigger(&mut self, phase_offset: f32, crackle_seed: u64) {
+        self.amp_env_state.trigger(true);
+        self.pitch_env_state.trigger(true);
+        self.lagged_pitch_env = None;
+        self.envelopes_pending = true;
+        self.time_since_trigger = 0.0;
+        self.time_since_pitch_trigger = 0.0;
+        self.crackle_noise = Rng::new(crackle_seed);
+        for layer in &mut self.layers {
+            layer.trigger(phase_offset);
+        }
+    }
+
+    /// Starts the pitch envelope over, leaving everything else playing.
+    fn retrigger_pitch(&mut self) {
+        self.pitch_env_state.trigger(true);
+        self.time_since_pitch_trigger = 0.0;
+    }
+
+    /// Silences the voice right away.
+    fn stop(&mut self) {
+        self.amp_env_state.set_stage(AhdsrStage::NotTriggered);
+        self.pitch_env_state.set_stage(AhdsrStage::NotTriggered);
+        self.delay_tail = 0;
+    }
+
+    fn release(&mut self) {
+        self.amp_env_state.trigger(false);
+        self.pitch_env_state.trigger(false);
+    }
+
+    fn is_active(&self) -> bool {
+        self.envelope_active() || self.delay_tail > 0
+    }
+
+    /// Whether the amp envelope is still going, not counting the tail left in the delays.
+    fn envelope_active(&self) -> bool {
+        self.amp_env_state.current_stage != AhdsrStage::NotTriggered
+    }
+
+    /// Renders the next stereo frame at the host's sample rate.
+    fn render(&mut self, params: &VoiceParams) -> [f32; 2] {
+        if self.oversampling <= 1 {
+            return self.render_sample(params);
+        }
+        let mut frame = [0.0; 2];
+        for _ in 0..self.oversampling {
+            let oversampled = self.render_sample(params);
+            for ((side, filters), sample) in
+                frame.iter_mut().zip(&mut self.decimators).zip(oversampled)
+            {
+                *side = filters
+                    .iter_mut()
+                    .fold(sample, |sample, filter| filter.process(sample));
+            }
+        }
+        frame
+    }
+
+    /// Hands the envelopes their values on the first sample of a hit, and on every sample with
+    /// `live` envelopes.
+    fn update_envelopes(&mut self, amp_env: AhdsrValues, pitch_env: AhdsrValues, live: bool) {
+        if self.envelopes_pending || live {
+            self.pitch_env_state.set_values(pitch_env);
+            self.amp_env_state.set_values(amp_env);
+            self.envelopes_pending = false;
+        }
+    }
+
+    /// Renders the next stereo frame at the voice's own sample rate.
+    fn render_sample(&mut self, params: &VoiceParams) -> [f32; 2] {
+        // changing the times partway through a stage moves where the stage ends, which can skip
+        // stages or jump the level, so normally they're only taken when the hit starts
+        self.update_envelopes(params.amp_env, params.pitch_env, params.live_envelopes);
+        self.amp_env_state.anti_click = params.anti_click;
+
+        let pitch_env = match &params.pitch_breakpoints {
+            Some(breakpoints) => breakpoints.value_at(self.time_since_pitch_trigger as f32),
+            None => self.pitch_env_state.advance(),
+        };
+        // the lag starts out where the envelope does, so it only rounds off the corners
+        let pitch_env = match (self.lagged_pitch_env, params.pitch_lag > 0.0) {
+            (Some(lagged), true) => {
+                let coefficient = 1.0 - f32::exp(-(params.pitch_lag * self.sample_rate).recip());
+                lagged + coefficient * (pitch_env - lagged)
+            }
+            _ => pitch_env,
+        };
+        self.lagged_pitch_env = Some(pitch_env);
+        let amp_env = self.amp_env_state.advance();
+        self.time_since_trigger += (self.sample_rate as f64).recip();
+        self.time_since_pitch_trigger += (self.sample_rate as f64).recip();
+        let rise = f32::max(amp_env - self.last_amp_env, 0.0);
+        self.last_amp_env = amp_env;
+
+        let input = LayerInput {
+            freq: params.sweep_law.frequency(
+                pitch_env,
+                params.sweep_curve,
+                params.start_freq,
+                params.end_freq,
+            ),
+            amp_env,
+            time: self.time_since_trigger as f32,
+            release_gain: self.amp_env_state.release_gain(),
+        };
+        let mut frame = [0.0; 2];
+        self.layer_output = [0.0; 2];
+        for (index, (engine, gain)) in [
+            (params.engine, 1.0 - params.engine_blend),
+            (params.engine_b, params.engine_blend),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            // silent layers don't need to keep up
+            if gain > 0.0 {
+                let tune_ratio = semitones_to_ratio(params.engine_tune[index]);
+                let layer = &mut self.layers[index];
+                let sample = layer.render(engine, params, tune_ratio, input);
+                let sample = gain * layer.tilt(sample, params.engine_tilt[index]);
+                // the delay is set in host samples
+                let delay = self.oversampling.max(1) * params.engine_delay[index];
+                let sample =
+                    params.engine_polarity[index] * self.layer_delays[index].process(sample, delay);
+                self.layer_output[index] = sample;
+                for (side, pan_gain) in frame.iter_mut().zip(balance(params.engine_pan[index])) {
+                    *side += pan_gain * sample;
+                }
+            }
+        }
+        let body_time = input.time - params.body_delay;
+        let body_gain = match (body_time >= 0.0, params.body_fade > 0.0) {
+            (false, _) => 0.0,
+            (true, true) => f32::min(body_time / params.body_fade, 1.0),
+            (true, false) => 1.0,
+        };
+        let delay = (params.body_delay * self.sample_rate).round() as usize;
+        let frame = self
+            .body_delay
+            .process(frame, delay)
+            .map(|side| body_gain * side);
+        self.delay_tail = match self.envelope_active() {
+            true => {
+                let layer_delay = params.engine_delay.into_iter().max().unwrap_or(0);
+                usize::min(delay, BODY_DELAY_MAX_SAMPLES - 1)
+                    + usize::min(
+                        self.oversampling.max(1) * layer_delay,
+                        LAYER_DELAY_BUFFER_LEN - 1,
+                    )
+            }
+            false => self.delay_tail.saturating_sub(1),
+        };
+
+        let mut sample = 0.0;
+
+        // a one pole lowpass, scaled so an instant attack still peaks at full scale
+        let coefficient =
+            1.0 - f32::exp(-f32::consts::TAU * params.attack_click_tone / self.sample_rate);
+        self.attack_click += coefficient * (rise / coefficient - self.attack_click);
+        sample += params.attack_click * self.attack_click;
+
+        if params.crackle > 0.0 {
+            // sparse impulses of random size and polarity
+            let impulse =
+                match self.crackle_noise.next_f32() < params.crackle_density / self.sample_rate {
+                    true => 2.0 * self.crackle_noise.next_f32() - 1.0,
+                    false => 0.0,
+                };
+            let coefficient =
+                1.0 - f32::exp(-f32::consts::TAU * params.crackle_tone / self.sample_rate);
+            // a leaky integrator, so each crackle peaks at its impulse's size whatever the tone
+            self.crackle += impulse - coefficient * self.crackle;
+            sample += params.crackle * amp_env * self.crackle;
+        }
+        frame.map(|side| match params.voice_drive {
+            Some(drive) => soft_clip(drive * (side + sample), params.voice_character),
+            None => side + sample,
+        })
+    }
+}
+
+/// Holds a voice's engines back by up to `BODY_DELAY_MAX_SAMPLES`.
+#[derive(Copy, Clone, Debug)]
+struct BodyDelay {
+    buffer: [[f32; 2]; BODY_DELAY_MAX_SAMPLES],
+    write: usize,
+}
+
+impl Default for BodyDelay {
+    fn default() -> Self {
+        Self {
+            buffer: [[0.0; 2]; BODY_DELAY_MAX_SAMPLES],
+            write: 0,
+        }
+    }
+}
+
+impl BodyDelay {
+    fn process(&mut self, frame: [f32; 2], delay: usize) -> [f32; 2] {
+        let delay = usize::min(delay, BODY_DELAY_MAX_SAMPLES - 1);
+        self.buffer[self.write] = frame;
+        let read = (self.write + BODY_DELAY_MAX_SAMPLES - delay) % BODY_DELAY_MAX_SAMPLES;
+        self.write = (self.write + 1) % BODY_DELAY_MAX_SAMPLES;
+        self.buffer[read]
+    }
+}
+
+/// Room in a layer delay, enough for `LAYER_DELAY_MAX_SAMPLES` host samples however much the voice
+/// is oversampled.
+const LAYER_DELAY_BUFFER_LEN: usize = LAYER_DELAY_MAX_SAMPLES * MAX_OVERSAMPLING;
+
+/// Holds a layer back by up to `LAYER_DELAY_BUFFER_LEN` samples.
+#[derive(Copy, Clone, Debug)]
+struct LayerDelay {
+    buffer: [f32; LAYER_DELAY_BUFFER_LEN],
+    write: usize,
+}
+
+impl Default for LayerDelay {
+    fn default() -> Self {
+        Self {
+            buffer: [0.0; LAYER_DELAY_BUFFER_LEN],
+            write: 0,
+        }
+    }
+}
+
+impl LayerDelay {
+    fn process(&mut self, sample: f32, delay: usize) -> f32 {
+        let delay = usize::min(delay, LAYER_DELAY_BUFFER_LEN - 1);
+        self.buffer[self.write] = sample;
+        let read = (self.write + LAYER_DELAY_BUFFER_LEN - delay) % LAYER_DELAY_BUFFER_LEN;
+        self.write = (self.write + 1) % LAYER_DELAY_BUFFER_LEN;
+        self.buffer[read]
+    }
+}
+
+/// What every layer of a voice shares on a given sample.
+#[derive(Copy, Clone, Debug)]
+struct LayerInput {
+    /// The frequency of the main pitch sweep.
+    freq: f32,
+    amp_env: f32,
+    /// Seconds since the voice was triggered.
+    time: f32,
+    /// How far the amp envelope has fallen since the note was released, from 1 to 0.
+    release_gain: f32,
+}
+
+/// One engine running within a voice.
+#[derive(Copy, Clone, Debug, Default)]
+struct Layer {
+    osc_state: OscillatorState,
+    resonator: Resonator,
+    click_noise: Rng,
+    membrane: Membrane,
+    /// The tilt EQ's lowpass state.
+    tilt_lowpass: f32,
+}
+
+impl Layer {
+    fn trigger(&mut self, phase_offset: f32) {
+        self.osc_state.phase = phase_offset as f64;
+        self.resonator.strike();
+        // the same click on every hit, like the rest of the voice
+        self.click_noise = Rng::default();
+        self.membrane.struck = true;
+    }
+
+    /// Splits `sample` into lows and highs around `TILT_PIVOT_HZ` and boosts one while cutting the
+    /// other, by `tilt` decibels between them.
+    fn tilt(&mut self, sample: f32, tilt: f32) -> f32 {
+        let sample_rate = self.osc_state.sample_rate;
+        let coefficient = 1.0 - f32::exp(-f32::consts::TAU * TILT_PIVOT_HZ / sample_rate);
+        self.tilt_lowpass += coefficient * (sample - self.tilt_lowpass);
+        let highs = sample - self.tilt_lowpass;
+        util::db_to_gain(-tilt / 2.0) * self.tilt_lowpass + util::db_to_gain(tilt / 2.0) * highs
+    }
+
+    fn render(
+        &mut self,
+        engine: Engine,
+        params: &VoiceParams,
+        tune_ratio: f32,
+        input: LayerInput,
+    ) -> f32 {
+        let sample_rate = self.osc_state.sample_rate;
+        let freq = f32::max(input.freq * tune_ratio, params.sweep_floor);
+        let amp_env = input.amp_env;
+        match engine {
+            Engine::Sweep => amp_env * osc_sine(self.osc_state.advance(freq)),
+            Engine::BridgedT => {
+                // the resonator decays on its own, so only the release is taken from the envelope
+                input.release_gain
+                    * self
+                        .resonator
+                        .process(freq, params.amp_env.decay, sample_rate)
+            }
+            Engine::Tr909 => {
+                let time = input.time;
+                let sweep_decay = params.pitch_env.decay.max(1e-3);
+                // a quick drop right after the click, then a slower settle onto the tuned pitch
+                let sweep = 0.5 * f32::exp(-time / TR909_FAST_SWEEP_SECONDS)
+                    + 0.5 * f32::exp(-4.6 * time / sweep_decay);
+                let freq = tune_ratio
+                    * params.end_freq
+                    * f32::powf(params.start_freq / params.end_freq, sweep);
+                let freq = f32::max(freq, params.sweep_floor);
+                let triangle = osc_triangle(self.osc_state.advance(freq));
+                let body = f32::tanh(2.0 * triangle) / f32::tanh(2.0);
+
+                let pulse = match time < TR909_PULSE_SECONDS {
+                    true => 1.0,
+                    false => 0.0,
+                };
+                let noise = (2.0 * self.click_noise.next_f32() - 1.0)
+                    * f32::exp(-time / TR909_NOISE_SECONDS);
+                amp_env * (body + params.click * (pulse + noise))
+            }
+            Engine::Membrane => amp_env * self.membrane.process(params, freq, sample_rate),
+        }
+    }
+}
+
+/// A two pole resonator, struck by a single impulse.
+#[derive(Copy, Clone, Debug, Default)]
+struct Resonator {
+    y1: f32,
+    y2: f32,
+    struck: bool,
+}
+
+impl Resonator {
+    fn strike(&mut self) {
+        *self = Self {
+            struck: true,
+            ..Self::default()
+        };
+    }
+
+    /// Rings at `freq`, falling by 60 dB over `decay` seconds.
+    fn process(&mut self, freq: f32, decay: f32, sample_rate: f32) -> f32 {
+        let omega = f32::consts::TAU * (freq / sample_rate).min(0.49);
+        let radius = f32::exp(-6.9 / (decay.max(1e-3) * sample_rate));
+        // scaled so the ring starts out at about full scale whatever the pitch
+        let input = match std::mem::take(&mut self.struck) {
+            true => omega.sin(),
+            false => 0.0,
+        };
+        let y = input + 2.0 * radius * omega.cos() * self.y1 - radius * radius * self.y2;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Points across the membrane simulation, not counting the fixed rim.
+const MEMBRANE_SIZE: usize = 8;
+
+/// A square membrane with a fixed rim, simulated with finite differences.
+#[derive(Copy, Clone, Debug, Default)]
+struct Membrane {
+    current: [[f32; MEMBRANE_SIZE]; MEMBRANE_SIZE],
+    previous: [[f32; MEMBRANE_SIZE]; MEMBRANE_SIZE],
+    /// Set when the voice is triggered, so the strike lands with the next sample's parameters.
+    struck: bool,
+}
+
+impl Membrane {
+    /// Strikes the membrane with a round mallet, leaving it at rest everywhere else.
+    fn strike(&mut self, position: f32) {
+        let center = (MEMBRANE_SIZE - 1) as f32 / 2.0;
+        // along the diagonal, stopping short of the rim
+        let strike_at = center - position * (center - 1.0);
+        for (y, row) in self.current.iter_mut().enumerate() {
+            for (x, point) in row.iter_mut().enumerate() {
+                let distance = f32::hypot(x as f32 - strike_at, y as f32 - strike_at);
+                *point = match distance < 2.0 {
+                    true => 0.5 + 0.5 * f32::cos(f32::consts::PI * distance / 2.0),
+                    false => 0.0,
+                };
+            }
+        }
+        self.previous = self.current;
+    }
+
+    /// Advances the simulation by one sample with the lowest mode at `freq`, returning the
+    /// displacement near the middle.
+    fn process(&mut self, params: &VoiceParams, freq: f32, sample_rate: f32) -> f32 {
+        if std::mem::take(&mut self.struck) {
+            self.strike(params.strike_position);
+        }
+
+        let pickup = self.current[MEMBRANE_SIZE / 2][MEMBRANE_SIZE / 2];
+        // membranes go sharp while they're stretched by a hard hit
+        let freq = freq * (1.0 + params.membrane_tension * pickup.abs());
+        // pick the wave speed that puts the lowest mode of the grid at `freq`
+        let mode = f32::sin(f32::consts::PI / (2.0 * (MEMBRANE_SIZE + 1) as f32));
+        let omega = f32::consts::TAU * (freq / sample_rate).min(0.1);
+        // anything faster than this blows up
+        let speed_squared = (f32::sin(omega / 2.0).powi(2) / (2.0 * mode * mode)).min(0.5);
+        let ring_seconds = 2.0 * f32::powf(0.025, params.membrane_damping);
+        let damping = 6.9 / (ring_seconds * sample_rate);
+
+        let at = |grid: &[[f32; MEMBRANE_SIZE]; MEMBRANE_SIZE], x: isize, y: isize| match (0
+            ..MEMBRANE_SIZE as isize)
+            .contains(&x)
+            && (0..MEMBRANE_SIZE as isize).contains(&y)
+        {
+            true => grid[y as usize][x as usize],
+            false => 0.0,
+        };
+        let mut next = [[0.0; MEMBRANE_SIZE]; MEMBRANE_SIZE];
+        for (y, row) in next.iter_mut().enumerate() {
+            for (x, point) in row.iter_mut().enumerate() {
+                let (xi, yi) = (x as isize, y as isize);
+                let here = self.current[y][x];
+                let laplacian = at(&self.current, xi - 1, yi)
+                    + at(&self.current, xi + 1, yi)
+                    + at(&self.current, xi, yi - 1)
+                    + at(&self.current, xi, yi + 1)
+                    - 4.0 * here;
+                *point = (2.0 * here - (1.0 - damping) * self.previous[y][x]
+                    + speed_squared * laplacian)
+                    / (1.0 + damping);
+            }
+        }
+        self.previous = self.current;
+        self.current = next;
+        pickup
+    }
+}
+
 #[derive(Copy, Clone, Debug, Default)]
 struct OscillatorState {
     sample_rate: f32,
-    phase: f32,
+    // accumulated in f64 so long tails at high sample rates don't drift out of tune
+    phase: f64,
 }
 
 impl OscillatorState {
     fn advance(&mut self, frequency: f32) -> f32 {
         let old_phase = self.phase;
-        self.phase += frequency * self.sample_rate.recip();
+        // the increment may be more than a whole cycle, or negative under FM
+        self.phase = f64::rem_euclid(self.phase + frequency as f64 / self.sample_rate as f64, 1.0);
+        // tiny negative phases round up to exactly 1.0
         if self.phase >= 1.0 {
-            self.phase -= f32::floor(self.phase);
+            self.phase = 0.0;
         }
-        old_phase
+        old_phase as f32
     }
 }
 
@@ -377,80 +4426,106 @@ impl AhdsrStage {
     }
 }
 
+/// The shortest attack the amp envelope ramps up over with Anti-Click on. Short enough not to soften
+/// the transient.
+const MIN_ATTACK_SECONDS: f32 = 0.0002;
+/// The shortest release with Anti-Click on, for notes cut off partway through a hit.
+const MIN_RELEASE_SECONDS: f32 = 0.001;
+
 #[derive(Copy, Clone, Debug, Default)]
 struct AhdsrState {
     sample_rate: f32,
 
     current_stage: AhdsrStage,
-    samples_since_stage_start: u64,
+    /// Seconds. Kept in double precision like the oscillator phase, so long stages don't drift.
+    time_in_stage: f64,
     last_value_at_transition: f32,
     current: f32,
 
-    attack: f32,
-    hold: f32,
-    decay: f32,
-    sustain: f32,
-    release: f32,
+    values: AhdsrValues,
+    /// Stretches the attack and release out to at least `MIN_ATTACK_SECONDS` and
+    /// `MIN_RELEASE_SECONDS`.
+    anti_click: bool,
 }
 
 impl AhdsrState {
-    fn apply_params(&mut self, params: &AhdsrParams) {
-        self.attack = params.attack_time.smoothed.next();
-        self.hold = params.hold_time.smoothed.next();
-        self.decay = params.decay_time.smoothed.next();
-        self.sustain = params.sustain_level.smoothed.next();
-        self.release = params.release_time.smoothed.next();
+    fn set_values(&mut self, values: AhdsrValues) {
+        self.values = values;
     }
 
     fn trigger(&mut self, triggered: bool) {
+        // one-shot envelopes play out on their own
+        if !triggered && self.values.one_shot {
+            return;
+        }
         self.set_stage(match triggered {
             true => AhdsrStage::Attack,
             false => AhdsrStage::Release,
         });
     }
 
+    /// The envelope's level relative to where it was when the release started, or 1 before then.
+    fn release_gain(&self) -> f32 {
+        match self.current_stage {
+            AhdsrStage::Release if self.last_value_at_transition > 0.0 => {
+                self.current / self.last_value_at_transition
+            }
+            AhdsrStage::Release | AhdsrStage::NotTriggered => 0.0,
+            _ => 1.0,
+        }
+    }
+
     fn set_stage(&mut self, stage: AhdsrStage) {
         self.current_stage = stage;
-        self.samples_since_stage_start = 0;
-        let (start, _) = stage.endpoint_values(self.current, self.sustain);
+        self.time_in_stage = 0.0;
+        let (start, _) = stage.endpoint_values(self.current, self.values.sustain_level());
         self.current = start;
         self.last_value_at_transition = start;
     }
 
     fn advance(&mut self) -> f32 {
-        let seconds_per_sample = self.sample_rate.recip();
+        self.advance_by((self.sample_rate as f64).recip())
+    }
 
+    /// Returns the envelope's value and then moves it on by `seconds`, which doesn't need to be the
+    /// same from one call to the next.
+    fn advance_by(&mut self, seconds: f64) -> f32 {
         let stage_time = loop {
             let time = match self.current_stage {
                 // neither of these stages have a time associated with them, so just bail early.
                 AhdsrStage::NotTriggered => return 0.0,
-                AhdsrStage::Sustain => return self.sustain,
+                AhdsrStage::Sustain if self.values.one_shot => {
+                    self.set_stage(AhdsrStage::NotTriggered);
+                    return 0.0;
+                }
+                AhdsrStage::Sustain => return self.values.sustain,
 
-                AhdsrStage::Attack => self.attack,
-                AhdsrStage::Hold => self.hold,
-                AhdsrStage::Decay => self.decay,
-                AhdsrStage::Release => self.release,
+                AhdsrStage::Attack if self.anti_click => {
+                    f32::max(self.values.attack, MIN_ATTACK_SECONDS)
+                }
+                AhdsrStage::Release if self.anti_click => {
+                    f32::max(self.values.release, MIN_RELEASE_SECONDS)
+                }
+                AhdsrStage::Attack => self.values.attack,
+                AhdsrStage::Hold => self.values.hold,
+                AhdsrStage::Decay => self.values.decay,
+                AhdsrStage::Release => self.values.release,
             };
-            if time > 0.0 {
-                // shatter the fabric of spacetime, etc.
+            if self.time_in_stage < time as f64 {
                 break time;
             }
-            // skip to the next stage that isn't zero-length
+            // skip to the next stage, carrying over however far the last step went past the end
+            // of this one. zero-length stages are skipped right away.
+            let overshoot = self.time_in_stage - time as f64;
             self.set_stage(self.current_stage.next());
+            self.time_in_stage = overshoot;
         };
 
-        let mut time_since_stage_start = self.samples_since_stage_start as f32 * seconds_per_sample;
-
-        if time_since_stage_start >= stage_time {
-            self.set_stage(self.current_stage.next());
-            time_since_stage_start = 0.0;
-        }
-        self.samples_since_stage_start += 1;
-
         let (start_value, end_value) = self
             .current_stage
-            .endpoint_values(self.last_value_at_transition, self.sustain);
-        let t = time_since_stage_start / stage_time;
+            .endpoint_values(self.last_value_at_transition, self.values.sustain_level());
+        let t = (self.time_in_stage / stage_time as f64) as f32;
+        self.time_in_stage += seconds;
         self.current = lerp(t, start_value.powf(0.5), end_value.powf(0.5)).powf(2.0);
         self.current
     }
@@ -464,6 +4539,284 @@ fn lerp(t: f32, a: f32, b: f32) -> f32 {
     a + (b - a) * t
 }
 
+/// The frequency of the equal tempered note closest to `freq`.
+fn nearest_note_freq(freq: f32) -> f32 {
+    util::f32_midi_note_to_freq(util::freq_to_midi_note(freq).round())
+}
+
+fn semitones_to_ratio(semitones: f32) -> f32 {
+    f32::powf(2.0, semitones / 12.0)
+}
+
+fn v2s_f32_semitones() -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(|value| format!("{value:+.1} st"))
+}
+
+type StringToValue = Arc<dyn Fn(&str) -> Option<f32> + Send + Sync>;
+
+fn s2v_f32_semitones() -> StringToValue {
+    Arc::new(|string| {
+        let string = string.trim();
+        let string = string.strip_suffix("st").unwrap_or(string);
+        string.trim().parse().ok()
+    })
+}
+
+/// Reads a time in seconds, taking either "ms" or "s" as the unit. Bare numbers are multiplied by
+/// `bare_scale`.
+fn parse_seconds(string: &str, bare_scale: f32) -> Option<f32> {
+    let string = string.trim().to_ascii_lowercase();
+    let (number, scale) = match (string.strip_suffix("ms"), string.strip_suffix('s')) {
+        (Some(number), _) => (number, 0.001),
+        (None, Some(number)) => (number, 1.0),
+        (None, None) => (string.as_str(), bare_scale),
+    };
+    number.trim().parse::<f32>().ok().map(|value| value * scale)
+}
+
+fn s2v_f32_seconds() -> StringToValue {
+    Arc::new(|string| parse_seconds(string, 1.0))
+}
+
+fn s2v_f32_ms() -> StringToValue {
+    Arc::new(|string| parse_seconds(string, 0.001).map(|seconds| seconds * 1000.0))
+}
+
+/// Like `formatters::s2v_f32_hz_then_khz`, but also takes note names with an optional offset in
+/// cents, like "A1" or "F#1+20c".
+fn s2v_f32_hz_or_note() -> StringToValue {
+    let hz = formatters::s2v_f32_hz_then_khz();
+    Arc::new(move |string| parse_note_freq(string).or_else(|| hz(string)))
+}
+
+fn parse_note_freq(string: &str) -> Option<f32> {
+    let mut chars = string.trim().chars();
+    let semitone = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (accidental, rest) = match (rest.strip_prefix('#'), rest.strip_prefix('b')) {
+        (Some(rest), _) => (1, rest),
+        (None, Some(rest)) => (-1, rest),
+        (None, None) => (0, rest),
+    };
+    // the octave can be negative, so the cents only start at a sign after its first character
+    let split = rest
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '+' || *c == '-')
+        .map_or(rest.len(), |(i, _)| i);
+    let (octave, cents) = rest.split_at(split);
+    let octave: i32 = octave.trim().parse().ok()?;
+    let cents: f32 = match cents.trim() {
+        "" => 0.0,
+        cents => {
+            let cents = cents.strip_suffix('c').unwrap_or(cents);
+            cents.trim().parse().ok()?
+        }
+    };
+    let note = (octave + 1) * 12 + semitone + accidental;
+    Some(util::f32_midi_note_to_freq(note as f32 + cents / 100.0))
+}
+
+/// For parameters stored in decibels, unlike `formatters::s2v_f32_gain_to_db` which converts to a
+/// linear gain.
+fn s2v_f32_db() -> StringToValue {
+    Arc::new(|string| {
+        let string = string.trim().to_ascii_lowercase();
+        let string = string.strip_suffix("db").unwrap_or(&string);
+        string.trim().parse().ok()
+    })
+}
+
+/// Writes a stereo frame to one sample of `output`, mixed down for mono outputs.
+fn write_frame(output: &mut [&mut [f32]], sample_id: usize, frame: [f32; 2]) {
+    let channels = output.len();
+    for (channel, samples) in output.iter_mut().enumerate() {
+        samples[sample_id] = match channels {
+            1 => 0.5 * (frame[0] + frame[1]),
+            _ => frame[channel.min(1)],
+        };
+    }
+}
+
+/// Left and right gains for a pan from -1 to 1. The center leaves both sides at full level, so
+/// mono hits sound the same as before they could be panned.
+fn balance(pan: f32) -> [f32; 2] {
+    [f32::min(1.0 - pan, 1.0), f32::min(1.0 + pan, 1.0)]
+}
+
 fn osc_sine(phase: f32) -> f32 {
     f32::sin(f32::consts::TAU * phase)
 }
+
+/// Lines up with `osc_sine`, peaking at a quarter cycle.
+fn osc_triangle(phase: f32) -> f32 {
+    1.0 - 4.0 * f32::abs((phase + 0.75).fract() - 0.5)
+}
+
+/// Saturates towards ±1. The knee gets sharper as `character` goes from 0 to 1.
+fn soft_clip(x: f32, character: f32) -> f32 {
+    let knee = 2.0 + 6.0 * character;
+    x / (1.0 + x.abs().powf(knee)).powf(knee.recip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oscillator(phase: f64) -> OscillatorState {
+        OscillatorState {
+            sample_rate: 48000.0,
+            phase,
+        }
+    }
+
+    #[test]
+    fn oscillator_returns_phase_before_advancing() {
+        let mut osc = oscillator(0.0);
+        assert_eq!(osc.advance(12000.0), 0.0);
+        assert_eq!(osc.advance(12000.0), 0.25);
+        assert_eq!(osc.advance(12000.0), 0.5);
+    }
+
+    #[test]
+    fn oscillator_wraps_frequencies_above_sample_rate() {
+        let mut osc = oscillator(0.0);
+        for _ in 0..1000 {
+            osc.advance(1.0e7);
+            assert!((0.0..1.0).contains(&osc.phase), "phase {}", osc.phase);
+        }
+        // 1.5 cycles per sample lands halfway around
+        let mut osc = oscillator(0.0);
+        osc.advance(72000.0);
+        assert!((osc.phase - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn oscillator_wraps_negative_frequencies() {
+        let mut osc = oscillator(0.1);
+        osc.advance(-9600.0);
+        assert!((osc.phase - 0.9).abs() < 1e-9);
+        for _ in 0..1000 {
+            osc.advance(-1.0e7);
+            assert!((0.0..1.0).contains(&osc.phase), "phase {}", osc.phase);
+        }
+    }
+
+    #[test]
+    fn oscillator_tiny_negative_steps_wrap_to_zero() {
+        // small enough that wrapping rounds up to exactly 1.0
+        let mut osc = oscillator(0.0);
+        osc.advance(-1.0e-15);
+        assert_eq!(osc.phase, 0.0);
+    }
+
+    #[test]
+    fn envelopes_are_latched_at_trigger() {
+        let first = AhdsrValues {
+            decay: 0.5,
+            ..Default::default()
+        };
+        let second = AhdsrValues {
+            decay: 2.0,
+            ..Default::default()
+        };
+        let mut voice = Voice::default();
+        voice.trigger(0.0, 0);
+        voice.update_envelopes(first, first, false);
+        voice.update_envelopes(second, second, false);
+        assert_eq!(voice.amp_env_state.values, first);
+        assert_eq!(voice.pitch_env_state.values, first);
+
+        // the next hit picks up the new values
+        voice.trigger(0.0, 0);
+        voice.update_envelopes(second, second, false);
+        assert_eq!(voice.amp_env_state.values, second);
+        assert_eq!(voice.pitch_env_state.values, second);
+    }
+
+    #[test]
+    fn live_envelopes_follow_every_change() {
+        let first = AhdsrValues {
+            sustain: 0.5,
+            ..Default::default()
+        };
+        let second = AhdsrValues {
+            sustain: 0.25,
+            ..Default::default()
+        };
+        let mut voice = Voice::default();
+        voice.trigger(0.0, 0);
+        voice.update_envelopes(first, first, true);
+        voice.update_envelopes(second, second, true);
+        assert_eq!(voice.amp_env_state.values, second);
+        assert_eq!(voice.pitch_env_state.values, second);
+    }
+
+    fn envelope(values: AhdsrValues) -> AhdsrState {
+        let mut envelope = AhdsrState {
+            sample_rate: 48000.0,
+            ..Default::default()
+        };
+        envelope.set_values(values);
+        envelope.trigger(true);
+        envelope
+    }
+
+    #[test]
+    fn envelope_skips_zero_length_stages() {
+        let mut env = envelope(AhdsrValues::ahdsr(0.0, 0.0, 1.0, 0.5, 1.0));
+        assert_eq!(env.advance_by(0.01), 1.0);
+        assert_eq!(env.current_stage, AhdsrStage::Decay);
+
+        let mut env = envelope(AhdsrValues::ahdsr(0.0, 0.0, 0.0, 0.5, 1.0));
+        assert_eq!(env.advance_by(0.01), 0.5);
+        assert_eq!(env.current_stage, AhdsrStage::Sustain);
+    }
+
+    #[test]
+    fn envelope_carries_overshoot_into_the_next_stage() {
+        let mut env = envelope(AhdsrValues::ahdsr(1.0, 1.0, 1.0, 0.5, 1.0));
+        env.advance_by(0.75);
+        env.advance_by(0.5);
+        assert_eq!(env.current_stage, AhdsrStage::Attack);
+        // a quarter of a second past the end of the attack
+        assert_eq!(env.advance_by(0.5), 1.0);
+        assert_eq!(env.current_stage, AhdsrStage::Hold);
+        assert!((env.time_in_stage - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn envelope_carries_overshoot_across_several_stages() {
+        let mut env = envelope(AhdsrValues::ahdsr(0.1, 0.1, 1.0, 0.5, 1.0));
+        env.advance_by(0.35);
+        let value = env.advance_by(0.0);
+        assert_eq!(env.current_stage, AhdsrStage::Decay);
+        let expected = lerp(0.15, 1.0, f32::sqrt(0.5)).powi(2);
+        assert!((value - expected).abs() < 1e-5, "{value} != {expected}");
+    }
+
+    #[test]
+    fn envelope_lands_in_the_same_place_whatever_the_step_sizes() {
+        let values = AhdsrValues::ahdsr(0.2, 0.1, 0.5, 0.3, 1.0);
+        let mut even = envelope(values);
+        for _ in 0..100 {
+            even.advance_by(0.005);
+        }
+        let mut uneven = envelope(values);
+        for step in [0.13, 0.002, 0.2, 0.048, 0.07, 0.05] {
+            uneven.advance_by(step);
+        }
+        assert_eq!(even.current_stage, uneven.current_stage);
+        let (even, uneven) = (even.advance_by(0.0), uneven.advance_by(0.0));
+        assert!((even - uneven).abs() < 1e-5, "{even} != {uneven}");
+    }
+}
@@ -0,0 +1,568 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::filter::Biquad;
+
+const TRUE_PEAK_TAPS: usize = 12;
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+/// Anything quieter than this no longer counts towards a hit's duration.
+const AUDIBLE_THRESHOLD_DB: f32 = -60.0;
+const CPU_WINDOW_SECONDS: f32 = 0.5;
+/// Where the correlation meter's lowpasses cut off, leaving the part of each layer that adds up or
+/// cancels out.
+const CORRELATION_LOWPASS_HZ: f32 = 150.0;
+/// How long the correlation meter looks back, roughly.
+const CORRELATION_WINDOW_SECONDS: f32 = 0.3;
+/// Below this much energy the layers are treated as silent, and the last reading is kept.
+const CORRELATION_MIN_ENERGY: f32 = 1e-6;
+pub const HISTORY_BARS: usize = 8;
+pub const HISTORY_STEPS_PER_BAR: usize = 32;
+pub const HISTORY_STEPS: usize = HISTORY_BARS * HISTORY_STEPS_PER_BAR;
+
+/// The measurements of the most recent hit, published for the editor.
+pub struct HitMeasurement {
+    pub momentary_lufs: AtomicF32,
+    pub true_peak_db: AtomicF32,
+    pub duration: AtomicF32,
+}
+
+impl Default for HitMeasurement {
+    fn default() -> Self {
+        Self {
+            momentary_lufs: AtomicF32::new(f32::NEG_INFINITY),
+            true_peak_db: AtomicF32::new(f32::NEG_INFINITY),
+            duration: AtomicF32::new(0.0),
+        }
+    }
+}
+
+/// The points along the signal chain that the editor's chain view meters, in the order the signal
+/// passes through them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChainNode {
+    EngineA,
+    EngineB,
+    Voices,
+    Mixer,
+    Multiband,
+    AnalogClip,
+    FxMix,
+    Subsonic,
+    Output,
+}
+
+impl ChainNode {
+    pub const ALL: [ChainNode; 9] = [
+        ChainNode::EngineA,
+        ChainNode::EngineB,
+        ChainNode::Voices,
+        ChainNode::Mixer,
+        ChainNode::Multiband,
+        ChainNode::AnalogClip,
+        ChainNode::FxMix,
+        ChainNode::Subsonic,
+        ChainNode::Output,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChainNode::EngineA => "Engine A",
+            ChainNode::EngineB => "Engine B",
+            ChainNode::Voices => "Voices",
+            ChainNode::Mixer => "Mixer",
+            ChainNode::Multiband => "Multiband",
+            ChainNode::AnalogClip => "Analog Clip",
+            ChainNode::FxMix => "FX Mix",
+            ChainNode::Subsonic => "Subsonic",
+            ChainNode::Output => "Output",
+        }
+    }
+}
+
+/// The peak level at each `ChainNode` since the editor last took it, published for the editor.
+#[derive(Default)]
+pub struct ChainLevels {
+    peaks: [AtomicF32; ChainNode::ALL.len()],
+}
+
+impl ChainLevels {
+    pub(crate) fn publish(&self, peaks: &ChainPeaks) {
+        for (level, peak) in self.peaks.iter().zip(peaks.0) {
+            level.fetch_max(peak, Ordering::Relaxed);
+        }
+    }
+
+    /// The peak gain at `node`, resetting it for the next call.
+    pub fn take(&self, node: ChainNode) -> f32 {
+        self.peaks[node as usize].swap(0.0, Ordering::Relaxed)
+    }
+}
+
+/// The peaks along the signal chain over one block on the audio thread.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct ChainPeaks([f32; ChainNode::ALL.len()]);
+
+impl ChainPeaks {
+    pub(crate) fn process(&mut self, node: ChainNode, frame: [f32; 2]) {
+        let peak = &mut self.0[node as usize];
+        *peak = frame
+            .into_iter()
+            .fold(*peak, |peak, side| f32::max(peak, side.abs()));
+    }
+}
+
+/// Measures how the lows of two signals line up on the audio thread, from -1 when they cancel
+/// out to 1 when they add up.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CorrelationMeter {
+    lowpasses: [Biquad; 2],
+    /// How much of the running sums carry over to the next sample.
+    decay: f32,
+    /// Running sums of `a * b`, `a * a`, and `b * b`.
+    sums: [f32; 3],
+}
+
+impl CorrelationMeter {
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.lowpasses = [Biquad::lowpass(
+            CORRELATION_LOWPASS_HZ,
+            std::f32::consts::FRAC_1_SQRT_2,
+            sample_rate,
+        ); 2];
+        self.decay = f32::exp(-(CORRELATION_WINDOW_SECONDS * sample_rate).recip());
+        self.sums = [0.0; 3];
+    }
+
+    pub(crate) fn process(&mut self, [a, b]: [f32; 2]) {
+        let a = self.lowpasses[0].process(a);
+        let b = self.lowpasses[1].process(b);
+        for (sum, product) in self.sums.iter_mut().zip([a * b, a * a, b * b]) {
+            *sum = *sum * self.decay + product;
+        }
+    }
+
+    pub(crate) fn publish(&self, correlation: &AtomicF32) {
+        let [ab, aa, bb] = self.sums;
+        let energy = aa * bb;
+        if energy > CORRELATION_MIN_ENERGY {
+            correlation.store(ab / energy.sqrt(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// The output's peaks on each channel over the last `HISTORY_BARS` bars of the host's transport,
+/// in steps of a fraction of a bar, published for the editor.
+pub struct PeakHistory {
+    peaks: [[AtomicF32; 2]; HISTORY_STEPS],
+    /// The step being recorded, or -1 while the transport is stopped.
+    position: AtomicI32,
+}
+
+impl Default for PeakHistory {
+    fn default() -> Self {
+        Self {
+            peaks: std::array::from_fn(|_| Default::default()),
+            position: AtomicI32::new(-1),
+        }
+    }
+}
+
+impl PeakHistory {
+    /// The peak gain on `channel` during `step`.
+    pub fn peak(&self, step: usize, channel: usize) -> f32 {
+        self.peaks[step][channel].load(Ordering::Relaxed)
+    }
+
+    pub fn position(&self) -> Option<usize> {
+        usize::try_from(self.position.load(Ordering::Relaxed)).ok()
+    }
+
+    pub(crate) fn clear(&self) {
+        for peak in self.peaks.iter().flatten() {
+            peak.store(0.0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Where a block starts on the host's timeline.
+#[derive(Copy, Clone, Debug)]
+struct BlockTiming {
+    beat: f64,
+    beats_per_sample: f64,
+    bar_start: f64,
+    bar_number: i64,
+    bar_beats: f64,
+}
+
+/// Works out which step of the `PeakHistory` each sample falls in on the audio thread, and keeps
+/// the peak of the current step until it moves on.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PeakHistoryRecorder {
+    timing: Option<BlockTiming>,
+    step: Option<usize>,
+    peak: [f32; 2],
+}
+
+impl PeakHistoryRecorder {
+    /// Nothing is recorded unless the transport is playing and the host reports where it is.
+    pub(crate) fn start_block(&mut self, transport: &Transport, sample_rate: f32) {
+        self.timing = match (transport.playing, transport.pos_beats(), transport.tempo) {
+            (true, Some(beat), Some(tempo)) => {
+                let numerator = transport.time_sig_numerator.unwrap_or(4) as f64;
+                let denominator = transport.time_sig_denominator.unwrap_or(4) as f64;
+                Some(BlockTiming {
+                    beat,
+                    beats_per_sample: tempo / 60.0 / sample_rate as f64,
+                    // hosts that don't say where the bar starts count bars from the first beat
+                    bar_start: transport.bar_start_pos_beats().unwrap_or(0.0),
+                    bar_number: transport.bar_number().unwrap_or(0) as i64,
+                    bar_beats: numerator * 4.0 / denominator,
+                })
+            }
+            _ => None,
+        };
+    }
+
+    pub(crate) fn process(&mut self, sample_id: usize, frame: [f32; 2], history: &PeakHistory) {
+        let Some(timing) = self.timing else {
+            return;
+        };
+        let beat = timing.beat + sample_id as f64 * timing.beats_per_sample;
+        // the block can run past the end of the bar it started in
+        let bars = (beat - timing.bar_start) / timing.bar_beats;
+        let bar = (timing.bar_number + bars.floor() as i64).rem_euclid(HISTORY_BARS as i64);
+        let step_in_bar = (bars.rem_euclid(1.0) * HISTORY_STEPS_PER_BAR as f64) as usize;
+        let step =
+            bar as usize * HISTORY_STEPS_PER_BAR + step_in_bar.min(HISTORY_STEPS_PER_BAR - 1);
+        if self.step != Some(step) {
+            self.flush(history);
+            // whatever was there is from `HISTORY_BARS` bars ago
+            for peak in &history.peaks[step] {
+                peak.store(0.0, Ordering::Relaxed);
+            }
+            history.position.store(step as i32, Ordering::Relaxed);
+            self.step = Some(step);
+        }
+        for (peak, side) in self.peak.iter_mut().zip(frame) {
+            *peak = f32::max(*peak, side.abs());
+        }
+    }
+
+    /// Publishes the current step's peak so far. Called at the end of every block.
+    pub(crate) fn finish_block(&mut self, history: &PeakHistory) {
+        self.flush(history);
+        if self.timing.is_none() {
+            self.step = None;
+            history.position.store(-1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&mut self, history: &PeakHistory) {
+        if let Some(step) = self.step {
+            for (published, peak) in history.peaks[step].iter().zip(self.peak) {
+                published.fetch_max(peak, Ordering::Relaxed);
+            }
+        }
+        self.peak = [0.0; 2];
+    }
+}
+
+/// How long processing takes as a fraction of the real time each block covers, published for the
+/// editor.
+#[derive(Default)]
+pub struct CpuLoad {
+    pub average: AtomicF32,
+    pub peak: AtomicF32,
+}
+
+/// Times each block on the audio thread and publishes the load over a window of about
+/// `CPU_WINDOW_SECONDS`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CpuMeter {
+    sample_rate: f32,
+    window_samples: usize,
+    window_seconds_busy: f64,
+    window_peak: f32,
+}
+
+impl CpuMeter {
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Records a block of `samples` samples that started processing at `started`.
+    pub(crate) fn process(&mut self, started: Instant, samples: usize, load: &CpuLoad) {
+        if samples == 0 || self.sample_rate <= 0.0 {
+            return;
+        }
+        let busy = started.elapsed().as_secs_f64();
+        let block_seconds = samples as f64 / self.sample_rate as f64;
+        self.window_peak = self.window_peak.max((busy / block_seconds) as f32);
+        self.window_seconds_busy += busy;
+        self.window_samples += samples;
+
+        if self.window_samples as f32 >= CPU_WINDOW_SECONDS * self.sample_rate {
+            let window_seconds = self.window_samples as f64 / self.sample_rate as f64;
+            load.average.store(
+                (self.window_seconds_busy / window_seconds) as f32,
+                Ordering::Relaxed,
+            );
+            load.peak.store(self.window_peak, Ordering::Relaxed);
+            self.window_samples = 0;
+            self.window_seconds_busy = 0.0;
+            self.window_peak = 0.0;
+        }
+    }
+}
+
+/// Measures a single hit as it plays: the maximum momentary loudness as per ITU-R BS.1770 (400 ms
+/// windows of K-weighted power, hopped every 100 ms), the 4x oversampled true peak, and how long
+/// it stays audible.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HitMeter {
+    sample_rate: f32,
+    shelf: Biquad,
+    highpass: Biquad,
+    true_peak: TruePeakDetector,
+
+    block_len: usize,
+    block_pos: usize,
+    block_sum: f64,
+    /// Mean square of the last four 100 ms blocks.
+    blocks: [f64; 4],
+    max_momentary: f64,
+
+    samples: u64,
+    last_audible: u64,
+    measuring: bool,
+}
+
+impl HitMeter {
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        (self.shelf, self.highpass) = k_weighting(sample_rate as f64);
+        self.block_len = (sample_rate * 0.1) as usize;
+        self.true_peak = TruePeakDetector::new();
+    }
+
+    /// Starts measuring a new hit, throwing away whatever was measured for the previous one.
+    pub(crate) fn start(&mut self) {
+        self.shelf.reset();
+        self.highpass.reset();
+        self.true_peak.reset();
+        self.block_pos = 0;
+        self.block_sum = 0.0;
+        self.blocks = [0.0; 4];
+        self.max_momentary = 0.0;
+        self.samples = 0;
+        self.last_audible = 0;
+        self.measuring = true;
+    }
+
+    pub(crate) fn stop(&mut self) {
+        self.measuring = false;
+    }
+
+    pub(crate) fn process(&mut self, sample: f32) {
+        if !self.measuring {
+            return;
+        }
+
+        let weighted = self.highpass.process(self.shelf.process(sample)) as f64;
+        self.block_sum += weighted * weighted;
+        self.block_pos += 1;
+        if self.block_pos >= self.block_len {
+            self.blocks.rotate_left(1);
+            self.blocks[3] = self.block_sum / self.block_len as f64;
+            self.block_pos = 0;
+            self.block_sum = 0.0;
+
+            let momentary = self.blocks.iter().sum::<f64>() / 4.0;
+            self.max_momentary = f64::max(self.max_momentary, momentary);
+        }
+
+        self.true_peak.process(sample);
+        if sample.abs() >= util::db_to_gain(AUDIBLE_THRESHOLD_DB) {
+            self.last_audible = self.samples;
+        }
+        self.samples += 1;
+    }
+
+    pub(crate) fn publish(&self, measurement: &HitMeasurement) {
+        let lufs = match self.max_momentary > 0.0 {
+            true => (-0.691 + 10.0 * self.max_momentary.log10()) as f32,
+            false => f32::NEG_INFINITY,
+        };
+        let true_peak_db = match self.true_peak.peak > 0.0 {
+            true => util::gain_to_db(self.true_peak.peak),
+            false => f32::NEG_INFINITY,
+        };
+        let duration = self.last_audible as f32 / self.sample_rate;
+        measurement.momentary_lufs.store(lufs, Ordering::Relaxed);
+        measurement
+            .true_peak_db
+            .store(true_peak_db, Ordering::Relaxed);
+        measurement.duration.store(duration, Ordering::Relaxed);
+    }
+}
+
+/// The two stage K-weighting prefilter from BS.1770, derived for an arbitrary sample rate.
+fn k_weighting(sample_rate: f64) -> (Biquad, Biquad) {
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = f64::tan(std::f64::consts::PI * f0 / sample_rate);
+    let vh = f64::powf(10.0, gain_db / 20.0);
+    let vb = f64::powf(vh, 0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            ((vh + vb * k / q + k * k) / a0) as f32,
+            (2.0 * (k * k - vh) / a0) as f32,
+            ((vh - vb * k / q + k * k) / a0) as f32,
+        ],
+        [
+            (2.0 * (k * k - 1.0) / a0) as f32,
+            ((1.0 - k / q + k * k) / a0) as f32,
+        ],
+    );
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = f64::tan(std::f64::consts::PI * f0 / sample_rate);
+    let a0 = 1.0 + k / q + k * k;
+    let highpass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [
+            (2.0 * (k * k - 1.0) / a0) as f32,
+            ((1.0 - k / q + k * k) / a0) as f32,
+        ],
+    );
+
+    (shelf, highpass)
+}
+
+/// Estimates inter-sample peaks by interpolating the signal with a windowed sinc.
+#[derive(Copy, Clone, Debug, Default)]
+struct TruePeakDetector {
+    coefficients: [[f32; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING],
+    history: [f32; TRUE_PEAK_TAPS],
+    peak: f32,
+}
+
+impl TruePeakDetector {
+    fn new() -> Self {
+        let center = (TRUE_PEAK_TAPS / 2) as f32;
+        let mut coefficients = [[0.0; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING];
+        for (phase, taps) in coefficients.iter_mut().enumerate() {
+            let fraction = phase as f32 / TRUE_PEAK_OVERSAMPLING as f32;
+            for (k, tap) in taps.iter_mut().enumerate() {
+                let t = k as f32 - center + fraction;
+                let window = match t.abs() < center {
+                    true => 0.5 * (1.0 + f32::cos(PI * t / center)),
+                    false => 0.0,
+                };
+                *tap = sinc(t) * window;
+            }
+        }
+        Self {
+            coefficients,
+            history: [0.0; TRUE_PEAK_TAPS],
+            peak: 0.0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.history = [0.0; TRUE_PEAK_TAPS];
+        self.peak = 0.0;
+    }
+
+    fn process(&mut self, sample: f32) {
+        self.history.rotate_right(1);
+        self.history[0] = sample;
+        for taps in &self.coefficients {
+            let interpolated: f32 = taps.iter().zip(&self.history).map(|(h, x)| h * x).sum();
+            self.peak = f32::max(self.peak, interpolated.abs());
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    match x == 0.0 {
+        true => 1.0,
+        false => {
+            let x = PI * x;
+            x.sin() / x
+        }
+    }
+}
+
+/// A recording of the most recent hit, shared with background analysis.
+#[derive(Clone, Debug, Default)]
+pub struct HitCapture {
+    pub samples: Vec<f32>,
+    pub sample_rate: f32,
+    /// Index of the first sample after the pitch sweep has settled.
+    pub sweep_end: usize,
+}
+
+/// Records a hit into a preallocated buffer on the audio thread.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HitRecorder {
+    samples: Vec<f32>,
+    sweep_end: usize,
+    recording: bool,
+}
+
+impl HitRecorder {
+    /// Reserves room for `capacity` samples. Recording stops once the buffer is full, so this
+    /// needs to be called before the recorder is used on the audio thread.
+    pub(crate) fn allocate(&mut self, capacity: usize) {
+        self.samples = Vec::with_capacity(capacity);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub(crate) fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub(crate) fn start(&mut self, sweep_end: usize) {
+        self.samples.clear();
+        self.sweep_end = sweep_end;
+        self.recording = true;
+    }
+
+    /// Returns whether the buffer is full.
+    pub(crate) fn process(&mut self, sample: f32) -> bool {
+        if self.recording && self.samples.len() < self.samples.capacity() {
+            self.samples.push(sample);
+        }
+        self.samples.len() >= self.samples.capacity()
+    }
+
+    /// Stops recording and hands the recording over to `capture`. Returns `false` if the capture
+    /// was locked by someone else, in which case this recording is dropped.
+    pub(crate) fn finish(&mut self, capture: &Mutex<HitCapture>, sample_rate: f32) -> bool {
+        self.recording = false;
+        let Ok(mut capture) = capture.try_lock() else {
+            return false;
+        };
+        // the capture was given the same capacity as the recorder, so this won't allocate
+        capture.samples.clear();
+        capture.samples.extend_from_slice(&self.samples);
+        capture.sample_rate = sample_rate;
+        capture.sweep_end = self.sweep_end;
+        true
+    }
+}
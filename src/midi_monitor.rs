@@ -0,0 +1,195 @@
+//! A log of the most recent MIDI events, for working out why a hit didn't trigger.
+
+use nih_plug::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// How many events the monitor keeps.
+pub const MIDI_LOG_LEN: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MidiKind {
+    NoteOn,
+    NoteOff,
+    PolyPressure,
+    ChannelPressure,
+    PitchBend,
+    Cc,
+    ProgramChange,
+}
+
+impl MidiKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            MidiKind::NoteOn => "Note On",
+            MidiKind::NoteOff => "Note Off",
+            MidiKind::PolyPressure => "Poly Pressure",
+            MidiKind::ChannelPressure => "Channel Pressure",
+            MidiKind::PitchBend => "Pitch Bend",
+            MidiKind::Cc => "CC",
+            MidiKind::ProgramChange => "Program Change",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MidiEntry {
+    pub kind: MidiKind,
+    /// Zero based, as nih_plug reports it.
+    pub channel: u8,
+    /// The note, CC, or program number, if the event has one.
+    pub number: Option<u8>,
+    /// Velocity, pressure, bend, or CC value, from 0 to 1 (-1 to 1 for pitch bends).
+    pub value: Option<f32>,
+    /// The sample within its block that the event landed on.
+    pub timing: u32,
+    pub received: Instant,
+}
+
+impl MidiEntry {
+    fn from_event<S>(event: &NoteEvent<S>, received: Instant) -> Option<Self> {
+        let entry = |kind, channel, number, value, timing| MidiEntry {
+            kind,
+            channel,
+            number,
+            value,
+            timing,
+            received,
+        };
+        Some(match *event {
+            NoteEvent::NoteOn {
+                timing,
+                channel,
+                note,
+                velocity,
+                ..
+            } => entry(
+                MidiKind::NoteOn,
+                channel,
+                Some(note),
+                Some(velocity),
+                timing,
+            ),
+            NoteEvent::NoteOff {
+                timing,
+                channel,
+                note,
+                velocity,
+                ..
+            } => entry(
+                MidiKind::NoteOff,
+                channel,
+                Some(note),
+                Some(velocity),
+                timing,
+            ),
+            NoteEvent::PolyPressure {
+                timing,
+                channel,
+                note,
+                pressure,
+                ..
+            } => entry(
+                MidiKind::PolyPressure,
+                channel,
+                Some(note),
+                Some(pressure),
+                timing,
+            ),
+            NoteEvent::MidiChannelPressure {
+                timing,
+                channel,
+                pressure,
+            } => entry(
+                MidiKind::ChannelPressure,
+                channel,
+                None,
+                Some(pressure),
+                timing,
+            ),
+            NoteEvent::MidiPitchBend {
+                timing,
+                channel,
+                value,
+            } => entry(
+                MidiKind::PitchBend,
+                channel,
+                None,
+                Some(value * 2.0 - 1.0),
+                timing,
+            ),
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc,
+                value,
+            } => entry(MidiKind::Cc, channel, Some(cc), Some(value), timing),
+            NoteEvent::MidiProgramChange {
+                timing,
+                channel,
+                program,
+            } => entry(
+                MidiKind::ProgramChange,
+                channel,
+                Some(program),
+                None,
+                timing,
+            ),
+            _ => return None,
+        })
+    }
+}
+
+/// The events seen by the audio thread, oldest first. Only filled in while the editor has the
+/// monitor open.
+#[derive(Debug, Default)]
+pub struct MidiLog {
+    pub enabled: AtomicBool,
+    entries: Mutex<MidiEntries>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct MidiEntries {
+    entries: [Option<MidiEntry>; MIDI_LOG_LEN],
+    /// Where the next entry goes, overwriting the oldest one once the log is full.
+    next: usize,
+}
+
+impl Default for MidiEntries {
+    fn default() -> Self {
+        Self {
+            entries: [None; MIDI_LOG_LEN],
+            next: 0,
+        }
+    }
+}
+
+impl MidiLog {
+    /// Records an event from the audio thread. Events are dropped rather than waiting on the
+    /// editor.
+    pub fn push<S>(&self, event: &NoteEvent<S>) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(entry) = MidiEntry::from_event(event, Instant::now()) else {
+            return;
+        };
+        if let Ok(mut entries) = self.entries.try_lock() {
+            let next = entries.next;
+            entries.entries[next] = Some(entry);
+            entries.next = (next + 1) % MIDI_LOG_LEN;
+        }
+    }
+
+    /// The logged events, oldest first.
+    pub fn entries(&self) -> Vec<MidiEntry> {
+        let entries = *self.entries.lock().unwrap();
+        let (newer, older) = entries.entries.split_at(entries.next);
+        older.iter().chain(newer).flatten().copied().collect()
+    }
+
+    pub fn clear(&self) {
+        *self.entries.lock().unwrap() = MidiEntries::default();
+    }
+}
@@ -0,0 +1,254 @@
+//! Morphing between two presets, recalling scenes, the patches in kit mode, and presets picked by
+//! MIDI program changes. The audio thread renders voices from values interpolated between
+//! snapshots instead of from the live parameters, so only continuous voice parameters morph, change
+//! with scenes or program changes, or differ between kit slots.
+//! Enums, toggles, and integer parameters keep their live values.
+
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::{ParamValue, PluginState};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::presets::Preset;
+
+/// Most parameters that can be morphed at once. Fixed so the tables can be copied around the audio
+/// thread without allocating.
+const MAX_MORPH_PARAMS: usize = 128;
+pub const SCENE_COUNT: usize = 8;
+/// Kit slots are looked up through a `SceneTable`, so there are as many of them as scenes.
+pub const KIT_SLOTS: usize = SCENE_COUNT;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MorphSlots {
+    pub a: Option<Snapshot>,
+    pub b: Option<Snapshot>,
+    /// Parameter addresses change between sessions, so this is rebuilt with `update_table` rather
+    /// than saved.
+    #[serde(skip)]
+    pub table: MorphTable,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Scenes {
+    pub slots: [Option<Snapshot>; SCENE_COUNT],
+    /// Rebuilt with `update_table` like `MorphSlots::table`.
+    #[serde(skip)]
+    pub table: SceneTable,
+}
+
+/// The patches played by kit mode, one per slot.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Kit {
+    pub slots: [Option<Snapshot>; KIT_SLOTS],
+    /// Each slot's choke group, where 0 is none. Playing a slot cuts off the others in its group.
+    #[serde(default)]
+    pub choke_groups: [u8; KIT_SLOTS],
+    /// Notes learned to play each slot, instead of its note counting up from Kit Base Note.
+    #[serde(default)]
+    pub notes: [Option<u8>; KIT_SLOTS],
+    /// Rebuilt with `update_table` like `MorphSlots::table`.
+    #[serde(skip)]
+    pub table: SceneTable,
+}
+
+/// The float parameter values of a preset or of the plugin at some point.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    /// Plain values by parameter ID.
+    pub values: BTreeMap<String, f32>,
+}
+
+impl Snapshot {
+    pub fn from_state(name: String, state: &PluginState) -> Self {
+        Self {
+            name,
+            values: state
+                .params
+                .iter()
+                .filter_map(|(id, value)| match value {
+                    ParamValue::F32(value) => Some((id.clone(), *value)),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn from_preset(preset: &Preset) -> Self {
+        Self::from_state(preset.name.clone(), &preset.state)
+    }
+}
+
+/// The start and end values of every parameter being morphed, sorted by the parameter's address.
+#[derive(Copy, Clone, Debug)]
+pub struct MorphTable {
+    entries: [(usize, f32, f32); MAX_MORPH_PARAMS],
+    len: usize,
+}
+
+impl Default for MorphTable {
+    fn default() -> Self {
+        Self {
+            entries: [(0, 0.0, 0.0); MAX_MORPH_PARAMS],
+            len: 0,
+        }
+    }
+}
+
+impl MorphTable {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The value of `param` at `amount` of the way from slot A to slot B, or `None` if it isn't
+    /// being morphed.
+    pub fn value(&self, param: &FloatParam, amount: f32) -> Option<f32> {
+        let address = param as *const FloatParam as usize;
+        let entries = &self.entries[..self.len];
+        let index = entries
+            .binary_search_by_key(&address, |(entry, ..)| *entry)
+            .ok()?;
+        let (_, a, b) = entries[index];
+        Some(a + (b - a) * amount)
+    }
+}
+
+/// Every scene's value for each float parameter, sorted by the parameter's address. Values missing
+/// from a scene are NaN.
+#[derive(Copy, Clone, Debug)]
+pub struct SceneTable {
+    addresses: [usize; MAX_MORPH_PARAMS],
+    values: [[f32; MAX_MORPH_PARAMS]; SCENE_COUNT],
+    filled: [bool; SCENE_COUNT],
+    len: usize,
+}
+
+impl Default for SceneTable {
+    fn default() -> Self {
+        Self {
+            addresses: [0; MAX_MORPH_PARAMS],
+            values: [[f32::NAN; MAX_MORPH_PARAMS]; SCENE_COUNT],
+            filled: [false; SCENE_COUNT],
+            len: 0,
+        }
+    }
+}
+
+impl SceneTable {
+    fn new(slots: &[Option<Snapshot>; SCENE_COUNT], params: &impl Params, exclude: &str) -> Self {
+        let mut table = SceneTable::default();
+        for (id, address) in float_params(params, exclude) {
+            table.addresses[table.len] = address;
+            for (scene, slot) in slots.iter().enumerate() {
+                if let Some(value) = slot.as_ref().and_then(|slot| slot.values.get(&id)) {
+                    table.values[scene][table.len] = *value;
+                }
+            }
+            table.len += 1;
+        }
+        for (filled, slot) in table.filled.iter_mut().zip(slots) {
+            *filled = slot.is_some();
+        }
+        table
+    }
+
+    /// A table with `snapshot` as its first scene and the rest empty.
+    pub fn from_snapshot(snapshot: Snapshot, params: &impl Params, exclude: &str) -> Self {
+        let mut slots: [Option<Snapshot>; SCENE_COUNT] = Default::default();
+        slots[0] = Some(snapshot);
+        Self::new(&slots, params, exclude)
+    }
+
+    pub fn is_filled(&self, scene: usize) -> bool {
+        self.filled.get(scene).copied().unwrap_or(false)
+    }
+
+    pub fn value(&self, param: &FloatParam, scene: usize) -> Option<f32> {
+        let address = param as *const FloatParam as usize;
+        let index = self.addresses[..self.len].binary_search(&address).ok()?;
+        Some(self.values[scene][index]).filter(|value| !value.is_nan())
+    }
+}
+
+/// Where a voice's parameters come from this sample, on top of the live parameter values.
+pub struct Overrides<'a> {
+    /// The preset picked by a program change that the editor hasn't loaded yet, as its first
+    /// scene. Takes the place of the live values.
+    pub program: &'a SceneTable,
+    pub morph: &'a MorphTable,
+    pub morph_amount: f32,
+    pub scenes: &'a SceneTable,
+    pub scene: Option<usize>,
+    /// The scene being faded away from, or `None` for the live or morphed values.
+    pub previous_scene: Option<usize>,
+    /// How far the fade from `previous_scene` to `scene` has gotten, from 0 to 1.
+    pub scene_fade: f32,
+}
+
+impl Overrides<'_> {
+    pub fn value(&self, param: &FloatParam, live: f32) -> f32 {
+        let live = self.program.value(param, 0).unwrap_or(live);
+        let base = self.morph.value(param, self.morph_amount).unwrap_or(live);
+        let scene_value = |scene: Option<usize>| {
+            scene
+                .and_then(|scene| self.scenes.value(param, scene))
+                .unwrap_or(base)
+        };
+        let target = scene_value(self.scene);
+        match self.scene_fade >= 1.0 {
+            true => target,
+            false => {
+                let from = scene_value(self.previous_scene);
+                from + (target - from) * self.scene_fade
+            }
+        }
+    }
+}
+
+impl MorphSlots {
+    /// Matches the slots up with the float parameters in `params`, skipping `exclude`. Needs to be
+    /// called whenever the slots change or the parameters are restored.
+    pub fn update_table(&mut self, params: &impl Params, exclude: &str) {
+        let mut table = MorphTable::default();
+        if let (Some(a), Some(b)) = (&self.a, &self.b) {
+            for (id, address) in float_params(params, exclude) {
+                let (Some(a), Some(b)) = (a.values.get(&id), b.values.get(&id)) else {
+                    continue;
+                };
+                table.entries[table.len] = (address, *a, *b);
+                table.len += 1;
+            }
+        }
+        self.table = table;
+    }
+}
+
+impl Scenes {
+    /// Like `MorphSlots::update_table`.
+    pub fn update_table(&mut self, params: &impl Params, exclude: &str) {
+        self.table = SceneTable::new(&self.slots, params, exclude);
+    }
+}
+
+impl Kit {
+    /// Like `MorphSlots::update_table`.
+    pub fn update_table(&mut self, params: &impl Params, exclude: &str) {
+        self.table = SceneTable::new(&self.slots, params, exclude);
+    }
+}
+
+/// The IDs and addresses of up to `MAX_MORPH_PARAMS` float parameters, sorted by address so the
+/// tables can be binary searched.
+fn float_params(params: &impl Params, exclude: &str) -> Vec<(String, usize)> {
+    let mut float_params: Vec<_> = params
+        .param_map()
+        .into_iter()
+        .filter_map(|(id, ptr, _)| match ptr {
+            ParamPtr::FloatParam(ptr) if id != exclude => Some((id, ptr as usize)),
+            _ => None,
+        })
+        .take(MAX_MORPH_PARAMS)
+        .collect();
+    float_params.sort_by_key(|(_, address)| *address);
+    float_params
+}
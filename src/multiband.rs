@@ -0,0 +1,83 @@
+use core::f32;
+
+use crate::filter::Biquad;
+
+/// Splits the signal in two with a fourth order Linkwitz-Riley crossover and drives each band on its
+/// own. The two bands stay in phase with each other, so they sum back together flat.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Multiband {
+    sample_rate: f32,
+    /// The frequency the filters were last tuned to.
+    crossover: f32,
+    lowpass: [Biquad; 2],
+    highpass: [Biquad; 2],
+    /// The phase shift of the two bands summed back together, for lining up the dry signal.
+    allpass: Biquad,
+}
+
+/// The drive and level of one band, as gains.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Band {
+    pub drive: f32,
+    pub level: f32,
+}
+
+impl Band {
+    fn process(self, sample: f32) -> f32 {
+        // no drive leaves the band clean rather than gently clipped
+        let driven = match self.drive > 1.0 {
+            true => f32::tanh(self.drive * sample),
+            false => sample,
+        };
+        self.level * driven
+    }
+}
+
+impl Multiband {
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.crossover = 0.0;
+        for filter in self.lowpass.iter_mut().chain(&mut self.highpass) {
+            filter.reset();
+        }
+        self.allpass.reset();
+    }
+
+    pub(crate) fn process(&mut self, sample: f32, crossover: f32, low: Band, high: Band) -> f32 {
+        if crossover != self.crossover {
+            self.crossover = crossover;
+            let lowpass = Biquad::lowpass(crossover, f32::consts::FRAC_1_SQRT_2, self.sample_rate);
+            let highpass =
+                Biquad::highpass(crossover, f32::consts::FRAC_1_SQRT_2, self.sample_rate);
+            for filter in &mut self.lowpass {
+                filter.set_coefficients(&lowpass);
+            }
+            for filter in &mut self.highpass {
+                filter.set_coefficients(&highpass);
+            }
+            // the bands sum to a second order allpass with the same corner as the crossover
+            self.allpass.set_coefficients(&Biquad::allpass(
+                crossover,
+                f32::consts::FRAC_1_SQRT_2,
+                self.sample_rate,
+            ));
+        }
+
+        let lows = self
+            .lowpass
+            .iter_mut()
+            .fold(sample, |sample, filter| filter.process(sample));
+        let highs = self
+            .highpass
+            .iter_mut()
+            .fold(sample, |sample, filter| filter.process(sample));
+        low.process(lows) + high.process(highs)
+    }
+
+    /// Shifts the phase of `dry` the same way splitting it into bands and summing them back would,
+    /// so it can be mixed with the output without cancelling around the crossover. Goes along with
+    /// each call to `process`.
+    pub(crate) fn align_dry(&mut self, dry: f32) -> f32 {
+        self.allpass.process(dry)
+    }
+}
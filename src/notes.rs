@@ -0,0 +1,38 @@
+/// Most notes that can be held down at once and still be fallen back to.
+const MAX_HELD_NOTES: usize = 16;
+
+/// The notes being held down, oldest first, for last note priority.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct NoteStack {
+    notes: [u8; MAX_HELD_NOTES],
+    len: usize,
+}
+
+impl NoteStack {
+    /// The most recently pressed note that is still held.
+    pub(crate) fn top(&self) -> Option<u8> {
+        self.notes[..self.len].last().copied()
+    }
+
+    /// Moves `note` to the top. The oldest note is forgotten when the stack is full.
+    pub(crate) fn push(&mut self, note: u8) {
+        self.remove(note);
+        if self.len == MAX_HELD_NOTES {
+            self.notes.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.notes[self.len] = note;
+        self.len += 1;
+    }
+
+    pub(crate) fn remove(&mut self, note: u8) {
+        if let Some(index) = self.notes[..self.len].iter().position(|&held| held == note) {
+            self.notes.copy_within(index + 1..self.len, index);
+            self.len -= 1;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Mutex;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Messages waiting for the editor to show them. Never touched from the audio thread.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    queue: Mutex<Vec<Notification>>,
+}
+
+impl Notifications {
+    pub fn push(&self, severity: Severity, message: String) {
+        self.queue
+            .lock()
+            .unwrap()
+            .push(Notification { severity, message });
+    }
+
+    pub fn take(&self) -> Vec<Notification> {
+        std::mem::take(&mut *self.queue.lock().unwrap())
+    }
+}
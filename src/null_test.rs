@@ -0,0 +1,55 @@
+//! The null test: the recorded reference kick played back upside down under each hit, so that
+//! whatever is left over is how far the patch is from the reference.
+
+/// Plays back the inverted reference on the audio thread.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct NullTest {
+    reference: Vec<f32>,
+    /// Samples left before the reference starts, so it lines up with hits held back by the
+    /// latency.
+    wait: usize,
+    /// The next sample to play, or `None` when the reference isn't playing.
+    position: Option<usize>,
+}
+
+impl NullTest {
+    /// Reserves room for `capacity` samples, which is as long as a reference recording can get.
+    pub(crate) fn allocate(&mut self, capacity: usize) {
+        self.reference = Vec::with_capacity(capacity);
+        self.position = None;
+    }
+
+    /// Replaces the reference with a new recording, cut short rather than allocating.
+    pub(crate) fn load(&mut self, samples: &[f32]) {
+        let len = usize::min(samples.len(), self.reference.capacity());
+        self.reference.clear();
+        self.reference.extend_from_slice(&samples[..len]);
+        self.position = None;
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// Starts the reference over after `delay` samples.
+    pub(crate) fn start(&mut self, delay: usize) {
+        if !self.reference.is_empty() {
+            self.wait = delay;
+            self.position = Some(0);
+        }
+    }
+
+    /// The next sample of the reference, already inverted.
+    pub(crate) fn next(&mut self) -> f32 {
+        let Some(position) = self.position else {
+            return 0.0;
+        };
+        if self.wait > 0 {
+            self.wait -= 1;
+            return 0.0;
+        }
+        let sample = self.reference.get(position).copied().unwrap_or(0.0);
+        self.position = Some(position + 1).filter(|&next| next < self.reference.len());
+        -sample
+    }
+}
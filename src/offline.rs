@@ -0,0 +1,33 @@
+use crate::{Voice, VoiceParams};
+
+/// Renders a single hit from `params` without touching any live state. The hit is released once
+/// its attack, hold, and decay stages have played out, and rendering stops when the amp envelope
+/// finishes or after `max_seconds`, whichever comes first.
+pub(crate) fn render_hit(params: &VoiceParams, sample_rate: f32, max_seconds: f32) -> Vec<f32> {
+    let env = params.amp_env;
+    let release_at = ((env.attack + env.hold + env.decay) * sample_rate) as usize;
+    let max_samples = (max_seconds * sample_rate) as usize;
+
+    let mut voice = Voice::default();
+    voice.set_sample_rate(sample_rate);
+    voice.trigger(params.phase_offset, 0);
+
+    let mut samples = Vec::with_capacity(max_samples);
+    for i in 0..max_samples {
+        if i == release_at {
+            voice.release();
+        }
+        if !voice.is_active() {
+            break;
+        }
+        let [left, right] = voice.render(params);
+        samples.push(0.5 * (left + right));
+    }
+    samples
+}
+
+pub(crate) fn peak(samples: &[f32]) -> f32 {
+    samples
+        .iter()
+        .fold(0.0, |peak, sample| f32::max(peak, sample.abs()))
+}
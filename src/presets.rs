@@ -0,0 +1,383 @@
+use base64::Engine;
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::import;
+
+const FACTORY_PRESETS: &[&str] = &[
+    include_str!("../presets/factory/techno_thump.json"),
+    include_str!("../presets/factory/house_punch.json"),
+    include_str!("../presets/factory/tight_click.json"),
+    include_str!("../presets/factory/dub_boom.json"),
+    include_str!("../presets/factory/trap_808.json"),
+    include_str!("../presets/factory/hardstyle_base.json"),
+];
+
+/// Marks text on the clipboard as one of our patches, and leaves room for changing the encoding.
+const PATCH_CODE_PREFIX: &str = "bhtk1:";
+/// Like `PATCH_CODE_PREFIX`, for a single section of a patch.
+const SECTION_CODE_PREFIX: &str = "bhtk-section1:";
+
+/// Persisted fields that describe the plugin instance rather than the patch, and thus are left out
+/// of saved presets.
+const NON_PATCH_FIELDS: &[&str] = &[
+    "editor-state",
+    "settings",
+    "morph",
+    "scenes",
+    "kit",
+    "locks",
+];
+
+/// Parameters that belong to the plugin instance rather than the patch. They're left out of saved
+/// presets and keep their values when a preset is loaded.
+pub(crate) const NON_PATCH_PARAMS: &[&str] = &["output_trim"];
+
+/// Sections of the patch that can be taken from a preset on their own, with the IDs of their
+/// parameters, matched by prefix. The first match wins, and parameters that don't match any go in
+/// `OTHER_GROUP`.
+pub(crate) const PARAM_GROUPS: &[(&str, &[&str])] = &[
+    (
+        "Amp Envelope",
+        &["amp_env", "anti_click", "live_envelopes", "length"],
+    ),
+    (
+        "Pitch Envelope",
+        &["pitch_env", "pitch_lag", "link_decays", "pitch_sync"],
+    ),
+    (
+        "Oscillator",
+        &[
+            "engine",
+            "start_",
+            "end_freq",
+            "snap_end_freq",
+            "sweep",
+            "phase_",
+            "align_peak",
+            "key_follow_center",
+            "level_key_follow",
+            "membrane_",
+            "strike_position",
+        ],
+    ),
+    (
+        "Voice",
+        &[
+            "voice_",
+            "click",
+            "attack_click",
+            "body_",
+            "crackle",
+            "reverse",
+        ],
+    ),
+    (
+        "Velocity",
+        &[
+            "velocity_",
+            "fixed_velocity",
+            "dynamic_range",
+            "key_sweep_time",
+        ],
+    ),
+    (
+        "Output and FX",
+        &[
+            "output_gain",
+            "analog_clip",
+            "fx_mix",
+            "dry_output",
+            "subsonic",
+            "multiband",
+            "auto_level",
+        ],
+    ),
+];
+pub(crate) const OTHER_GROUP: &str = "Other";
+/// The group for the patch's persisted fields that aren't part of another group.
+pub(crate) const FIELDS_GROUP: &str = "Expression and Rules";
+
+/// Identifies the contents of a preset directory, so changes can be noticed without parsing every
+/// preset again.
+pub type DirFingerprint = Vec<(PathBuf, Option<SystemTime>)>;
+
+/// A patch along with what's needed to find it again in the browser.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    pub state: PluginState,
+    /// Where a user preset was loaded from. Factory presets don't have one.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Preset {
+    /// Whether this preset should be listed for a case insensitive search `query`, optionally
+    /// restricted to presets carrying `tag`.
+    pub fn matches(&self, query: &str, tag: Option<&str>) -> bool {
+        if let Some(tag) = tag {
+            if !self
+                .tags
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(tag))
+            {
+                return false;
+            }
+        }
+
+        let query = query.trim().to_lowercase();
+        query.is_empty()
+            || [&self.name, &self.author, &self.description]
+                .iter()
+                .any(|field| field.to_lowercase().contains(&query))
+            || self
+                .tags
+                .iter()
+                .any(|tag| tag.to_lowercase().contains(&query))
+    }
+
+    /// Captures the current patch, leaving out anything that belongs to the plugin instance.
+    pub fn from_state(mut state: PluginState) -> Self {
+        state
+            .fields
+            .retain(|key, _| !NON_PATCH_FIELDS.contains(&key.as_str()));
+        state
+            .params
+            .retain(|id, _| !NON_PATCH_PARAMS.contains(&id.as_str()));
+        Self {
+            name: String::new(),
+            author: String::new(),
+            tags: Vec::new(),
+            description: String::new(),
+            state,
+            path: None,
+        }
+    }
+
+    /// Describes how this preset's plugin version differs from the running one, if it does.
+    pub fn version_mismatch(&self) -> Option<String> {
+        let version = env!("CARGO_PKG_VERSION");
+        (self.state.version != version).then(|| {
+            format!(
+                "{} was saved with version {} and may sound different in {version}",
+                self.name, self.state.version
+            )
+        })
+    }
+
+    /// A copy of the preset's state that can be handed to the host.
+    pub fn to_state(&self) -> PluginState {
+        clone_state(&self.state)
+    }
+}
+
+pub(crate) fn factory_presets() -> Vec<Preset> {
+    FACTORY_PRESETS
+        .iter()
+        .filter_map(|json| match serde_json::from_str(json) {
+            Ok(preset) => Some(preset),
+            Err(err) => {
+                nih_error!("Could not parse factory preset: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Encodes the patch part of `state` as a single line of text that survives being pasted into a
+/// chat message.
+pub(crate) fn encode_patch(state: PluginState) -> Result<String, String> {
+    let preset = Preset::from_state(state);
+    let json = serde_json::to_string(&preset.state).map_err(|err| err.to_string())?;
+    let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json);
+    Ok(format!("{PATCH_CODE_PREFIX}{encoded}"))
+}
+
+/// The inverse of `encode_patch`. Surrounding whitespace is ignored.
+pub(crate) fn decode_patch(code: &str) -> Result<PluginState, String> {
+    let encoded = code
+        .trim()
+        .strip_prefix(PATCH_CODE_PREFIX)
+        .ok_or_else(|| String::from("not a patch"))?;
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|err| err.to_string())?;
+    serde_json::from_slice(&json).map_err(|err| err.to_string())
+}
+
+fn is_preset_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+pub(crate) fn dir_fingerprint(dir: &Path) -> DirFingerprint {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut fingerprint = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_preset_file(&entry.path()))
+        .map(|entry| {
+            let modified = entry.metadata().and_then(|meta| meta.modified()).ok();
+            (entry.path(), modified)
+        })
+        .collect::<Vec<_>>();
+    fingerprint.sort();
+    fingerprint
+}
+
+/// Loads every preset in `dir`, sorted by name. Files that can't be read are skipped, and passed to
+/// `on_error` along with why.
+pub(crate) fn scan_dir(dir: &Path, mut on_error: impl FnMut(&Path, String)) -> Vec<Preset> {
+    let mut presets = dir_fingerprint(dir)
+        .into_iter()
+        .filter_map(|(path, _)| match load(&path) {
+            Ok(preset) => Some(preset),
+            Err(err) => {
+                nih_warn!("Could not load preset {}: {err}", path.display());
+                on_error(&path, err);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    presets.sort_by_key(|preset| preset.name.to_lowercase());
+    presets
+}
+
+pub(crate) fn load(path: &Path) -> Result<Preset, String> {
+    let json = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    // anything that isn't one of our own presets might still be a patch we know how to import
+    let mut preset = match serde_json::from_str::<Preset>(&json) {
+        Ok(preset) => preset,
+        Err(err) => import::import_generic(&json).map_err(|_| err.to_string())?,
+    };
+    preset.path = Some(path.to_owned());
+    Ok(preset)
+}
+
+/// Writes `preset` to `dir` under a file name derived from its name, returning the path it was
+/// written to.
+pub(crate) fn save(preset: &Preset, dir: &Path) -> Result<PathBuf, String> {
+    let file_name = preset
+        .name
+        .chars()
+        .map(|c| match c.is_alphanumeric() || c == '-' || c == '_' {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect::<String>();
+    if file_name.is_empty() {
+        return Err(String::from("presets need a name"));
+    }
+
+    let path = dir.join(file_name).with_extension("json");
+    let json = serde_json::to_string_pretty(preset).map_err(|err| err.to_string())?;
+    std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    std::fs::write(&path, json).map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+/// Every tag used by `presets`, lowercased, sorted, and without duplicates.
+pub(crate) fn all_tags<'a>(presets: impl Iterator<Item = &'a Preset>) -> Vec<String> {
+    let mut tags = presets
+        .flat_map(|preset| preset.tags.iter().map(|tag| tag.to_lowercase()))
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Which of `PARAM_GROUPS` the parameter with ID `id` belongs to.
+pub(crate) fn param_group(id: &str) -> &'static str {
+    PARAM_GROUPS
+        .iter()
+        .find(|(_, prefixes)| prefixes.iter().any(|prefix| id.starts_with(prefix)))
+        .map_or(OTHER_GROUP, |(name, _)| name)
+}
+
+/// Which of `PARAM_GROUPS` the persisted field `key` belongs to, or `FIELDS_GROUP`.
+pub(crate) fn field_group(key: &str) -> &'static str {
+    match key {
+        "pitch-breakpoints" => "Pitch Envelope",
+        "velocity-curve" => "Velocity",
+        _ => FIELDS_GROUP,
+    }
+}
+
+/// A section of a patch, copied on its own.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SectionPatch {
+    /// One of `PARAM_GROUPS` or `FIELDS_GROUP`.
+    pub section: String,
+    pub state: PluginState,
+}
+
+/// Encodes the parameters and fields in `section` of `state` like `encode_patch` does.
+pub(crate) fn encode_section(section: &str, state: PluginState) -> Result<String, String> {
+    let mut state = Preset::from_state(state).state;
+    state.params.retain(|id, _| param_group(id) == section);
+    state.fields.retain(|key, _| field_group(key) == section);
+    let section = SectionPatch {
+        section: section.to_owned(),
+        state,
+    };
+    let json = serde_json::to_string(&section).map_err(|err| err.to_string())?;
+    let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json);
+    Ok(format!("{SECTION_CODE_PREFIX}{encoded}"))
+}
+
+pub(crate) fn is_section_code(code: &str) -> bool {
+    code.trim().starts_with(SECTION_CODE_PREFIX)
+}
+
+pub(crate) fn decode_section(code: &str) -> Result<SectionPatch, String> {
+    let encoded = code
+        .trim()
+        .strip_prefix(SECTION_CODE_PREFIX)
+        .ok_or_else(|| String::from("not a section"))?;
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|err| err.to_string())?;
+    serde_json::from_slice(&json).map_err(|err| err.to_string())
+}
+
+pub(crate) fn same_value(a: &ParamValue, b: &ParamValue) -> bool {
+    match (a, b) {
+        (ParamValue::F32(a), ParamValue::F32(b)) => a == b,
+        (ParamValue::I32(a), ParamValue::I32(b)) => a == b,
+        (ParamValue::Bool(a), ParamValue::Bool(b)) => a == b,
+        (ParamValue::String(a), ParamValue::String(b)) => a == b,
+        _ => false,
+    }
+}
+
+pub(crate) fn clone_state(state: &PluginState) -> PluginState {
+    PluginState {
+        version: state.version.clone(),
+        params: state
+            .params
+            .iter()
+            .map(|(id, value)| (id.clone(), clone_value(value)))
+            .collect(),
+        fields: state.fields.clone(),
+    }
+}
+
+pub(crate) fn clone_value(value: &ParamValue) -> ParamValue {
+    match value {
+        ParamValue::F32(value) => ParamValue::F32(*value),
+        ParamValue::I32(value) => ParamValue::I32(*value),
+        ParamValue::Bool(value) => ParamValue::Bool(*value),
+        ParamValue::String(value) => ParamValue::String(value.clone()),
+    }
+}
@@ -0,0 +1,147 @@
+//! The reverse swell: the current kick rendered in the background, flipped around, and played so
+//! that it ends right as a hit starts.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::{offline, VoiceParams};
+
+/// How much of the swell's start is faded in, so cutting the hit's tail short doesn't click.
+const SWELL_FADE_SECONDS: f32 = 0.005;
+
+/// A freshly rendered swell, waiting for the audio thread to pick it up.
+#[derive(Debug, Default)]
+pub struct SwellRender {
+    pub samples: Mutex<Vec<f32>>,
+    pub ready: AtomicBool,
+    pub pending: AtomicBool,
+}
+
+/// Renders the first `length` seconds of a hit backwards. Runs in the background.
+pub(crate) fn render_swell(params: &VoiceParams, sample_rate: f32, length: f32) -> Vec<f32> {
+    let len = (length * sample_rate) as usize;
+    let mut samples = offline::render_hit(params, sample_rate, length);
+    // hits shorter than the swell are padded so the swell still ends right on the next hit
+    samples.resize(len, 0.0);
+    let fade_len = usize::min((SWELL_FADE_SECONDS * sample_rate) as usize, len);
+    for (i, sample) in samples[len - fade_len..].iter_mut().rev().enumerate() {
+        *sample *= i as f32 / fade_len as f32;
+    }
+    samples.reverse();
+    samples
+}
+
+/// Plays back the swell on the audio thread.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Swell {
+    samples: Vec<f32>,
+    /// The next sample to play, or `None` when the swell isn't playing.
+    position: Option<usize>,
+    /// Samples of silence left before the swell starts.
+    wait: usize,
+}
+
+impl Swell {
+    /// Reserves room for `capacity` samples, which is as long as a swell can get.
+    pub(crate) fn allocate(&mut self, capacity: usize) {
+        self.samples = Vec::with_capacity(capacity);
+        self.position = None;
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// Copies in a finished render, if there is one and it isn't locked. Swells longer than the
+    /// allocated capacity are cut short rather than allocating.
+    pub(crate) fn update(&mut self, render: &SwellRender) {
+        if !render.ready.load(Ordering::Acquire) {
+            return;
+        }
+        let Ok(samples) = render.samples.try_lock() else {
+            return;
+        };
+        let len = usize::min(samples.len(), self.samples.capacity());
+        self.samples.clear();
+        self.samples
+            .extend_from_slice(&samples[samples.len() - len..]);
+        render.ready.store(false, Ordering::Release);
+    }
+
+    /// Starts the swell over from the beginning, timed so it ends `lead` samples from now. Swells
+    /// shorter than that wait before they start.
+    pub(crate) fn start(&mut self, lead: usize) {
+        self.position = Some(0);
+        self.wait = lead.saturating_sub(self.samples.len());
+    }
+
+    pub(crate) fn stop(&mut self) {
+        self.position = None;
+    }
+
+    pub(crate) fn next(&mut self) -> f32 {
+        let Some(position) = self.position else {
+            return 0.0;
+        };
+        if self.wait > 0 {
+            self.wait -= 1;
+            return 0.0;
+        }
+        let sample = self.samples.get(position).copied().unwrap_or(0.0);
+        self.position = Some(position + 1).filter(|&next| next < self.samples.len());
+        sample
+    }
+}
+
+/// Holds back the hits by the reported latency, so the swell can start before them.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DelayLine {
+    buffer: Vec<f32>,
+    write: usize,
+    delay: usize,
+    /// Samples until everything written so far has come back out.
+    tail: usize,
+}
+
+impl DelayLine {
+    /// Reserves room for delays of up to `max_delay` samples.
+    pub(crate) fn allocate(&mut self, max_delay: usize) {
+        self.buffer = vec![0.0; max_delay + 1];
+        self.write = 0;
+        self.tail = 0;
+    }
+
+    pub(crate) fn set_delay(&mut self, delay: usize) {
+        let delay = usize::min(delay, self.buffer.len().saturating_sub(1));
+        if delay != self.delay {
+            self.buffer.fill(0.0);
+            self.delay = delay;
+            self.tail = 0;
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tail == 0
+    }
+
+    /// Drops everything that hasn't come back out yet.
+    pub(crate) fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.tail = 0;
+    }
+
+    pub(crate) fn process(&mut self, sample: f32) -> f32 {
+        if self.delay == 0 {
+            return sample;
+        }
+        self.tail = match sample != 0.0 {
+            true => self.delay,
+            false => self.tail.saturating_sub(1),
+        };
+        let len = self.buffer.len();
+        self.buffer[self.write] = sample;
+        let read = (self.write + len - self.delay) % len;
+        self.write = (self.write + 1) % len;
+        self.buffer[read]
+    }
+}
@@ -0,0 +1,71 @@
+use core::f32;
+
+use crate::filter::Biquad;
+
+const OVERSAMPLING: usize = 4;
+/// Q of the two stages of a fourth order Butterworth lowpass.
+const BUTTERWORTH_Q: [f32; 2] = [0.541_196_1, 1.306_563];
+/// Offsets the curve a little so it adds some even harmonics, like a transformer does.
+const BIAS: f32 = 0.15;
+/// Drive at full Analog Clip.
+const MAX_DRIVE_DB: f32 = 18.0;
+const DC_BLOCKER_HZ: f32 = 10.0;
+
+/// A gentle tape and transformer style tanh saturator, run at `OVERSAMPLING` times the sample
+/// rate so driving it hard doesn't alias.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct AnalogClip {
+    sample_rate: f32,
+    upsample: [Biquad; 2],
+    downsample: [Biquad; 2],
+    dc_input: f32,
+    dc_output: f32,
+}
+
+impl AnalogClip {
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let oversampled_rate = sample_rate * OVERSAMPLING as f32;
+        let cutoff = 0.45 * sample_rate;
+        let lowpass = BUTTERWORTH_Q.map(|q| Biquad::lowpass(cutoff, q, oversampled_rate));
+        self.upsample = lowpass;
+        self.downsample = lowpass;
+        self.dc_input = 0.0;
+        self.dc_output = 0.0;
+    }
+
+    /// Saturates `sample` by `amount`, from 0 to 1. Quiet signals come out at the same level
+    /// whatever the amount.
+    pub(crate) fn process(&mut self, sample: f32, amount: f32) -> f32 {
+        let drive = f32::powf(10.0, amount * MAX_DRIVE_DB / 20.0);
+        // the slope of the curve at zero, which quiet signals get scaled by
+        let slope = drive * (1.0 - BIAS.tanh().powi(2));
+        let mut output = 0.0;
+        for i in 0..OVERSAMPLING {
+            // zero stuffing, made up for by the gain on the first sample
+            let stuffed = match i {
+                0 => sample * OVERSAMPLING as f32,
+                _ => 0.0,
+            };
+            let upsampled = self
+                .upsample
+                .iter_mut()
+                .fold(stuffed, |sample, stage| stage.process(sample));
+            let shaped = (f32::tanh(drive * upsampled + BIAS) - BIAS.tanh()) / slope;
+            let downsampled = self
+                .downsample
+                .iter_mut()
+                .fold(shaped, |sample, stage| stage.process(sample));
+            if i == 0 {
+                output = downsampled;
+            }
+        }
+
+        // the bias leaves an offset behind on loud hits
+        let coefficient = f32::exp(-f32::consts::TAU * DC_BLOCKER_HZ / self.sample_rate);
+        let blocked = output - self.dc_input + coefficient * self.dc_output;
+        self.dc_input = output;
+        self.dc_output = blocked;
+        blocked
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Preferences that belong to the user rather than the patch. Each instance keeps its own copy so
+/// projects reopen the way they were left, and new instances start from the saved defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub ui_scale: f32,
+    /// Overrides where user presets are stored and scanned.
+    pub preset_dir: Option<PathBuf>,
+    /// The MIDI note played by the editor's audition button.
+    pub audition_note: u8,
+    /// Makes the audition button hold its note until it's pressed again, so the sustain can be
+    /// heard.
+    pub audition_latch: bool,
+    pub theme: Theme,
+    /// Shows the performance page instead of the main page.
+    pub performance_mode: bool,
+    pub keyboard_steps: KeyboardSteps,
+    pub knob_drag: KnobDrag,
+    /// Scales how far knobs turn for a given drag or scroll.
+    pub knob_sensitivity: f32,
+    /// Shows what picking a preset would change before loading it.
+    pub preview_preset_changes: bool,
+}
+
+/// How dragging turns a knob. Either way the knob moves relative to where it was, rather than
+/// jumping to the pointer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KnobDrag {
+    /// Dragging up or right turns it up.
+    Linear,
+    /// Dragging around it turns it the same way.
+    Circular,
+}
+
+/// How far the arrow keys move a focused control, as fractions of its range.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyboardSteps {
+    pub fine: f32,
+    /// With Shift held.
+    pub coarse: f32,
+}
+
+impl Default for KeyboardSteps {
+    fn default() -> Self {
+        Self {
+            fine: 0.01,
+            coarse: 0.1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            preset_dir: None,
+            audition_note: 36,
+            audition_latch: false,
+            theme: Theme::Dark,
+            performance_mode: false,
+            keyboard_steps: KeyboardSteps::default(),
+            knob_drag: KnobDrag::Linear,
+            knob_sensitivity: 1.0,
+            preview_preset_changes: false,
+        }
+    }
+}
+
+impl Settings {
+    /// The defaults saved with `save_global`, or the built in ones if there aren't any.
+    pub fn load_global() -> Result<Self, String> {
+        let Some(path) = global_path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|err| format!("could not parse {}: {err}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("could not read {}: {err}", path.display())),
+        }
+    }
+
+    /// Makes these settings the defaults for new instances.
+    pub fn save_global(&self) -> Result<(), String> {
+        let path = global_path().ok_or_else(|| String::from("no settings folder"))?;
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(&path, json).map_err(|err| err.to_string())
+    }
+
+    pub fn preset_dir(&self) -> Option<PathBuf> {
+        self.preset_dir
+            .clone()
+            .or_else(|| data_dir().map(|dir| dir.join("presets")))
+    }
+}
+
+/// Where the plugin keeps its files across all instances.
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    let data_dir = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| {
+            PathBuf::from(home)
+                .join("Library")
+                .join("Application Support")
+        })
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })
+    };
+    data_dir.map(|dir| dir.join("but heres the kicker"))
+}
+
+fn global_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("settings.json"))
+}
@@ -0,0 +1,100 @@
+//! How each kind of parameter is smoothed, so that values of the same kind move the same way
+//! whichever parameter they belong to. Frequencies move evenly in octaves and gains evenly in
+//! decibels, since that's how they're heard, while times and everything else move linearly.
+//! Envelope times are smoothed too, but unless Live Envelopes is on, a hit only reads them once
+//! when it starts.
+
+use nih_plug::prelude::SmoothingStyle;
+
+const FREQUENCY_MS: f32 = 20.0;
+/// Longer than the rest, since jumps in level are the easiest to hear.
+const GAIN_MS: f32 = 50.0;
+const TIME_MS: f32 = 5.0;
+const AMOUNT_MS: f32 = 5.0;
+const MIX_MS: f32 = 20.0;
+
+/// Frequencies in Hz, and rates like the crackle density.
+pub(crate) fn frequency() -> SmoothingStyle {
+    SmoothingStyle::Logarithmic(FREQUENCY_MS)
+}
+
+/// Offsets in semitones. These are already logarithmic in frequency, so they're smoothed linearly
+/// over the same time as frequencies.
+pub(crate) fn pitch() -> SmoothingStyle {
+    SmoothingStyle::Linear(FREQUENCY_MS)
+}
+
+/// Gains stored as linear factors. Smoothing them logarithmically moves them evenly in decibels.
+pub(crate) fn gain() -> SmoothingStyle {
+    SmoothingStyle::Logarithmic(GAIN_MS)
+}
+
+/// Gains stored in decibels already.
+pub(crate) fn decibels() -> SmoothingStyle {
+    SmoothingStyle::Linear(GAIN_MS)
+}
+
+/// Times, and factors that scale times.
+pub(crate) fn time() -> SmoothingStyle {
+    SmoothingStyle::Linear(TIME_MS)
+}
+
+/// Amounts, shapes, and positions that don't fall under any of the other kinds.
+pub(crate) fn amount() -> SmoothingStyle {
+    SmoothingStyle::Linear(AMOUNT_MS)
+}
+
+/// Crossfades between two signals or sounds, which are slowed down so sweeping them doesn't
+/// zipper.
+pub(crate) fn mix() -> SmoothingStyle {
+    SmoothingStyle::Linear(MIX_MS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KickParams;
+    use nih_plug::prelude::{FloatParam, Smoother};
+    use nih_plug::util;
+
+    fn is_linear(param: &FloatParam, ms: f32) -> bool {
+        matches!(param.smoothed.style, SmoothingStyle::Linear(style_ms) if style_ms == ms)
+    }
+
+    fn is_logarithmic(param: &FloatParam, ms: f32) -> bool {
+        matches!(param.smoothed.style, SmoothingStyle::Logarithmic(style_ms) if style_ms == ms)
+    }
+
+    #[test]
+    fn frequencies_and_gains_move_evenly_on_their_scales() {
+        let params = KickParams::default();
+        assert!(is_logarithmic(&params.start_freq, 20.0));
+        assert!(is_logarithmic(&params.end_freq, 20.0));
+        assert!(is_logarithmic(&params.output_gain, 50.0));
+        assert!(is_logarithmic(&params.output_trim, 50.0));
+    }
+
+    #[test]
+    fn values_already_on_their_scales_move_linearly() {
+        let params = KickParams::default();
+        assert!(is_linear(&params.engine_a_tune, 20.0));
+        assert!(is_linear(&params.engine_a_tilt, 50.0));
+    }
+
+    #[test]
+    fn everything_else_moves_linearly() {
+        let params = KickParams::default();
+        assert!(is_linear(&params.amp_env.attack_time, 5.0));
+        assert!(is_linear(&params.fx_mix, 20.0));
+    }
+
+    #[test]
+    fn gains_pass_halfway_in_decibels_halfway_through() {
+        let smoother = Smoother::new(gain());
+        smoother.reset(util::db_to_gain(-40.0));
+        // 50 steps at 1 kHz
+        smoother.set_target(1000.0, 1.0);
+        let midpoint = smoother.next_step(25);
+        assert!((util::gain_to_db(midpoint) + 20.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,213 @@
+//! The subsonic filter on the output, a steep highpass that keeps rumble below the kick's
+//! fundamental off big systems.
+
+use core::f32;
+
+use crate::filter::Biquad;
+use crate::reverse::DelayLine;
+use crate::{SubsonicPhase, SubsonicSlope};
+
+pub(crate) const SUBSONIC_MIN_HZ: f32 = 20.0;
+pub(crate) const SUBSONIC_MAX_HZ: f32 = 40.0;
+/// Second order stages in the steepest slope.
+const MAX_STAGES: usize = 4;
+
+impl SubsonicSlope {
+    fn stages(self) -> usize {
+        match self {
+            SubsonicSlope::Db12 => 1,
+            SubsonicSlope::Db24 => 2,
+            SubsonicSlope::Db48 => 4,
+        }
+    }
+}
+
+/// One channel of the subsonic filter. The minimum phase version is a Butterworth highpass, and
+/// the linear phase version matches its slope at the cost of latency.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Subsonic {
+    sample_rate: f32,
+    /// The cutoff, slope, and phase the filter was last set up for.
+    settings: Option<(f32, SubsonicSlope, SubsonicPhase)>,
+    minimum: [Biquad; MAX_STAGES],
+    linear: [LinearStage; MAX_STAGES],
+    /// Samples until everything put into the linear phase filter so far has come back out.
+    tail: usize,
+}
+
+impl Subsonic {
+    /// Allocates room for the linear phase filter at its lowest cutoff.
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.settings = None;
+        let max_len = max_moving_average_len(sample_rate);
+        for stage in &mut self.linear {
+            stage.allocate(max_len);
+        }
+    }
+
+    /// Retunes the filter if any of its settings changed. Changing the linear phase filter clears
+    /// it, so this shouldn't be called with smoothed values.
+    pub(crate) fn configure(&mut self, cutoff: f32, slope: SubsonicSlope, phase: SubsonicPhase) {
+        let previous = self.settings.replace((cutoff, slope, phase));
+        if previous == self.settings {
+            return;
+        }
+        let stages = slope.stages();
+        match phase {
+            SubsonicPhase::Minimum => {
+                if previous.is_none_or(|(_, _, phase)| phase != SubsonicPhase::Minimum) {
+                    self.minimum.iter_mut().for_each(Biquad::reset);
+                }
+                let order = 2 * stages;
+                for (k, filter) in self.minimum[..stages].iter_mut().enumerate() {
+                    let angle = (2 * k + 1) as f32 * f32::consts::PI / (2 * order) as f32;
+                    let q = 0.5 / angle.cos();
+                    filter.set_coefficients(&Biquad::highpass(cutoff, q, self.sample_rate));
+                }
+            }
+            SubsonicPhase::Linear => {
+                let len = moving_average_len(cutoff, stages, self.sample_rate);
+                for stage in &mut self.linear[..stages] {
+                    stage.set_len(len);
+                }
+                self.tail = 0;
+            }
+        }
+    }
+
+    /// The most delay the linear phase filter can add at `sample_rate`, in samples.
+    pub(crate) fn max_latency(sample_rate: f32) -> usize {
+        MAX_STAGES * max_moving_average_len(sample_rate)
+    }
+
+    /// The delay the linear phase filter adds, in samples.
+    pub(crate) fn latency(&self) -> u32 {
+        match self.settings {
+            Some((_, slope, SubsonicPhase::Linear)) => {
+                (slope.stages() * self.linear[0].latency()) as u32
+            }
+            _ => 0,
+        }
+    }
+
+    /// Whether the linear phase filter has played out everything put into it.
+    pub(crate) fn is_settled(&self) -> bool {
+        self.tail == 0
+    }
+
+    pub(crate) fn process(&mut self, sample: f32) -> f32 {
+        let Some((_, slope, phase)) = self.settings else {
+            return sample;
+        };
+        // the linear phase filter's impulse response is twice its latency long
+        self.tail = match sample != 0.0 {
+            true => 2 * self.latency() as usize,
+            false => self.tail.saturating_sub(1),
+        };
+        let stages = slope.stages();
+        match phase {
+            SubsonicPhase::Minimum => self.minimum[..stages]
+                .iter_mut()
+                .fold(sample, |sample, filter| filter.process(sample)),
+            SubsonicPhase::Linear => self.linear[..stages]
+                .iter_mut()
+                .fold(sample, |sample, stage| stage.process(sample)),
+        }
+    }
+}
+
+/// How long the moving averages get at the lowest cutoff.
+fn max_moving_average_len(sample_rate: f32) -> usize {
+    (sample_rate / SUBSONIC_MIN_HZ).ceil() as usize
+}
+
+/// How long the moving averages in each linear phase stage need to be for `stages` of them to
+/// come to -3 dB at `cutoff` together.
+fn moving_average_len(cutoff: f32, stages: usize, sample_rate: f32) -> usize {
+    let stage_gain = f32::powf(f32::consts::FRAC_1_SQRT_2, (stages as f32).recip());
+    // two moving averages `x` cycles of the cutoff long pass sinc(x)^2, and the stage passes
+    // whatever they don't, which only rises from 0 to 1 between x = 0 and 1
+    let sinc = |x: f32| f32::sin(f32::consts::PI * x) / (f32::consts::PI * x);
+    let (mut low, mut high) = (1e-3, 1.0);
+    for _ in 0..32 {
+        let mid = 0.5 * (low + high);
+        match 1.0 - sinc(mid) * sinc(mid) < stage_gain {
+            true => low = mid,
+            false => high = mid,
+        }
+    }
+    usize::max((low * sample_rate / cutoff).round() as usize, 1)
+}
+
+/// A linear phase highpass, made by taking a lowpassed copy away from the signal delayed by as
+/// much as the lowpass delays it. The lowpass is two moving averages, so it's cheap however long
+/// they get.
+#[derive(Clone, Debug, Default)]
+struct LinearStage {
+    averages: [MovingAverage; 2],
+    delay: DelayLine,
+}
+
+impl LinearStage {
+    fn allocate(&mut self, max_len: usize) {
+        for average in &mut self.averages {
+            average.allocate(max_len);
+        }
+        self.delay.allocate(max_len);
+    }
+
+    fn set_len(&mut self, len: usize) {
+        for average in &mut self.averages {
+            average.set_len(len);
+        }
+        self.delay.set_delay(self.latency());
+        self.delay.clear();
+    }
+
+    /// The two moving averages each delay by `(len - 1) / 2` samples.
+    fn latency(&self) -> usize {
+        self.averages[0].len.saturating_sub(1)
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        let lows = self
+            .averages
+            .iter_mut()
+            .fold(sample, |sample, average| average.process(sample));
+        self.delay.process(sample) - lows
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct MovingAverage {
+    buffer: Vec<f32>,
+    len: usize,
+    position: usize,
+    /// Kept in double precision so the running sum doesn't drift.
+    sum: f64,
+}
+
+impl MovingAverage {
+    fn allocate(&mut self, max_len: usize) {
+        self.buffer = vec![0.0; max_len];
+        self.len = 0;
+    }
+
+    fn set_len(&mut self, len: usize) {
+        self.len = usize::min(len, self.buffer.len());
+        self.buffer.fill(0.0);
+        self.position = 0;
+        self.sum = 0.0;
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        if self.len == 0 {
+            return sample;
+        }
+        self.sum += sample as f64 - self.buffer[self.position] as f64;
+        self.buffer[self.position] = sample;
+        self.position = (self.position + 1) % self.len;
+        (self.sum / self.len as f64) as f32
+    }
+}